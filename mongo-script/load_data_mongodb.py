@@ -41,7 +41,7 @@ db["user"].insert_many([
         "_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9d9b"),
         "name": "Krzysztof",
         "surname": "Admin",
-        "account_balance": 0,
+        "account_balance": {"amount": 0, "currency": "PLN"},
         "blocked": False,
         "email": "krzysztof@admin.com",
         "password": "$2a$10$p0MGb/vlu.sjfHiCV.k02.Bq14NAigmWbNqDJ19R8h0UBGf.UwcZy", # admin123
@@ -51,7 +51,7 @@ db["user"].insert_many([
         "_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9d9c"),
         "name": "Jan",
         "surname": "Kowalski",
-        "account_balance": 1000,
+        "account_balance": {"amount": 100000, "currency": "PLN"},
         "blocked": False,
         "email": "jan.kowalski@example.com",
         "password": "$2a$10$v/cJp3VcYXwG.3xfUerXFOYoqBxn1Fc04wQuKug2Nm/ev46u.u8SS", # password123
@@ -61,7 +61,7 @@ db["user"].insert_many([
         "_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9d9d"),
         "name": "Adam",
         "surname": "Nowak",
-        "account_balance": 2000,
+        "account_balance": {"amount": 200000, "currency": "PLN"},
         "blocked": False,
         "email": "adam.nowak@example.com",
         "password": "$2a$10$YKYPj5.wk6lpG9b7PazFtO0WMB.D.QQZDnVj9r40Z1Ume5hRbpIQK", # secure123
@@ -71,7 +71,7 @@ db["user"].insert_many([
         "_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9d9e"),
         "name": "Anna",
         "surname": "Kowalska",
-        "account_balance": 3000,
+        "account_balance": {"amount": 300000, "currency": "PLN"},
         "blocked": False,
         "email": "anna.kowalska@example.com",
         "password": "$2a$10$YQRpqbv2gZjchpc4gymo3Ok92PpkEsPXgpco7cSoi/wrIgGBWSifu", # pass123
@@ -81,7 +81,7 @@ db["user"].insert_many([
         "_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9d9f"),
         "name": "Jan",
         "surname": "Nowak",
-        "account_balance": 4000,
+        "account_balance": {"amount": 400000, "currency": "PLN"},
         "blocked": False,
         "email": "jan.nowak@example.com",
         "password": "$2a$10$VfvVXxOo39T5zsJCfCXQ4uktwn9KF5OVvgZBSg/6458MuHRHqzZ9C", # test123
@@ -91,7 +91,7 @@ db["user"].insert_many([
         "_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9da0"),
         "name": "Paweł",
         "surname": "Kowalski",
-        "account_balance": 5000,
+        "account_balance": {"amount": 500000, "currency": "PLN"},
         "blocked": True,
         "email": "pawel.kowalski@example.com",
         "password": "$2a$10$Z9/xJYUZFPcS54UuAETIGu4RFsskaOEel9NOjL1kysrMyKp0cvjUm", # hello123
@@ -101,14 +101,48 @@ db["user"].insert_many([
         "_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9da1"),
         "name": "Grzegorz",
         "surname": "Witkiewicz",
-        "account_balance": 6000,
+        "account_balance": {"amount": 600000, "currency": "PLN"},
         "blocked": True,
         "email": "grzegorz.witkiewicz@example.com",
         "password": "$2a$10$jC6LzGqaX9RM32QdZj.hMeqcUtbnnZPBNkTRSfVH9oOHlax1ufkXu", # grze123
         "role": "User"
+    },
+    {
+        "_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9da2"),
+        "name": "Parking",
+        "surname": "Operator",
+        "account_balance": {"amount": 0, "currency": "PLN"},
+        "blocked": False,
+        "email": "operator@parking-os.com",
+        "password": "$2a$10$p0MGb/vlu.sjfHiCV.k02.Bq14NAigmWbNqDJ19R8h0UBGf.UwcZy", # admin123
+        "role": "Operator"
     }
 ])
 
+OPERATOR_ID = "5f9b3b9b9d9b9d9b9d9b9da2"
+
+def ledger_legs(from_id, to_id, money, kind, ticket_id, parking_lot_id, description, created_at, balances):
+    transfer_id = ObjectId()
+    legs = []
+    for account_id, amount in [(from_id, -money["amount"]), (to_id, money["amount"])]:
+        balance_after = None
+        if account_id in balances:
+            balances[account_id] += amount
+            balance_after = {"amount": balances[account_id], "currency": money["currency"]}
+        legs.append({
+            "_id": ObjectId(),
+            "transfer_id": transfer_id,
+            "account_id": account_id,
+            "kind": kind,
+            "amount": {"amount": amount, "currency": money["currency"]},
+            "balance_after": balance_after,
+            "ticket_id": ticket_id,
+            "parking_lot_id": parking_lot_id,
+            "description": description,
+            "created_at": created_at,
+        })
+    return legs
+
 db["vehicle"].insert_many([
     {"_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9d90"), "user_id": "5f9b3b9b9d9b9d9b9d9b9d9c", "type": "Car", "brand": "BMW", "model": "X5", "license_plate_number": "WAW12345"},
     {"_id": ObjectId("5f9b3b9b9d9b9d9b9d9b9d91"), "user_id": "5f9b3b9b9d9b9d9b9d9b9d9c", "type": "Car", "brand": "Mercedes", "model": "Actros", "license_plate_number": "WAW54321"},
@@ -120,22 +154,25 @@ db["vehicle"].insert_many([
 headers = { 'Content-Type': 'application/json' }
 data_parking_lot = [
     {
-        "costOfMaintenance": {"electricity": 400, "cleaning": 500, "security": 1000}, 
+        "costOfMaintenance": {"electricity": {"amount": 40000, "currency": "PLN"}, "cleaning": {"amount": 50000, "currency": "PLN"}, "security": {"amount": 100000, "currency": "PLN"}}, 
         "location": {"city": "Warszawa", "address": "Al. Jerozolimskie 54, 00-024 Warszawa", "latitude": 52.228668, "longitude": 21.003550}, 
         "levels": [{"cars": 10, "trucks": 5}, {"cars": 10, "trucks": 5}, {"cars": 10, "trucks": 5}, {"cars": 10, "trucks": 5}],
-        "tariffs": [{"minTime": 1, "maxTime": 8, "pricePerHour": 5.0}, {"minTime": 9, "maxTime": 100, "pricePerHour": 4.0}]
+        "operatorId": "5f9b3b9b9d9b9d9b9d9b9da2",
+        "tariffs": [{"minTime": 1, "maxTime": 8, "pricePerHour": {"amount": 500, "currency": "PLN"}}, {"minTime": 9, "maxTime": 100, "pricePerHour": {"amount": 400, "currency": "PLN"}}]
     },
     {
-        "costOfMaintenance": {"electricity": 400, "cleaning": 600, "security": 1300}, 
+        "costOfMaintenance": {"electricity": {"amount": 40000, "currency": "PLN"}, "cleaning": {"amount": 60000, "currency": "PLN"}, "security": {"amount": 130000, "currency": "PLN"}}, 
         "location": {"city": "Warszawa", "address": "ul. Hoża 84, 00-682 Warszawa", "latitude": 52.226170, "longitude": 21.013750}, 
         "levels": [{"cars": 15, "trucks": 5}, {"cars": 10, "trucks": 5}, {"cars": 5, "trucks": 2}],
-        "tariffs": [{"minTime": 1, "maxTime": 5, "pricePerHour": 4.0}, {"minTime": 6, "maxTime": 100, "pricePerHour": 3.0}]
+        "operatorId": "5f9b3b9b9d9b9d9b9d9b9da2",
+        "tariffs": [{"minTime": 1, "maxTime": 5, "pricePerHour": {"amount": 400, "currency": "PLN"}}, {"minTime": 6, "maxTime": 100, "pricePerHour": {"amount": 300, "currency": "PLN"}}]
     },
     {
-        "costOfMaintenance": {"electricity": 500, "cleaning": 700, "security": 1500}, 
+        "costOfMaintenance": {"electricity": {"amount": 50000, "currency": "PLN"}, "cleaning": {"amount": 70000, "currency": "PLN"}, "security": {"amount": 150000, "currency": "PLN"}}, 
         "location": {"city": "Warszawa", "address": "ul. Furmańska 14, 00-313 Warszawa", "latitude": 52.244260, "longitude": 21.019190}, 
         "levels": [{"cars": 7, "trucks": 3}, {"cars": 7, "trucks": 3}],
-        "tariffs": [{"minTime": 1, "maxTime": 100, "pricePerHour": 8.0}]
+        "operatorId": "5f9b3b9b9d9b9d9b9d9b9da2",
+        "tariffs": [{"minTime": 1, "maxTime": 100, "pricePerHour": {"amount": 800, "currency": "PLN"}}]
     },
 ]
 
@@ -178,7 +215,7 @@ for i in range(1000):
     start_timestamp = generate_random_timestamp_2023()
     end_timestamp = start_timestamp + int(timedelta(hours=random.randint(1, 10)).total_seconds())
     time_difference_hours = (end_timestamp - start_timestamp) / 3600
-    amount_paid = round(time_difference_hours * random.uniform(5, 7))
+    amount_paid = {"amount": round(time_difference_hours * random.uniform(5, 7) * 100), "currency": "PLN"}
 
     tickets.append({
         "_id": ObjectId(),
@@ -192,10 +229,37 @@ for i in range(1000):
         "spot_ordinal_number": random.randint(0, 4),
         "parking_lot_id": str(parking_lot_id),
         "code": f"CODE{i}",
+        "status": "Exited",
     })
 
 # Add the generated tickets to the "ticket" collection
 db["ticket"].insert_many(tickets)
 
+# Back the seeded balances with ledger entries: an opening balance covering
+# the seeded balance plus everything spent on tickets, followed by one charge
+# per ticket credited to the operator of the parking lot
+charged = {}
+for ticket in tickets:
+    charged[ticket["user_id"]] = charged.get(ticket["user_id"], 0) + ticket["amount_paid"]["amount"]
+
+balances = {str(user["_id"]): 0 for user in db["user"].find({})}
+opening_timestamp = int(datetime(2023, 1, 1).timestamp())
+transactions = []
+for user in db["user"].find({}):
+    opening = user["account_balance"]["amount"] + charged.get(str(user["_id"]), 0)
+    if opening > 0:
+        transactions += ledger_legs("system:external", str(user["_id"]), {"amount": opening, "currency": "PLN"},
+                                    "Adjustment", None, None, "Opening balance", opening_timestamp, balances)
+
+for ticket in sorted(tickets, key=lambda ticket: ticket["end_timestamp"]):
+    transactions += ledger_legs(ticket["user_id"], OPERATOR_ID, ticket["amount_paid"], "TicketCharge",
+                                str(ticket["_id"]), ticket["parking_lot_id"], "Parking ticket", ticket["end_timestamp"], balances)
+
+db["transaction"].insert_many(transactions)
+db["user"].update_one(
+    {"_id": ObjectId(OPERATOR_ID)},
+    {"$set": {"account_balance": {"amount": balances[OPERATOR_ID], "currency": "PLN"}}},
+)
+
 # Close the MongoDB connection
 client.close()
\ No newline at end of file
@@ -0,0 +1,52 @@
+# Posts an opening `Adjustment` transfer for every user whose stored
+# `account_balance` is not yet backed by ledger entries, so that
+# GET /users/:id/reconciliation reports no difference afterwards.
+# Safe to run more than once.
+import os
+from datetime import datetime
+
+import pymongo
+from bson.objectid import ObjectId
+from dotenv import load_dotenv
+
+EXTERNAL_ACCOUNT = "system:external"
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+created_at = int(datetime.now().timestamp())
+migrated = 0
+
+for user in db["user"].find({}):
+    user_id = str(user["_id"])
+    ledger = list(db["transaction"].aggregate([
+        {"$match": {"account_id": user_id}},
+        {"$group": {"_id": None, "total": {"$sum": "$amount"}}},
+    ]))
+    ledger_balance = ledger[0]["total"] if ledger else 0.0
+    difference = float(user["account_balance"]) - ledger_balance
+    if difference == 0:
+        continue
+
+    transfer_id = ObjectId()
+    legs = [(EXTERNAL_ACCOUNT, -difference, None), (user_id, difference, float(user["account_balance"]))]
+    db["transaction"].insert_many([
+        {
+            "_id": ObjectId(),
+            "transfer_id": transfer_id,
+            "account_id": account_id,
+            "kind": "Adjustment",
+            "amount": amount,
+            "balance_after": balance_after,
+            "ticket_id": None,
+            "description": "Opening balance",
+            "created_at": created_at,
+        }
+        for account_id, amount, balance_after in legs
+    ])
+    migrated += 1
+
+print(f"Posted opening balances for {migrated} users")
+client.close()
@@ -0,0 +1,55 @@
+# Converts floating point money fields to exact `{amount, currency}` documents,
+# where `amount` is an integer number of minor units (grosze), and price
+# modifiers to integer basis points. Values that are already converted are
+# left untouched, so the script is safe to run more than once.
+import os
+
+import pymongo
+from dotenv import load_dotenv
+
+CURRENCY = "PLN"
+MINOR_PER_MAJOR = 100
+MODIFIER_SCALE = 10_000
+
+NUMERIC = {"$type": ["double", "int", "long", "decimal"]}
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+
+def to_money(value):
+    return {"amount": int(round(float(value) * MINOR_PER_MAJOR)), "currency": CURRENCY}
+
+
+def get_path(document, path):
+    for key in path.split("."):
+        if not isinstance(document, dict) or key not in document:
+            return None
+        document = document[key]
+    return document
+
+
+def migrate(collection, paths, convert):
+    migrated = 0
+    for path in paths:
+        for document in db[collection].find({path: NUMERIC}):
+            value = get_path(document, path)
+            db[collection].update_one({"_id": document["_id"]}, {"$set": {path: convert(value)}})
+            migrated += 1
+    print(f"{collection}: converted {migrated} values")
+
+
+migrate("user", ["account_balance"], to_money)
+migrate("ticket", ["amount_paid"], to_money)
+migrate("transaction", ["amount", "balance_after"], to_money)
+migrate("parking_lot", [
+    "cost_of_maintenance.electricity",
+    "cost_of_maintenance.cleaning",
+    "cost_of_maintenance.security",
+], to_money)
+migrate("tariff", ["price_per_hour"], to_money)
+migrate("parking_space", ["price_modifier"], lambda value: int(round(float(value) * MODIFIER_SCALE)))
+
+client.close()
@@ -0,0 +1,50 @@
+# Assigns an operator to every parking lot that has none and tags existing
+# ticket ledger entries with the parking lot they were charged for.
+#
+# Usage: OPERATOR_ID=<user id> python 003_parking_lot_operators.py
+#
+# Until now every ticket payment was credited to one hard-coded account, so
+# OPERATOR_ID should normally be that account (5f9b3b9b9d9b9d9b9d9b9d9b in
+# seeded databases): it is given the Operator role and its past charges then
+# show up in the income endpoints. Safe to run more than once.
+import os
+import sys
+
+import pymongo
+from bson.objectid import ObjectId
+from dotenv import load_dotenv
+
+load_dotenv()
+
+operator_id = os.getenv("OPERATOR_ID")
+if not operator_id:
+    sys.exit("OPERATOR_ID is not set")
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+result = db["user"].update_one({"_id": ObjectId(operator_id)}, {"$set": {"role": "Operator"}})
+if result.matched_count == 0:
+    sys.exit(f"user {operator_id} does not exist")
+
+result = db["parking_lot"].update_many(
+    {"operator_id": {"$exists": False}},
+    {"$set": {"operator_id": operator_id}},
+)
+print(f"Assigned operator {operator_id} to {result.modified_count} parking lots")
+
+tagged = 0
+for ticket in db["ticket"].find({}, {"parking_lot_id": 1}):
+    result = db["transaction"].update_many(
+        {"ticket_id": str(ticket["_id"]), "parking_lot_id": {"$exists": False}},
+        {"$set": {"parking_lot_id": ticket["parking_lot_id"]}},
+    )
+    tagged += result.modified_count
+
+result = db["transaction"].update_many(
+    {"parking_lot_id": {"$exists": False}},
+    {"$set": {"parking_lot_id": None}},
+)
+print(f"Tagged {tagged} ticket ledger entries, {result.modified_count} entries without a parking lot")
+
+client.close()
@@ -0,0 +1,40 @@
+# Puts the existing tariffs of every parking lot into version 1 of its tariff
+# set, in force from the start so that every past ticket is billed by it.
+# Safe to run more than once.
+import os
+from datetime import datetime
+
+import pymongo
+from bson.objectid import ObjectId
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+created_at = int(datetime.now().timestamp())
+migrated = 0
+
+for parking_lot in db["parking_lot"].find({}, {"_id": 1}):
+    parking_lot_id = str(parking_lot["_id"])
+    if db["tariff_set"].count_documents({"parking_lot_id": parking_lot_id}) > 0:
+        continue
+
+    tariff_set_id = ObjectId()
+    db["tariff"].update_many(
+        {"parking_lot_id": parking_lot_id, "tariff_set_id": {"$exists": False}},
+        {"$set": {"tariff_set_id": str(tariff_set_id)}},
+    )
+    db["tariff_set"].insert_one({
+        "_id": tariff_set_id,
+        "parking_lot_id": parking_lot_id,
+        "version": 1,
+        "effective_from": 0,
+        "created_at": created_at,
+    })
+    migrated += 1
+
+print(f"Created tariff sets for {migrated} parking lots")
+
+client.close()
@@ -0,0 +1,24 @@
+# Gives every ticket a `status`: tickets already charged have `Exited`, the
+# others are still `Parked`. Tickets without a status cannot be read, so run
+# this before deploying. Safe to run more than once.
+import os
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+exited = db["ticket"].update_many(
+    {"status": {"$exists": False}, "end_timestamp": {"$ne": 0}},
+    {"$set": {"status": "Exited"}},
+)
+parked = db["ticket"].update_many(
+    {"status": {"$exists": False}, "end_timestamp": 0},
+    {"$set": {"status": "Parked"}},
+)
+print(f"Marked {exited.modified_count} tickets as Exited and {parked.modified_count} as Parked")
+
+client.close()
@@ -0,0 +1,22 @@
+# Indexes for reservations: overlap checks look reservations up by parking
+# space or vehicle and time window, the no-show job by status and start.
+# Safe to run more than once.
+import os
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+reservations = db["reservation"]
+reservations.create_index([("parking_spot_id", 1), ("status", 1), ("start", 1)])
+reservations.create_index([("vehicle_license_number", 1), ("status", 1), ("start", 1)])
+reservations.create_index([("parking_lot_id", 1), ("start", 1)])
+reservations.create_index([("status", 1), ("start", 1)])
+reservations.create_index([("user_id", 1), ("start", 1)])
+print("Created reservation indexes")
+
+client.close()
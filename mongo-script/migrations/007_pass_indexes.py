@@ -0,0 +1,22 @@
+# Indexes for passes: overlap checks look passes up by vehicle or parking
+# space and validity, the renewal job by auto_renew and end.
+# Safe to run more than once.
+import os
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+passes = db["pass"]
+passes.create_index([("parking_lot_id", 1), ("vehicle_license_number", 1), ("valid_until", 1)])
+passes.create_index([("parking_spot_id", 1), ("valid_until", 1)])
+passes.create_index([("auto_renew", 1), ("valid_until", 1)])
+passes.create_index([("user_id", 1), ("valid_from", -1)])
+db["pass_plan"].create_index([("parking_lot_id", 1), ("active", 1)])
+print("Created pass indexes")
+
+client.close()
@@ -0,0 +1,23 @@
+# Indexes for gate devices and their events: devices are looked up by the
+# hash of their API key, the audit log by parking lot or gate and time.
+# Safe to run more than once.
+import os
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+gates = db["gate"]
+gates.create_index([("api_key_hash", 1)], unique=True)
+gates.create_index([("parking_lot_id", 1)])
+
+events = db["gate_event"]
+events.create_index([("parking_lot_id", 1), ("timestamp", -1)])
+events.create_index([("gate_id", 1), ("timestamp", -1)])
+print("Created gate indexes")
+
+client.close()
@@ -0,0 +1,19 @@
+# Indexes for licence plate reads: the review queue lists reads of a parking
+# lot by status and capture time.
+# Safe to run more than once.
+import os
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+reads = db["anpr_read"]
+reads.create_index([("parking_lot_id", 1), ("status", 1), ("captured_at", -1)])
+reads.create_index([("parking_lot_id", 1), ("captured_at", -1)])
+print("Created plate read indexes")
+
+client.close()
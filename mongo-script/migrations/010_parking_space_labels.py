@@ -0,0 +1,55 @@
+# Gives parking spaces created before spot labels a label like L2-C-017
+# (level, vehicle type, number of the type on the level, in spot order) and
+# makes labels unique per parking lot and level.
+# Safe to run more than once.
+import os
+from collections import defaultdict
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+TYPE_CODES = {
+    "Motorcycle": "M",
+    "Car": "C",
+    "Electric": "E",
+    "Van": "V",
+    "Truck": "T",
+    "Oversized": "O",
+}
+
+spaces = db["parking_space"]
+levels = defaultdict(list)
+for space in spaces.find().sort("location.no_space", 1):
+    levels[(space["parking_lot_id"], space["location"]["no_level"])].append(space)
+
+labelled = 0
+for (_, no_level), level_spaces in levels.items():
+    taken = {space["label"] for space in level_spaces if space.get("label")}
+    numbers = defaultdict(int)
+    for space in level_spaces:
+        if space.get("label"):
+            continue
+        code = TYPE_CODES[space["vehicle_type"]]
+        while True:
+            numbers[code] += 1
+            label = "L%d-%s-%03d" % (no_level, code, numbers[code])
+            if label not in taken:
+                break
+        taken.add(label)
+        spaces.update_one({"_id": space["_id"]}, {"$set": {"label": label}})
+        labelled += 1
+print("Labelled %d parking spaces" % labelled)
+
+spaces.create_index(
+    [("parking_lot_id", 1), ("location.no_level", 1), ("label", 1)],
+    unique=True,
+    partialFilterExpression={"label": {"$gt": ""}},
+)
+print("Created parking space label index")
+
+client.close()
@@ -0,0 +1,31 @@
+# Stores the location of each parking lot as a GeoJSON point and indexes it
+# for the nearby parking lot search. Lots with coordinates out of range are
+# left without a point and reported.
+# Safe to run more than once.
+import os
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+lots = db["parking_lot"]
+updated = 0
+for lot in lots.find({}, {"location": 1}):
+    latitude = lot["location"]["latitude"]
+    longitude = lot["location"]["longitude"]
+    if not (-90 <= latitude <= 90 and -180 <= longitude <= 180):
+        print("Parking lot %s has no valid coordinates" % lot["_id"])
+        continue
+    point = {"type": "Point", "coordinates": [longitude, latitude]}
+    lots.update_one({"_id": lot["_id"]}, {"$set": {"geo": point}})
+    updated += 1
+print("Stored %d parking lot points" % updated)
+
+lots.create_index([("geo", pymongo.GEOSPHERE)])
+print("Created parking lot 2dsphere index")
+
+client.close()
@@ -0,0 +1,49 @@
+# Access codes replace the first 8 characters of the parking lot id, which
+# stop working. Creates the code indexes and issues a random code to every
+# parking lot without a lot-wide code.
+# Safe to run more than once.
+import os
+import secrets
+import time
+
+import pymongo
+from bson import ObjectId
+from dotenv import load_dotenv
+
+load_dotenv()
+
+ALPHABET = "23456789ABCDEFGHJKMNPQRSTUVWXYZ"
+CODE_LENGTH = 8
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+codes = db["access_code"]
+codes.create_index([("code", 1)], unique=True)
+codes.create_index([("parking_lot_id", 1), ("revoked_at", 1), ("created_at", -1)])
+print("Created access code indexes")
+
+issued = 0
+for lot in db["parking_lot"].find({"archived_at": None}, {"_id": 1}):
+    lot_id = str(lot["_id"])
+    if codes.find_one({"parking_lot_id": lot_id, "gate_id": None, "revoked_at": None}):
+        continue
+    while True:
+        code = "".join(secrets.choice(ALPHABET) for _ in range(CODE_LENGTH))
+        try:
+            codes.insert_one({
+                "_id": ObjectId(),
+                "code": code,
+                "parking_lot_id": lot_id,
+                "gate_id": None,
+                "created_at": int(time.time()),
+                "expires_at": None,
+                "revoked_at": None,
+            })
+            break
+        except pymongo.errors.DuplicateKeyError:
+            continue
+    issued += 1
+print("Issued access codes to %d parking lots" % issued)
+
+client.close()
@@ -0,0 +1,41 @@
+# Ticket codes were the first 8 characters of the ticket id, which tickets
+# issued in the same second share. Gives every ticket but the oldest of each
+# shared code a random code and makes codes unique.
+# Safe to run more than once.
+import os
+import secrets
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+ALPHABET = "23456789ABCDEFGHJKMNPQRSTUVWXYZ"
+CODE_LENGTH = 8
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+tickets = db["ticket"]
+taken = set(tickets.distinct("code"))
+shared = tickets.aggregate([
+    {"$group": {"_id": "$code", "ids": {"$push": "$_id"}, "count": {"$sum": 1}}},
+    {"$match": {"count": {"$gt": 1}}},
+])
+
+recoded = 0
+for group in shared:
+    for ticket_id in sorted(group["ids"])[1:]:
+        while True:
+            code = "".join(secrets.choice(ALPHABET) for _ in range(CODE_LENGTH))
+            if code not in taken:
+                break
+        taken.add(code)
+        tickets.update_one({"_id": ticket_id}, {"$set": {"code": code}})
+        recoded += 1
+print("Gave %d tickets a new code" % recoded)
+
+tickets.create_index([("code", 1)], unique=True)
+print("Created ticket code index")
+
+client.close()
@@ -0,0 +1,45 @@
+# A parking lot has at most one code of its own: for no gate, without expiry
+# and not revoked. Revokes all but the oldest of such codes, which requests
+# racing on first use could issue, and creates the index keeping them unique.
+# Safe to run more than once.
+import os
+import time
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+LOT_CODE = {
+    "gate_id": {"$type": "null"},
+    "expires_at": {"$type": "null"},
+    "revoked_at": {"$type": "null"},
+}
+
+codes = db["access_code"]
+shared = codes.aggregate([
+    {"$match": LOT_CODE},
+    {"$sort": {"created_at": 1, "_id": 1}},
+    {"$group": {"_id": "$parking_lot_id", "ids": {"$push": "$_id"}}},
+    {"$match": {"ids.1": {"$exists": True}}},
+])
+
+revoked = 0
+now = int(time.time())
+for lot in shared:
+    result = codes.update_many({"_id": {"$in": lot["ids"][1:]}}, {"$set": {"revoked_at": now}})
+    revoked += result.modified_count
+print("Revoked %d extra parking lot codes" % revoked)
+
+codes.create_index(
+    [("parking_lot_id", 1)],
+    unique=True,
+    partialFilterExpression=LOT_CODE,
+    name="parking_lot_code",
+)
+print("Created parking lot code index")
+
+client.close()
@@ -0,0 +1,34 @@
+# Tariff set versions are unique per parking lot. Renumbers the sets of
+# parking lots where concurrent edits stored the same version twice, in the
+# order they were created, and creates the index keeping versions unique.
+# Safe to run more than once.
+import os
+
+import pymongo
+from dotenv import load_dotenv
+
+load_dotenv()
+
+client = pymongo.MongoClient(os.getenv("MONGO_URI"))
+db = client[os.getenv("MONGO_DB_NAME")]
+
+sets = db["tariff_set"]
+shared = sets.aggregate([
+    {"$group": {"_id": {"parking_lot_id": "$parking_lot_id", "version": "$version"}, "count": {"$sum": 1}}},
+    {"$match": {"count": {"$gt": 1}}},
+    {"$group": {"_id": "$_id.parking_lot_id"}},
+])
+
+renumbered = 0
+for lot in shared:
+    lot_sets = sets.find({"parking_lot_id": lot["_id"]}).sort([("version", 1), ("created_at", 1), ("_id", 1)])
+    for version, tariff_set in enumerate(list(lot_sets), start=1):
+        if tariff_set["version"] != version:
+            sets.update_one({"_id": tariff_set["_id"]}, {"$set": {"version": version}})
+            renumbered += 1
+print("Renumbered %d tariff sets" % renumbered)
+
+sets.create_index([("parking_lot_id", 1), ("version", 1)], unique=True)
+print("Created tariff set version index")
+
+client.close()
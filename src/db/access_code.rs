@@ -0,0 +1,243 @@
+use std::str::FromStr;
+
+use bson::{doc, oid::ObjectId};
+use futures::StreamExt;
+use mongodb::{
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+    ClientSession,
+};
+
+use crate::structs::{
+    access_code,
+    error::MyError::{self, *},
+    gate::GateDirection,
+    model::AccessCode,
+    response::{AccessCodeResponse, ParkingLotByCodeResponse},
+    schema::CreateAccessCodeSchema,
+};
+
+use super::common::{is_duplicate_key, DB};
+
+type Result<T> = std::result::Result<T, MyError>;
+
+impl DB {
+    /// Issues a random code for the parking lot, or for one of its entry gates.
+    pub async fn create_access_code(&self, parking_lot_id: &str, body: &CreateAccessCodeSchema) -> Result<AccessCodeResponse> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        if let Some(gate_id) = &body.gate_id {
+            let gate = self.get_gate(parking_lot_id, gate_id).await?;
+            if gate.direction != GateDirection::Entry || !gate.active {
+                return Err(ValidationError(format!("gate {} is not an active entry gate", gate_id)));
+            }
+        }
+        if body.expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now().timestamp()) {
+            return Err(ValidationError("expiry must be in the future".to_string()));
+        }
+        if body.gate_id.is_none() && body.expires_at.is_none() {
+            let lot_code = self
+                .access_code_collection
+                .find_one(self.lot_code_filter(&parking_lot._id.to_hex()), None)
+                .await
+                .map_err(MongoQueryError)?;
+            if lot_code.is_some() {
+                return Err(ValidationError(format!("parking lot {} has a code already, rotate it instead", parking_lot_id)));
+            }
+        }
+
+        let code = self.insert_access_code(&parking_lot._id.to_hex(), body.gate_id.to_owned(), body.expires_at).await?;
+
+        Ok(self.doc_to_access_code(&code))
+    }
+
+    /// Stores a new code, drawing again in the unlikely case it is taken.
+    async fn insert_access_code(&self, parking_lot_id: &str, gate_id: Option<String>, expires_at: Option<i64>) -> Result<AccessCode> {
+        let mut attempt = 1;
+        loop {
+            let code = self.new_access_code(parking_lot_id, gate_id.to_owned(), expires_at);
+            match self.access_code_collection.insert_one(&code, None).await {
+                Ok(_) => return Ok(code),
+                Err(e) if is_duplicate_key(&e) && attempt < access_code::GENERATE_ATTEMPTS => attempt += 1,
+                Err(e) => return Err(MongoQueryError(e)),
+            }
+        }
+    }
+
+    /// Codes of the parking lot that were not revoked, newest first.
+    pub async fn get_access_codes(&self, parking_lot_id: &str) -> Result<Vec<AccessCodeResponse>> {
+        let mut cursor = self
+            .access_code_collection
+            .find(
+                doc! { "parking_lot_id": parking_lot_id, "revoked_at": null },
+                FindOptions::builder().sort(doc! { "created_at": -1 }).build(),
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<AccessCodeResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            json_result.push(self.doc_to_access_code(&doc.map_err(MongoQueryError)?));
+        }
+
+        Ok(json_result)
+    }
+
+    fn new_access_code(&self, parking_lot_id: &str, gate_id: Option<String>, expires_at: Option<i64>) -> AccessCode {
+        AccessCode {
+            _id: ObjectId::new(),
+            code: access_code::generate_code(),
+            parking_lot_id: parking_lot_id.to_owned(),
+            gate_id,
+            created_at: chrono::Utc::now().timestamp(),
+            expires_at,
+            revoked_at: None,
+        }
+    }
+
+    /// The code of the whole parking lot: for no gate, without expiry and not
+    /// revoked. A partial unique index keeps it to one per parking lot.
+    fn lot_code_filter(&self, parking_lot_id: &str) -> bson::Document {
+        doc! { "parking_lot_id": parking_lot_id, "gate_id": null, "expires_at": null, "revoked_at": null }
+    }
+
+    /// Current code of the whole parking lot, issued on first use. Requests
+    /// racing to issue it all get the one that was stored.
+    pub async fn get_parking_lot_code(&self, parking_lot_id: &str) -> Result<String> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let mut attempt = 1;
+        loop {
+            let update = doc! {
+                "$setOnInsert": {
+                    "code": access_code::generate_code(),
+                    "created_at": chrono::Utc::now().timestamp(),
+                },
+            };
+            let code = self
+                .access_code_collection
+                .find_one_and_update(self.lot_code_filter(&parking_lot._id.to_hex()), update, options.clone())
+                .await;
+            match code {
+                Ok(Some(code)) => return Ok(access_code::format_code(&code.code)),
+                Ok(None) => return Err(NotFoundError(format!("access code of parking lot {}", parking_lot_id))),
+                // the drawn code is taken, or another request issued the lot code first
+                Err(e) if is_duplicate_key(&e) && attempt < access_code::GENERATE_ATTEMPTS => attempt += 1,
+                Err(e) => return Err(MongoQueryError(e)),
+            }
+        }
+    }
+
+    /// Replaces a code with a new one for the same entrance and expiry. Both
+    /// happen in one transaction, so a failed rotation leaves the old code
+    /// working. Requires the deployment to run as a replica set.
+    pub async fn rotate_access_code(&self, parking_lot_id: &str, code_id: &str) -> Result<AccessCodeResponse> {
+        self.get_open_parking_lot(parking_lot_id).await?;
+
+        let mut attempt = 1;
+        loop {
+            let mut session = self.client.start_session(None).await.map_err(MongoQueryError)?;
+            session.start_transaction(None).await.map_err(MongoQueryError)?;
+
+            match self.rotate_code(parking_lot_id, code_id, &mut session).await {
+                Ok(rotated) => {
+                    session.commit_transaction().await.map_err(MongoQueryError)?;
+                    return Ok(self.doc_to_access_code(&rotated));
+                }
+                Err(e) => {
+                    let _ = session.abort_transaction().await;
+                    match e {
+                        // a write error ends the transaction, so a taken code is drawn again in a new one
+                        MongoQueryError(e) if is_duplicate_key(&e) && attempt < access_code::GENERATE_ATTEMPTS => attempt += 1,
+                        e => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+
+    async fn rotate_code(&self, parking_lot_id: &str, code_id: &str, session: &mut ClientSession) -> Result<AccessCode> {
+        let oid = ObjectId::from_str(code_id).map_err(|_| InvalidIDError(code_id.to_owned()))?;
+        let code = self
+            .access_code_collection
+            .find_one_and_update_with_session(
+                doc! { "_id": oid, "parking_lot_id": parking_lot_id, "revoked_at": null },
+                doc! { "$set": { "revoked_at": chrono::Utc::now().timestamp() } },
+                None,
+                session,
+            )
+            .await
+            .map_err(MongoQueryError)?
+            .ok_or_else(|| NotFoundError(format!("access code {} of parking lot {}", code_id, parking_lot_id)))?;
+
+        let expires_at = code.expires_at.filter(|expires_at| *expires_at > chrono::Utc::now().timestamp());
+        let rotated = self.new_access_code(parking_lot_id, code.gate_id, expires_at);
+        self.access_code_collection
+            .insert_one_with_session(&rotated, None, session)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(rotated)
+    }
+
+    pub async fn revoke_access_code(&self, parking_lot_id: &str, code_id: &str) -> Result<AccessCodeResponse> {
+        let code = self.revoke_code(parking_lot_id, code_id).await?;
+
+        Ok(self.doc_to_access_code(&code))
+    }
+
+    /// Revokes a code not revoked yet and returns it as revoked.
+    async fn revoke_code(&self, parking_lot_id: &str, code_id: &str) -> Result<AccessCode> {
+        let oid = ObjectId::from_str(code_id).map_err(|_| InvalidIDError(code_id.to_owned()))?;
+        let now = chrono::Utc::now().timestamp();
+        let code = self
+            .access_code_collection
+            .find_one_and_update(
+                doc! { "_id": oid, "parking_lot_id": parking_lot_id, "revoked_at": null },
+                doc! { "$set": { "revoked_at": now } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        match code {
+            Some(code) => Ok(AccessCode { revoked_at: Some(now), ..code }),
+            None => Err(NotFoundError(format!("access code {} of parking lot {}", code_id, parking_lot_id))),
+        }
+    }
+
+    /// Parking lot of a valid code. Unknown, revoked and expired codes are
+    /// all reported as not found.
+    pub async fn get_parking_lot_by_code(&self, code: &str) -> Result<ParkingLotByCodeResponse> {
+        let normalized = access_code::normalize_code(code).ok_or_else(|| InvalidCodeError(code.to_owned()))?;
+        let now = chrono::Utc::now().timestamp();
+        let access_code = self
+            .access_code_collection
+            .find_one(doc! { "code": &normalized, "revoked_at": null }, None)
+            .await
+            .map_err(MongoQueryError)?
+            .filter(|access_code| access_code.expires_at.is_none_or(|expires_at| expires_at > now))
+            .ok_or_else(|| NotFoundError(format!("access code {}", code)))?;
+
+        let parking_lot = self.get_open_parking_lot(&access_code.parking_lot_id).await?;
+
+        Ok(ParkingLotByCodeResponse {
+            parking_lot: self.get_parking_lot_by_id(&parking_lot._id.to_hex()).await?,
+            gate_id: access_code.gate_id,
+        })
+    }
+
+    fn doc_to_access_code(&self, code: &AccessCode) -> AccessCodeResponse {
+        AccessCodeResponse {
+            id: code._id.to_hex(),
+            code: access_code::format_code(&code.code),
+            parking_lot_id: code.parking_lot_id.to_owned(),
+            gate_id: code.gate_id.to_owned(),
+            created_at: code.created_at,
+            expires_at: code.expires_at,
+            revoked_at: code.revoked_at,
+        }
+    }
+}
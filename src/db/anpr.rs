@@ -0,0 +1,248 @@
+use std::str::FromStr;
+
+use bson::{doc, oid::ObjectId, Document};
+use futures::StreamExt;
+use mongodb::options::FindOptions;
+
+use crate::{
+    structs::{
+        anpr::{self, AnprOutcome, AnprReadStatus},
+        error::MyError::{self, *},
+        gate::GateDirection,
+        model::{AnprRead, GateDevice},
+        reservation::ReservationStatus,
+        response::{AnprReadResponse, GateDecisionResponse},
+        schema::{AnprReadSchema, GateEventSchema, ReviewAnprReadSchema},
+        ticket_status::TicketStatus,
+    },
+};
+
+use super::common::DB;
+
+type Result<T> = std::result::Result<T, MyError>;
+
+impl DB {
+    /// Matches a camera read against the vehicles expected at the gate and,
+    /// when the read is confident and matches a single plate, lets the vehicle
+    /// in or out as the gate would for that plate. Other reads wait in the
+    /// review queue.
+    pub async fn ingest_anpr_read(&self, gate: &GateDevice, gate_id: &str, body: &AnprReadSchema) -> Result<AnprReadResponse> {
+        if gate._id.to_hex() != gate_id {
+            return Err(ForbiddenError(gate._id.to_hex()));
+        }
+        anpr::validate_read(&body.plate, body.confidence)?;
+
+        let now = chrono::Utc::now().timestamp();
+        let normalized_plate = anpr::normalize_plate(&body.plate);
+        let candidates = self.get_expected_plates(gate, &normalized_plate).await?;
+        let found = anpr::match_plate(&normalized_plate, candidates.iter().map(String::as_str));
+
+        let mut read = AnprRead {
+            _id: ObjectId::new(),
+            gate_id: gate_id.to_owned(),
+            parking_lot_id: gate.parking_lot_id.to_owned(),
+            camera_id: body.camera_id.to_owned(),
+            plate: body.plate.to_owned(),
+            normalized_plate,
+            confidence: body.confidence,
+            image_ref: body.image_ref.to_owned(),
+            captured_at: body.timestamp.unwrap_or(now),
+            matched_plate: None,
+            status: AnprReadStatus::PendingReview,
+            reason: None,
+            event_id: None,
+            reviewed_by: None,
+            created_at: now,
+        };
+        let decision = match anpr::decide(body.confidence, found) {
+            AnprOutcome::Automatic(plate) => {
+                let decision = self.open_or_deny_plate(gate, &plate).await?;
+                read.status = AnprReadStatus::Processed;
+                read.matched_plate = Some(plate);
+                read.event_id = Some(decision.event_id.to_owned());
+                Some(decision)
+            }
+            AnprOutcome::Review(reason) => {
+                read.reason = Some(reason);
+                None
+            }
+        };
+
+        self.anpr_read_collection
+            .insert_one(&read, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(self.doc_to_anpr_read(&read, decision))
+    }
+
+    /// Plates a read at the gate may be of: vehicles with an open ticket in
+    /// the parking lot, and on entry vehicles with a reservation starting now
+    /// and registered vehicles whose plate is the read up to separators and
+    /// confusable characters.
+    async fn get_expected_plates(&self, gate: &GateDevice, normalized_plate: &str) -> Result<Vec<String>> {
+        let mut plates: Vec<String> = Vec::new();
+        let statuses = match gate.direction {
+            GateDirection::Entry => vec![TicketStatus::Issued],
+            GateDirection::Exit => [TicketStatus::ACTIVE.as_slice(), &[TicketStatus::Paid]].concat(),
+        };
+        let mut tickets = self
+            .ticket_collection
+            .find(doc! { "parking_lot_id": &gate.parking_lot_id, "status": { "$in": bson::to_bson(&statuses)? } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+        while let Some(doc) = tickets.next().await {
+            plates.push(doc.map_err(MongoQueryError)?.vehicle_license_number);
+        }
+        if gate.direction == GateDirection::Exit {
+            return Ok(plates);
+        }
+
+        let parking_lot = self.get_parking_lot(&gate.parking_lot_id).await?;
+        let now = chrono::Utc::now().timestamp();
+        let mut reservations = self
+            .reservation_collection
+            .find(
+                doc! {
+                    "parking_lot_id": &gate.parking_lot_id,
+                    "status": bson::to_bson(&ReservationStatus::Booked)?,
+                    "start": { "$lte": now + parking_lot.reservation_policy.hold_before },
+                    "end": { "$gt": now },
+                },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        while let Some(doc) = reservations.next().await {
+            plates.push(doc.map_err(MongoQueryError)?.vehicle_license_number);
+        }
+
+        let mut vehicles = self
+            .vehicle_collection
+            .find(doc! { "license_plate_number": { "$regex": anpr::plate_pattern(normalized_plate), "$options": "i" } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+        while let Some(doc) = vehicles.next().await {
+            plates.push(doc.map_err(MongoQueryError)?.license_plate_number);
+        }
+
+        Ok(plates)
+    }
+
+    async fn open_or_deny_plate(&self, gate: &GateDevice, plate: &str) -> Result<GateDecisionResponse> {
+        let body = GateEventSchema { vehicle_license_number: Some(plate.to_owned()), ticket_code: None };
+        self.open_or_deny(gate, gate.direction, &body).await
+    }
+
+    pub async fn get_anpr_reads(&self, parking_lot_id: &str, status: Option<AnprReadStatus>, limit: i64, skip: u64) -> Result<Vec<AnprReadResponse>> {
+        let mut filter = doc! { "parking_lot_id": parking_lot_id };
+        if let Some(status) = status {
+            filter.insert("status", bson::to_bson(&status)?);
+        }
+        let options = FindOptions::builder()
+            .sort(doc! { "captured_at": -1, "_id": -1 })
+            .limit(limit)
+            .skip(skip)
+            .build();
+
+        let mut cursor = self
+            .anpr_read_collection
+            .find(filter, options)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<AnprReadResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            json_result.push(self.doc_to_anpr_read(&doc.map_err(MongoQueryError)?, None));
+        }
+
+        Ok(json_result)
+    }
+
+    /// Settles a read from the review queue. With a plate the ticket is opened
+    /// or closed as the gate would have for it; without one the read is
+    /// rejected.
+    pub async fn review_anpr_read(&self, parking_lot_id: &str, id: &str, reviewer_id: &str, body: &ReviewAnprReadSchema) -> Result<AnprReadResponse> {
+        let read = self.get_anpr_read(parking_lot_id, id).await?;
+
+        let Some(plate) = &body.vehicle_license_number else {
+            self.transition_anpr_read(&read, AnprReadStatus::Rejected, doc! { "reviewed_by": reviewer_id }).await?;
+            let rejected = AnprRead { status: AnprReadStatus::Rejected, reviewed_by: Some(reviewer_id.to_owned()), ..read };
+            return Ok(self.doc_to_anpr_read(&rejected, None));
+        };
+
+        let gate = self.get_gate(parking_lot_id, &read.gate_id).await?;
+        let update = doc! { "reviewed_by": reviewer_id, "matched_plate": plate };
+        self.transition_anpr_read(&read, AnprReadStatus::Resolved, update).await?;
+
+        let decision = self.open_or_deny_plate(&gate, plate).await?;
+        self.anpr_read_collection
+            .update_one(doc! { "_id": read._id }, doc! { "$set": { "event_id": &decision.event_id } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let resolved = AnprRead {
+            status: AnprReadStatus::Resolved,
+            matched_plate: Some(plate.to_owned()),
+            reviewed_by: Some(reviewer_id.to_owned()),
+            event_id: Some(decision.event_id.to_owned()),
+            ..read
+        };
+        Ok(self.doc_to_anpr_read(&resolved, Some(decision)))
+    }
+
+    async fn get_anpr_read(&self, parking_lot_id: &str, id: &str) -> Result<AnprRead> {
+        let oid = ObjectId::from_str(id).map_err(|_| InvalidIDError(id.to_owned()))?;
+        let read = self
+            .anpr_read_collection
+            .find_one(doc! { "_id": oid, "parking_lot_id": parking_lot_id }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        match read {
+            Some(read) => Ok(read),
+            None => Err(NotFoundError(format!("plate read {} of parking lot {}", id, parking_lot_id))),
+        }
+    }
+
+    /// Sets the status of a read together with `update`, provided nobody
+    /// reviewed it since it was loaded.
+    async fn transition_anpr_read(&self, read: &AnprRead, status: AnprReadStatus, mut update: Document) -> Result<()> {
+        if !read.status.can_become(status) {
+            return Err(IllegalAnprTransitionError(read._id.to_hex(), read.status, status));
+        }
+
+        update.insert("status", bson::to_bson(&status)?);
+        let result = self
+            .anpr_read_collection
+            .update_one(
+                doc! { "_id": read._id, "status": bson::to_bson(&read.status)? },
+                doc! { "$set": update },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        if result.matched_count == 0 {
+            return Err(IllegalAnprTransitionError(read._id.to_hex(), read.status, status));
+        }
+
+        Ok(())
+    }
+
+    fn doc_to_anpr_read(&self, read: &AnprRead, decision: Option<GateDecisionResponse>) -> AnprReadResponse {
+        AnprReadResponse {
+            id: read._id.to_hex(),
+            gate_id: read.gate_id.to_owned(),
+            camera_id: read.camera_id.to_owned(),
+            plate: read.plate.to_owned(),
+            normalized_plate: read.normalized_plate.to_owned(),
+            confidence: read.confidence,
+            image_ref: read.image_ref.to_owned(),
+            captured_at: read.captured_at,
+            matched_plate: read.matched_plate.to_owned(),
+            status: read.status,
+            reason: read.reason.to_owned(),
+            decision,
+        }
+    }
+}
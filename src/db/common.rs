@@ -1,19 +1,30 @@
 use std::time::Duration;
-use mongodb::{options::{Compressor, ClientOptions}, Collection, Client};
+use mongodb::{error::{ErrorKind, WriteFailure}, options::{Compressor, ClientOptions}, Collection, Client};
 
 use crate::structs::{
     error::MyError, 
-    model::{ParkingLot, Ticket, User, Vehicle, ParkingSpace, Tariff}, 
+    model::{ParkingLot, Ticket, User, Vehicle, ParkingSpace, Tariff, TariffSet, Session, Transaction, Reservation, PassPlan, Pass, GateDevice, GateEvent, AnprRead, AccessCode}, 
 };
 
 #[derive(Clone, Debug)]
 pub struct DB {
+    pub client:                     Client,
     pub user_collection:            Collection<User>,
     pub ticket_collection:          Collection<Ticket>,
     pub parking_lot_collection:     Collection<ParkingLot>,
     pub vehicle_collection:         Collection<Vehicle>,
     pub parking_space_collection:   Collection<ParkingSpace>,
     pub tariff_collection:          Collection<Tariff>,
+    pub tariff_set_collection:      Collection<TariffSet>,
+    pub session_collection:         Collection<Session>,
+    pub transaction_collection:     Collection<Transaction>,
+    pub reservation_collection:     Collection<Reservation>,
+    pub pass_plan_collection:       Collection<PassPlan>,
+    pub pass_collection:            Collection<Pass>,
+    pub gate_collection:            Collection<GateDevice>,
+    pub gate_event_collection:      Collection<GateEvent>,
+    pub anpr_read_collection:       Collection<AnprRead>,
+    pub access_code_collection:     Collection<AccessCode>,
 }
 
 type Result<T> = std::result::Result<T, MyError>;
@@ -62,16 +73,45 @@ impl DB {
         let vehicle_collection: Collection<Vehicle> = database.collection("vehicle");
         let parking_space_collection: Collection<ParkingSpace> = database.collection("parking_space");
         let tariff_collection: Collection<Tariff> = database.collection("tariff");
+        let tariff_set_collection: Collection<TariffSet> = database.collection("tariff_set");
+        let session_collection: Collection<Session> = database.collection("session");
+        let transaction_collection: Collection<Transaction> = database.collection("transaction");
+        let reservation_collection: Collection<Reservation> = database.collection("reservation");
+        let pass_plan_collection: Collection<PassPlan> = database.collection("pass_plan");
+        let pass_collection: Collection<Pass> = database.collection("pass");
+        let gate_collection: Collection<GateDevice> = database.collection("gate");
+        let gate_event_collection: Collection<GateEvent> = database.collection("gate_event");
+        let anpr_read_collection: Collection<AnprRead> = database.collection("anpr_read");
+        let access_code_collection: Collection<AccessCode> = database.collection("access_code");
 
         println!("Database connected successfully");
 
         Ok(Self {
+            client,
             user_collection,
             ticket_collection,
             parking_lot_collection,
             vehicle_collection,
             parking_space_collection,
             tariff_collection,
+            tariff_set_collection,
+            session_collection,
+            transaction_collection,
+            reservation_collection,
+            pass_plan_collection,
+            pass_collection,
+            gate_collection,
+            gate_event_collection,
+            anpr_read_collection,
+            access_code_collection,
         })
     }
+}
+
+/// Whether `e` is a violation of a unique index.
+pub fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) if write_error.code == 11000
+    ) || matches!(e.kind.as_ref(), ErrorKind::Command(command_error) if command_error.code == 11000)
 }
\ No newline at end of file
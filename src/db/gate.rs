@@ -0,0 +1,309 @@
+use std::str::FromStr;
+
+use bson::{doc, oid::ObjectId};
+use futures::StreamExt;
+use mongodb::options::{FindOneOptions, FindOptions};
+
+use crate::{
+    structs::{
+        error::MyError::{self, *},
+        gate::{GateAction, GateDirection},
+        model::{GateDevice, GateEvent, Ticket},
+        response::{GateDecisionResponse, GateDeviceResponse, GateEventResponse, GateKeyResponse},
+        schema::{CreateGateSchema, GateEventSchema},
+        ticket_status::TicketStatus,
+    },
+    utils::secret,
+};
+
+use super::common::DB;
+
+type Result<T> = std::result::Result<T, MyError>;
+
+impl DB {
+    /// Registers a gate device. The API key is returned once and only its hash
+    /// is stored.
+    pub async fn create_gate(&self, parking_lot_id: &str, body: &CreateGateSchema) -> Result<GateKeyResponse> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        let api_key = secret::generate_token(32);
+        let gate = GateDevice {
+            _id: ObjectId::new(),
+            parking_lot_id: parking_lot._id.to_hex(),
+            name: body.name.to_owned(),
+            direction: body.direction,
+            lane: body.lane.to_owned(),
+            api_key_hash: secret::hash_token(&api_key),
+            active: true,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        self.gate_collection
+            .insert_one(&gate, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(GateKeyResponse { gate: self.doc_to_gate(&gate), api_key })
+    }
+
+    pub async fn get_gates(&self, parking_lot_id: &str) -> Result<Vec<GateDeviceResponse>> {
+        let mut cursor = self
+            .gate_collection
+            .find(doc! { "parking_lot_id": parking_lot_id }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<GateDeviceResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            json_result.push(self.doc_to_gate(&doc.map_err(MongoQueryError)?));
+        }
+
+        Ok(json_result)
+    }
+
+    /// Issues a new API key for the gate; the old one stops working at once.
+    pub async fn rotate_gate_key(&self, parking_lot_id: &str, gate_id: &str) -> Result<GateKeyResponse> {
+        let gate = self.get_gate(parking_lot_id, gate_id).await?;
+        let api_key = secret::generate_token(32);
+        self.gate_collection
+            .update_one(
+                doc! { "_id": gate._id },
+                doc! { "$set": { "api_key_hash": secret::hash_token(&api_key), "active": true } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(GateKeyResponse { gate: self.doc_to_gate(&GateDevice { active: true, ..gate }), api_key })
+    }
+
+    /// Stops accepting the gate's key. Its events are kept.
+    pub async fn deactivate_gate(&self, parking_lot_id: &str, gate_id: &str) -> Result<GateDeviceResponse> {
+        let gate = self.get_gate(parking_lot_id, gate_id).await?;
+        self.gate_collection
+            .update_one(doc! { "_id": gate._id }, doc! { "$set": { "active": false } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(self.doc_to_gate(&GateDevice { active: false, ..gate }))
+    }
+
+    pub async fn get_gate(&self, parking_lot_id: &str, gate_id: &str) -> Result<GateDevice> {
+        let oid = ObjectId::from_str(gate_id).map_err(|_| InvalidIDError(gate_id.to_owned()))?;
+        let gate = self
+            .gate_collection
+            .find_one(doc! { "_id": oid, "parking_lot_id": parking_lot_id }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        match gate {
+            Some(gate) => Ok(gate),
+            None => Err(NotFoundError(format!("gate {} of parking lot {}", gate_id, parking_lot_id))),
+        }
+    }
+
+    /// Active gate holding the API key.
+    pub async fn authenticate_gate(&self, api_key: &str) -> Result<GateDevice> {
+        let gate = self
+            .gate_collection
+            .find_one(doc! { "api_key_hash": secret::hash_token(api_key), "active": true }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        gate.ok_or(UnauthorizedError("unknown gate API key".to_string()))
+    }
+
+    pub async fn handle_gate_event(
+        &self,
+        gate: &GateDevice,
+        gate_id: &str,
+        direction: GateDirection,
+        body: &GateEventSchema,
+    ) -> Result<GateDecisionResponse> {
+        if gate._id.to_hex() != gate_id {
+            return Err(ForbiddenError(gate._id.to_hex()));
+        }
+
+        self.open_or_deny(gate, direction, body).await
+    }
+
+    /// Decides whether the barrier opens for a vehicle and records the event.
+    /// Anything that keeps the vehicle out, from a full lot to an empty
+    /// wallet, is answered with `Deny` and a message for the gate display.
+    pub async fn open_or_deny(&self, gate: &GateDevice, direction: GateDirection, body: &GateEventSchema) -> Result<GateDecisionResponse> {
+        let gate_id = gate._id.to_hex();
+        let decision = match (gate.direction == direction, direction) {
+            (false, _) => Err(ValidationError(format!("{} events are not accepted at an {} gate", direction, gate.direction))),
+            (true, GateDirection::Entry) => self.admit_vehicle(gate, body).await,
+            (true, GateDirection::Exit) => self.release_vehicle(gate, body).await,
+        };
+        let (action, message, ticket_code) = match decision {
+            Ok((message, ticket_code)) => (GateAction::Open, message, Some(ticket_code)),
+            Err(e) => {
+                println!("gate {} denied {:?}: {}", gate_id, body.vehicle_license_number, e);
+                (GateAction::Deny, deny_message(&e), body.ticket_code.to_owned())
+            }
+        };
+
+        let event = GateEvent {
+            _id: ObjectId::new(),
+            gate_id,
+            parking_lot_id: gate.parking_lot_id.to_owned(),
+            direction,
+            vehicle_license_number: body.vehicle_license_number.to_owned(),
+            ticket_code: ticket_code.to_owned(),
+            action,
+            message: message.to_owned(),
+            timestamp: chrono::Utc::now().timestamp(),
+        };
+        self.gate_event_collection
+            .insert_one(&event, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(GateDecisionResponse { event_id: event._id.to_hex(), action, message, ticket_code })
+    }
+
+    /// Lets a vehicle in on a ticket issued ahead, a reservation it can check
+    /// in with, or a new ticket charged to the owner of the registered vehicle.
+    async fn admit_vehicle(&self, gate: &GateDevice, body: &GateEventSchema) -> Result<(String, String)> {
+        let ticket = match (&body.ticket_code, &body.vehicle_license_number) {
+            (Some(code), _) => Some(self.get_gate_ticket(gate, code).await?),
+            (None, Some(vehicle_license_number)) => self.find_gate_ticket(gate, vehicle_license_number, &TicketStatus::ACTIVE).await?,
+            (None, None) => return Err(ValidationError("no licence plate or ticket code".to_string())),
+        };
+        if let Some(ticket) = ticket {
+            if ticket.status != TicketStatus::Issued {
+                return Err(ValidationError(format!("vehicle {} is already parked", ticket.vehicle_license_number)));
+            }
+            self.set_ticket_status(&ticket.code, TicketStatus::Parked).await?;
+            return Ok(("Welcome".to_string(), ticket.code));
+        }
+
+        let vehicle_license_number = body.vehicle_license_number.as_deref().unwrap_or_default();
+        let parking_lot = self.get_parking_lot(&gate.parking_lot_id).await?;
+        if let Some(reservation) = self.find_arriving_reservation(&parking_lot, vehicle_license_number).await? {
+            let code = self.check_in_reservation(&reservation._id.to_hex(), &reservation.user_id).await?;
+            return Ok(("Welcome, your reservation is checked in".to_string(), code));
+        }
+
+        let vehicle = self.get_vehicle_by_license_plate_number(vehicle_license_number).await?;
+        let code = self
+            .issue_ticket(&vehicle.user_id, vehicle_license_number, None, &gate.parking_lot_id, None)
+            .await?;
+
+        Ok(("Welcome".to_string(), code))
+    }
+
+    /// Charges the stay of the vehicle's ticket, unless it is paid already,
+    /// and lets it out.
+    async fn release_vehicle(&self, gate: &GateDevice, body: &GateEventSchema) -> Result<(String, String)> {
+        let ticket = match (&body.ticket_code, &body.vehicle_license_number) {
+            (Some(code), _) => self.get_gate_ticket(gate, code).await?,
+            (None, Some(vehicle_license_number)) => {
+                let statuses = [TicketStatus::ACTIVE.as_slice(), &[TicketStatus::Paid]].concat();
+                self.find_gate_ticket(gate, vehicle_license_number, &statuses)
+                    .await?
+                    .ok_or(NotFoundError(format!("ticket of vehicle {}", vehicle_license_number)))?
+            }
+            (None, None) => return Err(ValidationError("no licence plate or ticket code".to_string())),
+        };
+
+        let ticket = self.put_ticket(&ticket.code).await?;
+        Ok((format!("Goodbye, you paid {}", ticket.amount_paid), ticket.code))
+    }
+
+    async fn get_gate_ticket(&self, gate: &GateDevice, code: &str) -> Result<Ticket> {
+        let ticket = self.get_ticket_by_code(code).await?;
+        if ticket.parking_lot_id != gate.parking_lot_id {
+            return Err(ValidationError(format!("ticket {} is for another parking lot", code)));
+        }
+
+        Ok(ticket)
+    }
+
+    /// Latest ticket of the vehicle in the gate's parking lot in one of `statuses`.
+    async fn find_gate_ticket(&self, gate: &GateDevice, vehicle_license_number: &str, statuses: &[TicketStatus]) -> Result<Option<Ticket>> {
+        let options = FindOneOptions::builder()
+            .sort(doc! { "issue_timestamp": -1 })
+            .build();
+
+        self.ticket_collection
+            .find_one(
+                doc! {
+                    "parking_lot_id": &gate.parking_lot_id,
+                    "vehicle_license_number": vehicle_license_number,
+                    "status": { "$in": bson::to_bson(statuses)? },
+                },
+                options,
+            )
+            .await
+            .map_err(MongoQueryError)
+    }
+
+    pub async fn get_gate_events(&self, parking_lot_id: &str, gate_id: Option<&str>, limit: i64, skip: u64) -> Result<Vec<GateEventResponse>> {
+        let mut filter = doc! { "parking_lot_id": parking_lot_id };
+        if let Some(gate_id) = gate_id {
+            filter.insert("gate_id", gate_id);
+        }
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": -1, "_id": -1 })
+            .limit(limit)
+            .skip(skip)
+            .build();
+
+        let mut cursor = self
+            .gate_event_collection
+            .find(filter, options)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<GateEventResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            json_result.push(self.doc_to_gate_event(&doc.map_err(MongoQueryError)?));
+        }
+
+        Ok(json_result)
+    }
+
+    fn doc_to_gate(&self, gate: &GateDevice) -> GateDeviceResponse {
+        GateDeviceResponse {
+            id: gate._id.to_hex(),
+            parking_lot_id: gate.parking_lot_id.to_owned(),
+            name: gate.name.to_owned(),
+            direction: gate.direction,
+            lane: gate.lane.to_owned(),
+            active: gate.active,
+            created_at: gate.created_at,
+        }
+    }
+
+    fn doc_to_gate_event(&self, event: &GateEvent) -> GateEventResponse {
+        GateEventResponse {
+            id: event._id.to_hex(),
+            gate_id: event.gate_id.to_owned(),
+            direction: event.direction,
+            vehicle_license_number: event.vehicle_license_number.to_owned(),
+            ticket_code: event.ticket_code.to_owned(),
+            action: event.action,
+            message: event.message.to_owned(),
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+/// What the gate display shows when the barrier stays closed. Database
+/// failures are not shown to drivers.
+fn deny_message(e: &MyError) -> String {
+    match e {
+        NoParkingSpaceError(_) | NoAvailableParkingSpaceError(_) => "Parking lot full".to_string(),
+        ParkingLotArchivedError(_) => "Parking lot closed".to_string(),
+        NotEnoughBalanceError(_) => "Not enough balance in the wallet, please top up".to_string(),
+        VehicleNotFoundError(_) => "Vehicle not registered".to_string(),
+        MongoNotFound(_) | NotFoundError(_) => "Ticket not found".to_string(),
+        TicketPaymentPendingError(_) => "Payment in progress, please wait".to_string(),
+        IllegalTicketTransitionError(..) => "Ticket not valid at this gate".to_string(),
+        UserBlockedError(_) => "Account blocked, please call the attendant".to_string(),
+        ValidationError(message) => message.to_owned(),
+        ReservationError(e) => e.to_string(),
+        _ => "Please call the attendant".to_string(),
+    }
+}
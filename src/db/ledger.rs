@@ -0,0 +1,310 @@
+use bson::{oid::ObjectId, doc, Document};
+use chrono::{Datelike, TimeZone, Utc};
+use futures::StreamExt;
+use mongodb::{
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+    ClientSession,
+};
+
+use crate::structs::{
+    error::MyError::{*, self},
+    model::{Transaction, TransactionKind},
+    money::Money,
+    response::{TransactionResponse, BalanceReconciliationResponse, IncomeStats},
+    schema::CreateTransactionSchema,
+};
+
+use super::common::DB;
+
+type Result<T> = std::result::Result<T, MyError>;
+
+/// Counterparty for money entering or leaving the system (deposits, adjustments).
+pub const EXTERNAL_ACCOUNT: &str = "system:external";
+
+pub struct Transfer<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub amount: Money,
+    pub kind: TransactionKind,
+    pub ticket_id: Option<String>,
+    pub parking_lot_id: Option<String>,
+    pub description: String,
+}
+
+impl DB {
+    /// Moves `amount` between two accounts inside a MongoDB transaction: both
+    /// balances are changed with `$inc` and both ledger legs are written, or
+    /// nothing is. Requires the deployment to run as a replica set.
+    pub async fn post_transfer(&self, transfer: &Transfer<'_>) -> Result<ObjectId> {
+        if !transfer.amount.is_positive() {
+            return Err(ValidationError(format!("transfer amount must be positive, got {}", transfer.amount)));
+        }
+
+        let mut session = self.client.start_session(None).await.map_err(MongoQueryError)?;
+        session.start_transaction(None).await.map_err(MongoQueryError)?;
+
+        match self.post_transfer_legs(transfer, &mut session).await {
+            Ok(transfer_id) => {
+                session.commit_transaction().await.map_err(MongoQueryError)?;
+                Ok(transfer_id)
+            }
+            Err(e) => {
+                let _ = session.abort_transaction().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn post_transfer_legs(&self, transfer: &Transfer<'_>, session: &mut ClientSession) -> Result<ObjectId> {
+        if let (TransactionKind::Refund, Some(ticket_id)) = (&transfer.kind, &transfer.ticket_id) {
+            // read in the transaction: a concurrent refund changes the same balance, so one of the two aborts
+            let refundable = self.get_refundable_amount(transfer.to, ticket_id, transfer.amount, session).await?;
+            if transfer.amount.checked_sub(refundable)?.is_positive() {
+                return Err(ValidationError(format!("ticket {} has only {} left to refund", ticket_id, refundable)));
+            }
+        }
+
+        let transfer_id = ObjectId::new();
+        let created_at = chrono::Utc::now().timestamp();
+
+        let debit = Money::zero(transfer.amount.currency).checked_sub(transfer.amount)?;
+        let debit_balance = self.apply_balance_change(transfer.from, debit, session).await?;
+        let credit_balance = self.apply_balance_change(transfer.to, transfer.amount, session).await?;
+
+        let legs = [
+            (transfer.from, debit, debit_balance),
+            (transfer.to, transfer.amount, credit_balance),
+        ];
+        let entries = legs.into_iter().map(|(account_id, amount, balance_after)| Transaction {
+            _id: ObjectId::new(),
+            transfer_id,
+            account_id: account_id.to_owned(),
+            kind: transfer.kind.to_owned(),
+            amount,
+            balance_after,
+            ticket_id: transfer.ticket_id.to_owned(),
+            parking_lot_id: transfer.parking_lot_id.to_owned(),
+            description: transfer.description.to_owned(),
+            created_at,
+        });
+
+        self.transaction_collection
+            .insert_many_with_session(entries, None, session)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(transfer_id)
+    }
+
+    /// What the user was charged for a ticket, less what was refunded so far,
+    /// in the currency of `amount`.
+    async fn get_refundable_amount(&self, user_id: &str, ticket_id: &str, amount: Money, session: &mut ClientSession) -> Result<Money> {
+        let filter = doc! {
+            "account_id": user_id,
+            "ticket_id": ticket_id,
+            "kind": { "$in": ["TicketCharge", "Refund"] },
+            "amount.currency": amount.currency.to_string(),
+        };
+        let mut cursor = self
+            .transaction_collection
+            .find_with_session(filter, None, session)
+            .await
+            .map_err(MongoQueryError)?;
+
+        // charges are debited from the user and refunds credited back
+        let mut refundable = Money::zero(amount.currency);
+        while let Some(doc) = cursor.next(session).await {
+            refundable = refundable.checked_sub(doc.map_err(MongoQueryError)?.amount)?;
+        }
+
+        Ok(refundable)
+    }
+
+    /// Applies `delta` to a user's balance and returns the new balance. Debits
+    /// only match while the balance, held in the same currency, covers them.
+    /// System accounts keep no stored balance.
+    async fn apply_balance_change(&self, account_id: &str, delta: Money, session: &mut ClientSession) -> Result<Option<Money>> {
+        let oid = match ObjectId::parse_str(account_id) {
+            Ok(oid) => oid,
+            Err(_) => return Ok(None),
+        };
+
+        let mut filter = doc! { "_id": oid, "account_balance.currency": delta.currency.to_string() };
+        if delta.is_negative() {
+            filter.insert("account_balance.amount", doc! { "$gte": -delta.amount });
+        }
+        let update = doc! { "$inc": { "account_balance.amount": delta.amount } };
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let user = self
+            .user_collection
+            .find_one_and_update_with_session(filter, update, options, session)
+            .await
+            .map_err(MongoQueryError)?;
+
+        match user {
+            Some(user) => Ok(Some(user.account_balance)),
+            None if delta.is_negative() => Err(NotEnoughBalanceError(account_id.to_owned())),
+            None => Err(MongoNotFound(format!("account: {}", account_id))),
+        }
+    }
+
+    /// Refunds and manual adjustments posted by an administrator. A refund is
+    /// paid back by the operator that was credited for the ticket; a negative
+    /// adjustment moves money out of the user's wallet.
+    pub async fn create_user_transaction(&self, user_id: &str, body: &CreateTransactionSchema) -> Result<String> {
+        self.get_user_by_id(user_id).await?;
+
+        let (from, to, parking_lot_id) = match (&body.kind, !body.amount.is_negative()) {
+            (TransactionKind::Refund, true) => {
+                let ticket_id = body.ticket_id.as_deref()
+                    .ok_or(ValidationError("a refund requires ticketId".to_string()))?;
+                let ticket = self.get_ticket_by_id(ticket_id).await?;
+                if ticket.user_id != user_id {
+                    return Err(ValidationError(format!("ticket {} does not belong to user {}", ticket_id, user_id)));
+                }
+                let parking_lot = self.get_parking_lot(&ticket.parking_lot_id).await?;
+                (parking_lot.operator_id, user_id.to_owned(), Some(ticket.parking_lot_id))
+            }
+            (TransactionKind::Adjustment, true) => (EXTERNAL_ACCOUNT.to_owned(), user_id.to_owned(), None),
+            (TransactionKind::Adjustment, false) => (user_id.to_owned(), EXTERNAL_ACCOUNT.to_owned(), None),
+            (kind, _) => return Err(ValidationError(format!("cannot post {:?} of {}", kind, body.amount))),
+        };
+
+        let transfer_id = self.post_transfer(&Transfer {
+            from: &from,
+            to: &to,
+            amount: body.amount.abs(),
+            kind: body.kind.to_owned(),
+            ticket_id: body.ticket_id.to_owned(),
+            parking_lot_id,
+            description: body.description.to_owned(),
+        }).await?;
+
+        Ok(transfer_id.to_hex())
+    }
+
+    pub async fn fetch_account_transactions(&self, account_id: &str, limit: i64, skip: u64) -> Result<Vec<TransactionResponse>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "created_at": -1, "_id": -1 })
+            .limit(limit)
+            .skip(skip)
+            .build();
+
+        let mut cursor = self
+            .transaction_collection
+            .find(doc! { "account_id": account_id }, options)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<TransactionResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            json_result.push(self.doc_to_transaction(&doc.map_err(MongoQueryError)?));
+        }
+
+        Ok(json_result)
+    }
+
+    /// Compares the stored balance of a user with the sum of their ledger entries.
+    pub async fn reconcile_balance(&self, user_id: &str) -> Result<BalanceReconciliationResponse> {
+        let user = self.get_user_by_id(user_id).await?;
+
+        let currency = user.account_balance.currency;
+        let pipeline = vec![
+            doc! { "$match": { "account_id": user_id, "amount.currency": currency.to_string() } },
+            doc! { "$group": { "_id": null, "total": { "$sum": "$amount.amount" } } },
+        ];
+        let mut cursor = self
+            .transaction_collection
+            .aggregate(pipeline, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        // `$sum` yields an int32 while the total fits, an int64 otherwise
+        let total = match cursor.next().await {
+            Some(doc) => match doc.map_err(MongoQueryError)?.get("total") {
+                Some(bson::Bson::Int32(total)) => *total as i64,
+                Some(bson::Bson::Int64(total)) => *total,
+                _ => 0,
+            },
+            None => 0,
+        };
+        let ledger_balance = Money::new(total, currency);
+
+        Ok(BalanceReconciliationResponse {
+            user_id: user_id.to_owned(),
+            balance: user.account_balance,
+            ledger_balance,
+            difference: user.account_balance.checked_sub(ledger_balance)?,
+        })
+    }
+
+    /// Monthly revenue of a parking lot, read from the ledger of its operator:
+    /// ticket charges, reservation fees and passes credited for the lot minus
+    /// refunds paid out for it. `extra_filter` narrows the entries further,
+    /// e.g. to one parking space.
+    pub async fn fetch_parking_lot_revenue(&self, operator_id: &str, parking_lot_id: &str, extra_filter: Document) -> Result<Vec<IncomeStats>> {
+        let mut filter = doc! {
+            "account_id": operator_id,
+            "parking_lot_id": parking_lot_id,
+            "kind": { "$in": ["TicketCharge", "Refund", "ReservationFee", "PassPurchase"] },
+        };
+        filter.extend(extra_filter);
+        let options = FindOptions::builder()
+            .sort(doc! { "created_at": 1 })
+            .build();
+
+        let mut cursor = self
+            .transaction_collection
+            .find(filter, options)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<IncomeStats> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let transaction = doc.map_err(MongoQueryError)?;
+            let month = match Utc.timestamp_opt(transaction.created_at, 0).unwrap().month() {
+                1 => "January",
+                2 => "February",
+                3 => "March",
+                4 => "April",
+                5 => "May",
+                6 => "June",
+                7 => "July",
+                8 => "August",
+                9 => "September",
+                10 => "October",
+                11 => "November",
+                _ => "December",
+            }.to_string();
+
+            let income = transaction.amount;
+            if let Some(stats) = json_result.iter_mut().find(|stats| stats.month == month) {
+                stats.income = stats.income.checked_add(income)?;
+            } else {
+                json_result.push(IncomeStats {
+                    month,
+                    income,
+                });
+            }
+        }
+
+        Ok(json_result)
+    }
+
+    fn doc_to_transaction(&self, transaction: &Transaction) -> TransactionResponse {
+        TransactionResponse {
+            id: transaction._id.to_hex(),
+            transfer_id: transaction.transfer_id.to_hex(),
+            kind: transaction.kind.to_owned(),
+            amount: transaction.amount,
+            balance_after: transaction.balance_after,
+            ticket_id: transaction.ticket_id.to_owned(),
+            parking_lot_id: transaction.parking_lot_id.to_owned(),
+            description: transaction.description.to_owned(),
+            created_at: transaction.created_at,
+        }
+    }
+}
@@ -1,7 +1,14 @@
 pub mod common;
 pub mod user;
+pub mod session;
+pub mod ledger;
 pub mod parking_lot;
 pub mod parking_space;
 pub mod vehicle;
 pub mod ticket;
-pub mod tariff;
\ No newline at end of file
+pub mod tariff;
+pub mod reservation;
+pub mod pass;
+pub mod gate;
+pub mod anpr;
+pub mod access_code;
\ No newline at end of file
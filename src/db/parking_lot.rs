@@ -1,25 +1,34 @@
-use std::str::FromStr;
+use std::{collections::BTreeMap, str::FromStr};
 
 use bson::{oid::ObjectId, doc};
-use chrono::{Utc, TimeZone, Datelike};
 use futures::StreamExt;
 
 use crate::structs::{
     error::MyError::{*, self}, 
-    model::{ParkingLot, ParkingLocation, VehicleType, Ticket},
-    response::{ParkingLotResponse, ParkingLotStatsResponse, ParkingLotStats, IncomeStats}, 
-    schema::{CreateParkingSchema, CreateParkingSpaceSchema},  
+    geo::{self, GeoPoint},
+    layout::{self, LevelDescription, PlannedSpot},
+    query::QueryNearbyParkingLots,
+    model::{ParkingLot, ParkingLocation, ParkingSpace, VehicleType, User, Role, PricingPolicy, RoundingMode, Calendar, AllocationStrategy},
+    reservation::ReservationPolicy,
+    response::{NearbyParkingLotResponse, ParkingLotResponse, ParkingLotStatsResponse, IncomeStats}, 
+    schema::{CreateParkingSchema, CreateParkingSpaceSchema, SetParkingLotOperatorSchema, UpdateParkingLotSchema},  
+    ticket_status::TicketStatus,
 };
 
-use super::common::DB;
+use super::common::{is_duplicate_key, DB};
 
 type Result<T> = std::result::Result<T, MyError>;
 
 impl DB {
-    pub async fn fetch_parkings(&self) -> Result<Vec<ParkingLotResponse>> {
+    /// Parking lots in service, or the archived ones when `archived` is set.
+    pub async fn fetch_parkings(&self, archived: bool) -> Result<Vec<ParkingLotResponse>> {
+        let filter = match archived {
+            true => doc! { "archived_at": { "$ne": null } },
+            false => doc! { "archived_at": null },
+        };
         let mut cursor = self
             .parking_lot_collection
-            .find(None, None)
+            .find(filter, None)
             .await
             .map_err(MongoQueryError)?;
     
@@ -32,59 +41,104 @@ impl DB {
     }
 
     pub async fn create_parking(&self, body: &CreateParkingSchema) -> Result<String> {
+        self.get_operator(&body.operator_id).await?;
+        self.validate_pricing_policy(&body.pricing_policy).await?;
+        body.reservation_policy.validate()?;
+        self.validate_allocation(&body.allocation, body.levels.len() as u32).await?;
+        let plans = body
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(idx, level)| layout::plan_level(idx as u32, &level.layout()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let geo = GeoPoint::try_from(&body.location)?;
+
         let new_parking_lot_id = ObjectId::new();
         let parking = ParkingLot {
             _id: new_parking_lot_id,
             cost_of_maintenance: body.cost_of_maintenance.to_owned(),
             location: body.location.to_owned(),
             no_levels: body.levels.len() as u32,
+            operator_id: body.operator_id.to_owned(),
+            pricing_policy: body.pricing_policy.to_owned(),
+            calendar: body.calendar.to_owned(),
+            allocation: body.allocation.to_owned(),
+            reservation_policy: body.reservation_policy.to_owned(),
+            archived_at: None,
+            geo: Some(geo),
         };
 
         match self.parking_lot_collection.insert_one(parking, None).await {
             Ok(result) => result,
-            Err(e) => {
-                if e.to_string()
-                    .contains("E110000 duplicate key error collection")
-                {
-                    return Err(MongoDuplicateError(e));
-                }
-                return Err(MongoQueryError(e));
-            }
+            Err(e) if is_duplicate_key(&e) => return Err(MongoDuplicateError(e)),
+            Err(e) => return Err(MongoQueryError(e)),
         };
 
+        // a lot whose tariffs or spaces cannot be stored is removed again
+        if let Err(e) = self.create_parking_contents(new_parking_lot_id, body, &plans).await {
+            self.discard_parking_lot(new_parking_lot_id).await?;
+            return Err(e);
+        }
+
+        Ok("Successful operation".to_string())
+    }
+
+    async fn create_parking_contents(&self, parking_lot_id: ObjectId, body: &CreateParkingSchema, plans: &[Vec<PlannedSpot>]) -> Result<()> {
+        self.create_tariff_set(&parking_lot_id.to_hex(), &body.tariffs, None).await?;
+
         let mut spot_name: u32 = 0;
-        for (idx, level) in body.levels.iter().enumerate() {
-            for _ in 0..level.cars {
-                self.create_parking_space(&CreateParkingSpaceSchema {
-                    parking_lot_id: new_parking_lot_id,
-                    location: ParkingLocation {
-                        no_level: idx as u32,
-                        no_space: spot_name,
-                    },
-                    vehicle_type: VehicleType::Car,
-                }).await?;
-                spot_name += 1;
-            }
-            for num in 0..level.trucks {
-                self.create_parking_space(&CreateParkingSpaceSchema {
-                    parking_lot_id: new_parking_lot_id,
-                    location: ParkingLocation {
-                        no_level: idx as u32,
-                        no_space: num,
-                    },
-                    vehicle_type: VehicleType::Truck,
-                }).await?;
-                spot_name += 1;
-            }
+        for (idx, spots) in plans.iter().enumerate() {
+            spot_name = self.create_level_spaces(parking_lot_id, idx as u32, spots, spot_name).await?;
         }
 
-        println!("body.tariffs: {:?}", body.tariffs);
+        Ok(())
+    }
+
+    /// Removes a parking lot that was not created in full, together with the
+    /// tariffs and spaces stored for it so far.
+    async fn discard_parking_lot(&self, parking_lot_id: ObjectId) -> Result<()> {
+        self.tariff_collection
+            .delete_many(doc! { "parking_lot_id": parking_lot_id.to_hex() }, None)
+            .await
+            .map_err(MongoQueryError)?;
+        self.tariff_set_collection
+            .delete_many(doc! { "parking_lot_id": parking_lot_id.to_hex() }, None)
+            .await
+            .map_err(MongoQueryError)?;
+        self.parking_space_collection
+            .delete_many(doc! { "parking_lot_id": parking_lot_id }, None)
+            .await
+            .map_err(MongoQueryError)?;
+        self.parking_lot_collection
+            .delete_one(doc! { "_id": parking_lot_id }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(())
+    }
 
-        for tariff in &body.tariffs {
-            self.create_tariff(tariff, &new_parking_lot_id.to_hex()).await?;
+    /// Creates the planned spaces of a level numbered from `first_spot` on and
+    /// returns the number of the next spot. Spot numbers run through the whole
+    /// lot; labels are unique on the level.
+    async fn create_level_spaces(&self, parking_lot_id: ObjectId, no_level: u32, spots: &[PlannedSpot], first_spot: u32) -> Result<u32> {
+        let mut spot_name = first_spot;
+        for spot in spots {
+            self.create_parking_space(&CreateParkingSpaceSchema {
+                parking_lot_id,
+                location: ParkingLocation {
+                    no_level,
+                    no_space: spot_name,
+                },
+                vehicle_type: spot.vehicle_type,
+                category: spot.category.to_owned(),
+                label: spot.label.to_owned(),
+                zone: spot.zone.to_owned(),
+                row: spot.row.to_owned(),
+            }).await?;
+            spot_name += 1;
         }
 
-        Ok("Successful operation".to_string())
+        Ok(spot_name)
     }
     
     fn doc_to_parking(&self, parking: &ParkingLot) -> Result<ParkingLotResponse> {
@@ -93,12 +147,24 @@ impl DB {
             cost_of_maintance: parking.cost_of_maintenance.to_owned(),
             location: parking.location.to_owned(),
             no_levels: parking.no_levels.to_owned(),
+            operator_id: parking.operator_id.to_owned(),
+            pricing_policy: parking.pricing_policy.to_owned(),
+            calendar: parking.calendar.to_owned(),
+            allocation: parking.allocation.to_owned(),
+            reservation_policy: parking.reservation_policy.to_owned(),
+            archived_at: parking.archived_at,
         };
 
         Ok(parking_response)
     }
 
     pub async fn get_parking_lot_by_id(&self, parking_lot_id: &str) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+
+        self.doc_to_parking(&parking_lot)
+    }
+
+    pub async fn get_parking_lot(&self, parking_lot_id: &str) -> Result<ParkingLot> {
         let oid = ObjectId::from_str(parking_lot_id).map_err(|_| InvalidIDError(parking_lot_id.to_owned()))?;
 
         let parking_lot = self
@@ -112,135 +178,384 @@ impl DB {
             .await
             .map_err(MongoQueryError)?;
 
-        println!("parking_lot: {:?}", parking_lot);
-        
         match parking_lot {
-            Some(doc) => Ok(self.doc_to_parking(&doc)?),
+            Some(doc) => Ok(doc),
             None => Err(NotFoundError(parking_lot_id.to_string()))
         }
     }
 
+    /// Space counts of each level in use, by level number. Levels are listed
+    /// by number, which has gaps where levels were removed.
     pub async fn get_parking_lot_levels_by_id(&self, parking_lot_id: &str) -> Result<Vec<ParkingLotStatsResponse>> {
-        let parking_spaces = self
-            .fetch_parking_spaces()
-            .await?
-            .into_iter()
-            .filter(|parking_space| parking_space.parking_lot_id == ObjectId::from_str(parking_lot_id).map_err(|_| InvalidIDError(parking_lot_id.to_owned())).unwrap())
-            .collect::<Vec<_>>();
-
-        let mut parking_lot_stats: Vec<ParkingLotStatsResponse> = Vec::new();
-        for parking_space in parking_spaces {
-            let index = parking_space.location.no_level as usize;
-            let occupied = parking_space.occupied;
-            match parking_lot_stats.get_mut(index) {
-                Some(parking_lot_stat) => 
-                    self.add_new_stat_occupied_space(parking_lot_stat, parking_space.vehicle_type, occupied),
-                None => {
-                    let mut parking_lot_stat = ParkingLotStatsResponse {
-                        truck: ParkingLotStats {
-                            spots_occupied: 0,
-                            spots_free: 0,
-                        },
-                        car: ParkingLotStats {
-                            spots_occupied: 0,
-                            spots_free: 0,
-                        },
-                    };
-                    self.add_new_stat_occupied_space(&mut parking_lot_stat, parking_space.vehicle_type, occupied);
-                    parking_lot_stats.push(parking_lot_stat);
-                }
-            }
+        let oid = ObjectId::from_str(parking_lot_id).map_err(|_| InvalidIDError(parking_lot_id.to_owned()))?;
+        let mut cursor = self
+            .parking_space_collection
+            .find(doc! { "parking_lot_id": oid, "retired": { "$ne": true } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut parking_lot_stats: BTreeMap<u32, ParkingLotStatsResponse> = BTreeMap::new();
+        while let Some(parking_space) = cursor.next().await {
+            let parking_space = parking_space.map_err(MongoQueryError)?;
+            let no_level = parking_space.location.no_level;
+            let parking_lot_stat = parking_lot_stats
+                .entry(no_level)
+                .or_insert_with(|| ParkingLotStatsResponse { no_level, ..Default::default() });
+            self.add_new_stat_occupied_space(parking_lot_stat, &parking_space);
         }
 
-        Ok(parking_lot_stats)
+        Ok(parking_lot_stats.into_values().collect())
     }
 
-    fn add_new_stat_occupied_space(&self, parking_lot_stat: &mut ParkingLotStatsResponse, vehicle_type: VehicleType, occupied: bool) {
-        match vehicle_type {
-            VehicleType::Car => {
-                if !occupied {
-                    parking_lot_stat.car.spots_free += 1;
-                } else {
-                    parking_lot_stat.car.spots_occupied += 1;
-                }
-            }
-            VehicleType::Truck => {
-                if !occupied {
-                    parking_lot_stat.truck.spots_free += 1;
-                } else {
-                    parking_lot_stat.truck.spots_occupied += 1;
-                }
-            
-            }
+    fn add_new_stat_occupied_space(&self, parking_lot_stat: &mut ParkingLotStatsResponse, parking_space: &ParkingSpace) {
+        let stat = match parking_space.vehicle_type {
+            VehicleType::Motorcycle => &mut parking_lot_stat.motorcycle,
+            VehicleType::Car => &mut parking_lot_stat.car,
+            VehicleType::Electric => &mut parking_lot_stat.electric,
+            VehicleType::Van => &mut parking_lot_stat.van,
+            VehicleType::Truck => &mut parking_lot_stat.truck,
+            VehicleType::Oversized => &mut parking_lot_stat.oversized,
+        };
+        if parking_space.occupied {
+            stat.spots_occupied += 1;
+        } else if parking_space.out_of_service {
+            stat.spots_out_of_service += 1;
+        } else {
+            stat.spots_free += 1;
         }
     }
 
-    pub async fn get_parking_lot_by_code(&self, code: &str) -> Result<ParkingLotResponse> {
-        println!("code: {}", code);
-        match code.len() {
-            0..=7 => return Err(InvalidCodeError(code.to_string())),
-            8 => (),
-            _ => return Err(InvalidCodeError(code.to_string())),
+    pub async fn get_parking_lot_income(&self, parking_lot_id: &str) -> Result<Vec<IncomeStats>> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+
+        self.fetch_parking_lot_revenue(&parking_lot.operator_id, parking_lot_id, doc! {}).await
+    }
+
+    /// Returns the user with the given id if it can own parking lots.
+    pub async fn get_operator(&self, operator_id: &str) -> Result<User> {
+        let operator = self.get_user_by_id(operator_id).await?;
+        if operator.role != Role::Operator {
+            return Err(ValidationError(format!("user {} is not an operator", operator_id)));
         }
-        let parking_lot = self
-            .fetch_parkings()
-            .await?
-            .into_iter()
-            .find(|parking_lot| parking_lot.id.starts_with(code))
-            .map(|parking_lot| parking_lot);
-
-        println!("parking_lot: {:?}", parking_lot);
-        
-        match parking_lot {
-            Some(doc) => Ok(doc),
-            None => Err(NotFoundError(code.to_string()))
+
+        Ok(operator)
+    }
+
+    /// Hands a parking lot over to another operator. Revenue already booked
+    /// stays with the previous operator.
+    pub async fn set_parking_lot_operator(&self, parking_lot_id: &str, body: &SetParkingLotOperatorSchema) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+        self.get_operator(&body.operator_id).await?;
+
+        self.parking_lot_collection
+            .update_one(
+                doc! { "_id": parking_lot._id },
+                doc! { "$set": { "operator_id": body.operator_id.to_owned() } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    pub async fn set_parking_lot_pricing_policy(&self, parking_lot_id: &str, body: &PricingPolicy) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+        self.validate_pricing_policy(body).await?;
+
+        self.parking_lot_collection
+            .update_one(
+                doc! { "_id": parking_lot._id },
+                doc! { "$set": { "pricing_policy": bson::to_bson(body)? } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    /// Replaces the time zone and holidays used to pick tariffs of a parking lot.
+    pub async fn set_parking_lot_calendar(&self, parking_lot_id: &str, body: &Calendar) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+
+        self.parking_lot_collection
+            .update_one(
+                doc! { "_id": parking_lot._id },
+                doc! { "$set": { "calendar": bson::to_bson(body)? } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    pub async fn set_parking_lot_allocation(&self, parking_lot_id: &str, body: &AllocationStrategy) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+        self.validate_allocation(body, parking_lot.no_levels).await?;
+
+        self.parking_lot_collection
+            .update_one(
+                doc! { "_id": parking_lot._id },
+                doc! { "$set": { "allocation": bson::to_bson(body)? } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    pub async fn set_parking_lot_reservation_policy(&self, parking_lot_id: &str, body: &ReservationPolicy) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+        body.validate()?;
+
+        self.parking_lot_collection
+            .update_one(
+                doc! { "_id": parking_lot._id },
+                doc! { "$set": { "reservation_policy": bson::to_bson(body)? } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    /// Open parking lots within the radius of the point, nearest first, with
+    /// the spaces free now for the vehicle type, or for any vehicle.
+    pub async fn get_nearby_parking_lots(&self, query: &QueryNearbyParkingLots) -> Result<Vec<NearbyParkingLotResponse>> {
+        let point = GeoPoint::new(query.lat, query.lng)?;
+        let radius = geo::validate_radius(query.radius)?;
+        if !(1..=geo::MAX_NEARBY_LIMIT).contains(&query.limit) {
+            return Err(ValidationError(format!("limit must be between 1 and {}", geo::MAX_NEARBY_LIMIT)));
         }
+        let pipeline = vec![
+            doc! {
+                "$geoNear": {
+                    "near": bson::to_bson(&point)?,
+                    "distanceField": "distance",
+                    "maxDistance": radius,
+                    "spherical": true,
+                    "query": { "archived_at": null },
+                },
+            },
+            doc! { "$limit": query.limit },
+        ];
+
+        let mut cursor = self
+            .parking_lot_collection
+            .aggregate(pipeline, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let space_types = query.vehicle_type.map(|vehicle_type| vehicle_type.space_types());
+        let mut json_result: Vec<NearbyParkingLotResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let doc = doc.map_err(MongoQueryError)?;
+            let distance = doc.get_f64("distance")?;
+            let parking_lot: ParkingLot = bson::from_document(doc)?;
+            json_result.push(NearbyParkingLotResponse {
+                free_spaces: self.count_free_parking_spaces(&parking_lot, space_types).await?,
+                parking_lot: self.doc_to_parking(&parking_lot)?,
+                distance,
+            });
+        }
+
+        Ok(json_result)
     }
 
-    pub async fn get_parking_lot_income(&self, parking_lot_id: &str) -> Result<Vec<IncomeStats>> {
+    /// Parking lot that still takes vehicles; archived lots can only be read.
+    pub async fn get_open_parking_lot(&self, parking_lot_id: &str) -> Result<ParkingLot> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+        if parking_lot.archived_at.is_some() {
+            return Err(ParkingLotArchivedError(parking_lot_id.to_owned()));
+        }
+
+        Ok(parking_lot)
+    }
+
+    pub async fn update_parking_lot(&self, parking_lot_id: &str, body: &UpdateParkingLotSchema) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+
+        let mut update = doc! {};
+        if let Some(cost_of_maintenance) = &body.cost_of_maintenance {
+            update.insert("cost_of_maintenance", bson::to_bson(cost_of_maintenance)?);
+        }
+        if let Some(location) = &body.location {
+            update.insert("location", bson::to_bson(location)?);
+            update.insert("geo", bson::to_bson(&GeoPoint::try_from(location)?)?);
+        }
+        if update.is_empty() {
+            return Err(ValidationError("nothing to update".to_string()));
+        }
+
+        self.parking_lot_collection
+            .update_one(doc! { "_id": parking_lot._id }, doc! { "$set": update }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    /// Builds a new top level with spots numbered after the last spot of the lot.
+    pub async fn add_parking_lot_level(&self, parking_lot_id: &str, body: &LevelDescription) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        let spots = layout::plan_level(parking_lot.no_levels, &body.layout())?;
+
+        // taking the level number first keeps two levels added at once from getting the same one
+        let result = self
+            .parking_lot_collection
+            .update_one(
+                doc! { "_id": parking_lot._id, "no_levels": parking_lot.no_levels },
+                doc! { "$set": { "no_levels": parking_lot.no_levels + 1 } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        if result.matched_count == 0 {
+            return Err(ParkingLotInUseError(format!("levels of parking lot {} changed, try again", parking_lot_id)));
+        }
+
+        let last_spot = self
+            .parking_space_collection
+            .find_one(
+                doc! { "parking_lot_id": parking_lot._id },
+                mongodb::options::FindOneOptions::builder().sort(doc! { "location.no_space": -1 }).build(),
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        let first_spot = last_spot.map_or(0, |parking_space| parking_space.location.no_space + 1);
+        self.create_level_spaces(parking_lot._id, parking_lot.no_levels, &spots, first_spot).await?;
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    /// Retires the spaces of a level. Levels with a parked vehicle, or with a
+    /// space kept for a reservation or pass, stay as they are. Retired spaces
+    /// keep their tickets and income; the level number is not reused.
+    pub async fn remove_parking_lot_level(&self, parking_lot_id: &str, level: u32) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        let filter = doc! { "parking_lot_id": parking_lot._id, "location.no_level": level, "retired": { "$ne": true } };
+
         let mut cursor = self
+            .parking_space_collection
+            .find(filter.clone(), None)
+            .await
+            .map_err(MongoQueryError)?;
+        let mut spaces: Vec<ParkingSpace> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let parking_space = doc.map_err(MongoQueryError)?;
+            if parking_space.occupied {
+                return Err(ParkingLotInUseError(format!("a vehicle is parked on level {}", level)));
+            }
+            spaces.push(parking_space);
+        }
+        if spaces.is_empty() {
+            return Err(NotFoundError(format!("level {} of parking lot {}", level, parking_lot_id)));
+        }
+        self.check_parking_spaces_not_kept(parking_lot_id, &spaces).await?;
+        let space_ids: Vec<ObjectId> = spaces.iter().map(|space| space._id).collect();
+
+        let mut retire = filter.clone();
+        retire.insert("occupied", false);
+        self.parking_space_collection
+            .update_many(retire, doc! { "$set": { "retired": true } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        // a vehicle that got a space in the meantime keeps it and the level stays
+        let claimed = self
+            .parking_space_collection
+            .count_documents(filter, None)
+            .await
+            .map_err(MongoQueryError)?;
+        if claimed > 0 {
+            self.parking_space_collection
+                .update_many(doc! { "_id": { "$in": &space_ids } }, doc! { "$set": { "retired": false } }, None)
+                .await
+                .map_err(MongoQueryError)?;
+            return Err(ParkingLotInUseError(format!("a vehicle is parked on level {}", level)));
+        }
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    /// Takes a parking lot out of service. It no longer takes vehicles,
+    /// reservations or passes, but its tickets and income stay readable.
+    pub async fn archive_parking_lot(&self, parking_lot_id: &str) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        let now = chrono::Utc::now().timestamp();
+
+        let parked = self
             .ticket_collection
-            .find(
-                doc! {
-                    "parking_lot_id": parking_lot_id.to_owned(),
-                },
+            .count_documents(
+                doc! { "parking_lot_id": parking_lot_id, "status": { "$in": bson::to_bson(&TicketStatus::ACTIVE)? } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        if parked > 0 {
+            return Err(ParkingLotInUseError(format!("{} tickets are still open", parked)));
+        }
+        let filter = doc! { "parking_lot_id": parking_lot_id };
+        if self.find_holding_reservations(filter, &doc! { "end": { "$gt": now } }).await?.next().await.is_some() {
+            return Err(ParkingLotInUseError("reservations are still booked".to_string()));
+        }
+        let passes = self
+            .pass_collection
+            .count_documents(doc! { "parking_lot_id": parking_lot_id, "valid_until": { "$gt": now } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+        if passes > 0 {
+            return Err(ParkingLotInUseError(format!("{} passes are still valid", passes)));
+        }
+
+        self.parking_lot_collection
+            .update_one(
+                doc! { "_id": parking_lot._id, "archived_at": null },
+                doc! { "$set": { "archived_at": now } },
                 None,
             )
             .await
             .map_err(MongoQueryError)?;
 
-        let mut json_result: Vec<IncomeStats> = Vec::new();
-        while let Some(doc) = cursor.next().await {
-            println!("doc: {:?}", doc);
-            let ticket: Ticket = doc.unwrap();
-            let issue_timestamp = chrono::NaiveDateTime::from_timestamp_opt(ticket.issue_timestamp, 0).unwrap();
-            let date_time = Utc.from_utc_datetime(&issue_timestamp).month();
-            let month = match date_time {
-                1 => "January",
-                2 => "February",
-                3 => "March",
-                4 => "April",
-                5 => "May",
-                6 => "June",
-                7 => "July",
-                8 => "August",
-                9 => "September",
-                10 => "October",
-                11 => "November",
-                _ => "December",
-            }.to_string();
-
-            let income = ticket.amount_paid;
-            if let Some(stats) = json_result.iter_mut().find(|stats| stats.month == month) {
-                stats.income += income;
-            } else {
-                json_result.push(IncomeStats {
-                    month,
-                    income,
-                });
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    pub async fn restore_parking_lot(&self, parking_lot_id: &str) -> Result<ParkingLotResponse> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+
+        self.parking_lot_collection
+            .update_one(doc! { "_id": parking_lot._id }, doc! { "$set": { "archived_at": null } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        self.get_parking_lot_by_id(parking_lot_id).await
+    }
+
+    async fn validate_allocation(&self, allocation: &AllocationStrategy, no_levels: u32) -> Result<()> {
+        if let AllocationStrategy::Nearest { level, .. } = allocation {
+            if *level >= no_levels {
+                return Err(ValidationError(format!("parking lot has no level {}", level)));
             }
         }
-        println!("json_result: {:?}", json_result);    
-        Ok(json_result)
+
+        Ok(())
+    }
+
+    async fn validate_pricing_policy(&self, policy: &PricingPolicy) -> Result<()> {
+        if policy.cap.is_some_and(|cap| !cap.is_positive()) {
+            return Err(ValidationError("daily cap must be positive".to_string()));
+        }
+        if policy.minimum.is_some_and(|minimum| minimum.is_negative()) {
+            return Err(ValidationError("minimum charge cannot be negative".to_string()));
+        }
+        if policy.rounding.mode != RoundingMode::None && policy.rounding.step < 1 {
+            return Err(ValidationError("rounding step must be at least 1".to_string()));
+        }
+
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1,36 +1,25 @@
-use std::str::FromStr;
+use std::{cmp::Reverse, collections::HashMap, str::FromStr};
 
 use bson::{doc, oid::ObjectId};
-use chrono::{DateTime, Datelike, TimeZone, Utc};
 use futures::StreamExt;
-
-use crate::structs::{
-    error::MyError::{self, *},
-    model::{ParkingSpace, Ticket, VehicleType},
-    response::{IncomeStats, IncomeStatsResponse, ParkingSpaceResponse},
-    schema::CreateParkingSpaceSchema,
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+
+use crate::{
+    pricing::calculator::Stay,
+    structs::{
+        error::MyError::{self, *},
+        model::{AllocationStrategy, ParkingLot, ParkingSpace, Ticket, VehicleType},
+        money::{Currency, Money, Multiplier},
+        response::{IncomeStatsResponse, ParkingSpaceResponse},
+        schema::{CreateParkingSpaceSchema, UpdateLevelSpacesSchema, UpdateParkingSpaceSchema, UpdateSpotPricingSchema},
+    },
 };
 
-use super::common::DB;
+use super::common::{is_duplicate_key, DB};
 
 type Result<T> = std::result::Result<T, MyError>;
 
 impl DB {
-    pub async fn fetch_parking_spaces(&self) -> Result<Vec<ParkingSpace>> {
-        let mut cursor = self
-            .parking_space_collection
-            .find(None, None)
-            .await
-            .map_err(MongoQueryError)?;
-
-        let mut json_result: Vec<ParkingSpace> = Vec::new();
-        while let Some(doc) = cursor.next().await {
-            json_result.push(doc.unwrap());
-        }
-
-        Ok(json_result)
-    }
-
     pub async fn create_parking_space(
         &self,
         parking_space: &CreateParkingSpaceSchema,
@@ -42,104 +31,135 @@ impl DB {
             location: parking_space.location.to_owned(),
             vehicle_type: parking_space.vehicle_type.to_owned(),
             occupied: false,
-            price_modifier: 1.0,
+            price_modifier: Multiplier::ONE,
+            category: parking_space.category.to_owned(),
+            retired: false,
+            out_of_service: false,
+            label: parking_space.label.to_owned(),
+            zone: parking_space.zone.to_owned(),
+            row: parking_space.row.to_owned(),
         };
 
         match self
             .parking_space_collection
-            .insert_one(parking_space, None)
+            .insert_one(&parking_space, None)
             .await
         {
             Ok(result) => result,
-            Err(e) => {
-                if e.to_string()
-                    .contains("E110000 duplicate key error collection")
-                {
-                    return Err(MongoDuplicateError(e));
-                }
-                return Err(MongoQueryError(e));
+            Err(e) if is_duplicate_key(&e) => {
+                return Err(ParkingSpaceInUseError(format!(
+                    "label {} is taken on level {}",
+                    parking_space.label, parking_space.location.no_level
+                )));
             }
+            Err(e) => return Err(MongoQueryError(e)),
         };
 
         Ok("Successful operation".to_string())
     }
 
-    pub async fn get_new_parking_space_by_license_number(
+    /// Picks a free space for the vehicle with the strategy of the lot and
+    /// claims it with a `find_one_and_update`, so two vehicles arriving at once
+    /// never get the same space. Spaces taken in between, and spaces held for
+    /// reservations about to start or kept for passes, are skipped.
+    pub async fn claim_parking_space(
         &self,
-        licence_number: &str,
-        parking_lot_id: &str,
+        parking_lot: &ParkingLot,
+        space_types: &[VehicleType],
+        preferred_level: Option<u32>,
     ) -> Result<ParkingSpace> {
-        let vehicle_type: &str = match licence_number.as_bytes()[0] {
-            b'A' => "Truck",
-            _ => "Car",
-        };
-
-        let mut cursor = self
-            .parking_space_collection
-            .find(
-                doc! {
-                    "parking_lot_id": ObjectId::from_str(parking_lot_id).map_err(|_| InvalidIDError(parking_lot_id.to_owned()))?,
-                    "vehicle_type": vehicle_type,
-                    "occupied": false,
-                },
-                None,
-            )
-            .await
-            .map_err(MongoQueryError)?;
+        let strategy = spot_strategy(&parking_lot.allocation, preferred_level);
+
+        loop {
+            let filter = self.free_parking_spaces_filter(parking_lot, Some(space_types)).await?;
+            let mut cursor = self
+                .parking_space_collection
+                .find(filter, None)
+                .await
+                .map_err(MongoQueryError)?;
+
+            let mut candidates: Vec<ParkingSpace> = Vec::new();
+            while let Some(doc) = cursor.next().await {
+                candidates.push(doc.map_err(MongoQueryError)?);
+            }
+            if candidates.is_empty() {
+                return Err(NoParkingSpaceError(parking_lot._id.to_hex()));
+            }
 
-        let mut json_result: Vec<ParkingSpace> = Vec::new();
-        while let Some(doc) = cursor.next().await {
-            json_result.push(doc.unwrap());
-            break;
+            rank_for_vehicle(strategy.as_ref(), space_types, &mut candidates);
+            for candidate in candidates {
+                let parking_space = self
+                    .parking_space_collection
+                    .find_one_and_update(
+                        doc! {
+                            "_id": candidate._id,
+                            "occupied": false,
+                        },
+                        doc! {
+                            "$set": {
+                                "occupied": true,
+                            },
+                        },
+                        FindOneAndUpdateOptions::builder()
+                            .return_document(ReturnDocument::After)
+                            .build(),
+                    )
+                    .await
+                    .map_err(MongoQueryError)?;
+
+                if let Some(parking_space) = parking_space {
+                    return Ok(parking_space);
+                }
+            }
         }
+    }
 
-        if json_result.len() == 0 {
-            return Err(NoParkingSpaceError(licence_number.to_owned()));
+    /// Spaces of the lot that can be handed out now, of `space_types` or of any
+    /// type: free, in service, and not held for a reservation or kept for a pass.
+    async fn free_parking_spaces_filter(&self, parking_lot: &ParkingLot, space_types: Option<&[VehicleType]>) -> Result<bson::Document> {
+        let now = chrono::Utc::now().timestamp();
+        let mut held = self.get_held_parking_spaces(parking_lot).await?;
+        held.extend(self.get_pass_parking_spaces(&parking_lot._id.to_hex(), now, now).await?);
+
+        let mut filter = doc! {
+            "_id": { "$nin": held },
+            "parking_lot_id": parking_lot._id,
+            "occupied": false,
+            "retired": { "$ne": true },
+            "out_of_service": { "$ne": true },
+        };
+        if let Some(space_types) = space_types {
+            filter.insert("vehicle_type", doc! { "$in": bson::to_bson(space_types)? });
         }
 
-        self.toggle_occupied_parking_space(&json_result[0]._id.to_hex())
-            .await?;
+        Ok(filter)
+    }
 
-        println!("json_result: {:?}", json_result[0]);
+    pub async fn count_free_parking_spaces(&self, parking_lot: &ParkingLot, space_types: Option<&[VehicleType]>) -> Result<u64> {
+        let filter = self.free_parking_spaces_filter(parking_lot, space_types).await?;
 
-        Ok(json_result[0].to_owned())
+        self.parking_space_collection
+            .count_documents(filter, None)
+            .await
+            .map_err(MongoQueryError)
     }
 
-    pub async fn toggle_occupied_parking_space(&self, parking_space_id: &str) -> Result<String> {
+    /// Marks a space taken or free. Returns whether it changed, so releasing
+    /// a space twice is harmless.
+    pub async fn set_parking_space_occupied(&self, parking_space_id: &str, occupied: bool) -> Result<bool> {
         let oid = ObjectId::from_str(parking_space_id)
             .map_err(|_| InvalidIDError(parking_space_id.to_owned()))?;
 
-        let parking_space = match self
+        let result = self
             .parking_space_collection
-            .find_one(
-                doc! {
-                    "_id": oid,
-                },
-                None,
-            )
-            .await
-            .map_err(MongoQueryError)?
-        {
-            Some(parking_space) => Some(parking_space),
-            None => {
-                return Err(NotFoundError(format!(
-                    "parking_space with id: {}",
-                    parking_space_id
-                )))
-            }
-        };
-
-        let mut parking_space = parking_space.unwrap();
-        parking_space.occupied = !parking_space.occupied;
-
-        self.parking_space_collection
             .update_one(
                 doc! {
                     "_id": oid,
+                    "occupied": !occupied,
                 },
                 doc! {
                     "$set": {
-                        "occupied": parking_space.occupied,
+                        "occupied": occupied,
                     },
                 },
                 None,
@@ -147,7 +167,7 @@ impl DB {
             .await
             .map_err(MongoQueryError)?;
 
-        Ok("Successful operation".to_string())
+        Ok(result.modified_count == 1)
     }
 
     pub async fn get_parking_space_by_parking_spot_id(
@@ -155,30 +175,14 @@ impl DB {
         parking_lot_id: &str,
         parking_space_id: &str,
     ) -> Result<ParkingSpace> {
-        let parking_space = self
-            .fetch_parking_spaces()
-            .await?
-            .into_iter()
-            .find(|parking_space| {
-                parking_space.parking_lot_id
-                    == ObjectId::from_str(parking_lot_id)
-                        .map_err(|_| InvalidIDError(parking_lot_id.to_owned()))
-                        .unwrap()
-                    && parking_space._id
-                        == ObjectId::from_str(parking_space_id)
-                            .map_err(|_| InvalidIDError(parking_space_id.to_owned()))
-                            .unwrap()
-            });
+        let parking_lot_oid = ObjectId::from_str(parking_lot_id).map_err(|_| InvalidIDError(parking_lot_id.to_owned()))?;
+        let oid = ObjectId::from_str(parking_space_id).map_err(|_| InvalidIDError(parking_space_id.to_owned()))?;
 
-        println!("parking_space: {:?}", parking_space);
-
-        match parking_space {
-            Some(parking_space) => Ok(parking_space),
-            None => Err(NotFoundError(format!(
-                "parking_space with id: {}",
-                parking_space_id
-            ))),
-        }
+        self.parking_space_collection
+            .find_one(doc! { "_id": oid, "parking_lot_id": parking_lot_oid }, None)
+            .await
+            .map_err(MongoQueryError)?
+            .ok_or_else(|| NotFoundError(format!("parking_space with id: {}", parking_space_id)))
     }
 
     pub async fn get_parking_spaces_by_parking_lot_id(
@@ -206,52 +210,38 @@ impl DB {
 
         let mut json_result: Vec<ParkingSpaceResponse> = Vec::new();
         while let Some(doc) = cursor.next().await {
-            let parking_space: ParkingSpace = doc.unwrap();
-            json_result.push(ParkingSpaceResponse {
-                id: parking_space._id.to_hex(),
-                parking_lot_id: parking_space.parking_lot_id.to_hex(),
-                level: parking_space.location.no_level,
-                ordinal_number: parking_space.location.no_space,
-                vehicle_type: match parking_space.vehicle_type {
-                    VehicleType::Car => "Car".to_string(),
-                    VehicleType::Truck => "Truck".to_string(),
-                },
-                is_occupied: parking_space.occupied,
-            });
+            json_result.push(self.doc_to_parking_space(&doc.unwrap()));
         }
 
         Ok(json_result)
     }
 
+    fn doc_to_parking_space(&self, parking_space: &ParkingSpace) -> ParkingSpaceResponse {
+        ParkingSpaceResponse {
+            id: parking_space._id.to_hex(),
+            parking_lot_id: parking_space.parking_lot_id.to_hex(),
+            level: parking_space.location.no_level,
+            ordinal_number: parking_space.location.no_space,
+            vehicle_type: parking_space.vehicle_type.to_string(),
+            is_occupied: parking_space.occupied,
+            category: parking_space.category.to_owned(),
+            price_modifier: parking_space.price_modifier,
+            retired: parking_space.retired,
+            out_of_service: parking_space.out_of_service,
+            label: parking_space.label.to_owned(),
+            zone: parking_space.zone.to_owned(),
+            row: parking_space.row.to_owned(),
+        }
+    }
+
     pub async fn get_parking_space_income(
         &self,
         parking_lot_id: &str,
         parking_space_id: &str,
     ) -> Result<IncomeStatsResponse> {
         let parking_space = self
-            .fetch_parking_spaces()
-            .await?
-            .into_iter()
-            .find(|parking_space| {
-                parking_space.parking_lot_id
-                    == ObjectId::from_str(parking_lot_id)
-                        .map_err(|_| InvalidIDError(parking_lot_id.to_owned()))
-                        .unwrap()
-                    && parking_space._id
-                        == ObjectId::from_str(parking_space_id)
-                            .map_err(|_| InvalidIDError(parking_space_id.to_owned()))
-                            .unwrap()
-            });
-
-        let parking_space = match parking_space {
-            Some(parking_space) => parking_space,
-            None => {
-                return Err(NotFoundError(format!(
-                    "parking_space with id: {}",
-                    parking_space_id
-                )))
-            }
-        };
+            .get_parking_space_by_parking_spot_id(parking_lot_id, parking_space_id)
+            .await?;
 
         let mut cursor = self
             .ticket_collection
@@ -268,72 +258,440 @@ impl DB {
         println!("parking_space: {:?}", parking_space);
         // println!("cursor: {:?}", cursor.next().await);
 
-        let mut json_result: Vec<IncomeStats> = Vec::new();
+        let mut tickets: Vec<Ticket> = Vec::new();
         while let Some(doc) = cursor.next().await {
-            let ticket: Ticket = doc.unwrap();
-            let issue_timestamp =
-                chrono::NaiveDateTime::from_timestamp_opt(ticket.issue_timestamp, 0).unwrap();
-            let date_time = Utc.from_utc_datetime(&issue_timestamp).month();
-            let month = match date_time {
-                1 => "January",
-                2 => "February",
-                3 => "March",
-                4 => "April",
-                5 => "May",
-                6 => "June",
-                7 => "July",
-                8 => "August",
-                9 => "September",
-                10 => "October",
-                11 => "November",
-                _ => "December",
+            tickets.push(doc.unwrap());
+        }
+
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+        let ticket_ids: Vec<String> = tickets.iter().map(|ticket| ticket._id.to_hex()).collect();
+        let json_result = self
+            .fetch_parking_lot_revenue(&parking_lot.operator_id, parking_lot_id, doc! { "ticket_id": { "$in": ticket_ids } })
+            .await?;
+
+        // `now` is the price of the stay in progress, `today` adds tickets closed since midnight UTC
+        let now_timestamp = chrono::Utc::now().timestamp();
+        let today_timestamp = now_timestamp - now_timestamp.rem_euclid(24 * 3600);
+        let mut today_income = Money::zero(Currency::default());
+        let mut now_income = Money::zero(Currency::default());
+        for ticket in tickets {
+            if ticket.end_timestamp == 0 {
+                let stay = Stay { start: ticket.issue_timestamp, end: now_timestamp };
+                let spot = self.get_ticket_priced_spot(&ticket, &parking_space, &stay).await?;
+                let amount = self
+                    .price_stay(&parking_lot, &spot, &stay)
+                    .await?
+                    .total()?;
+                now_income = now_income.checked_add(amount)?;
+                today_income = today_income.checked_add(amount)?;
+            } else if ticket.end_timestamp >= today_timestamp {
+                today_income = today_income.checked_add(ticket.amount_paid)?;
             }
-            .to_string();
+        }
+
+        Ok(IncomeStatsResponse {
+            stats: json_result,
+            today: today_income,
+            now: now_income,
+        })
+    }
+
+    /// Changes the category and price modifier of one parking space.
+    pub async fn update_spot_pricing(&self, parking_lot_id: &str, parking_space_id: &str, body: &UpdateSpotPricingSchema) -> Result<String> {
+        let parking_space = self.get_parking_space_by_parking_spot_id(parking_lot_id, parking_space_id).await?;
+
+        self.update_spaces_pricing(doc! { "_id": parking_space._id }, body).await
+    }
+
+    /// Changes the category and price modifier of every parking space on a level.
+    pub async fn update_level_pricing(&self, parking_lot_id: &str, level: u32, body: &UpdateSpotPricingSchema) -> Result<String> {
+        let oid = ObjectId::from_str(parking_lot_id).map_err(|_| InvalidIDError(parking_lot_id.to_owned()))?;
+
+        self.update_spaces_pricing(doc! { "parking_lot_id": oid, "location.no_level": level }, body).await
+    }
+
+    async fn update_spaces_pricing(&self, filter: bson::Document, body: &UpdateSpotPricingSchema) -> Result<String> {
+        let update = self.pricing_update(body)?;
+        if update.is_empty() {
+            return Err(ValidationError("nothing to update".to_string()));
+        }
+
+        let result = self
+            .parking_space_collection
+            .update_many(filter, doc! { "$set": update }, None)
+            .await
+            .map_err(MongoQueryError)?;
 
-            let income = ticket.amount_paid;
-            if let Some(stats) = json_result.iter_mut().find(|stats| stats.month == month) {
-                stats.income += income;
-            } else {
-                json_result.push(IncomeStats { month, income });
+        if result.matched_count == 0 {
+            return Err(NotFoundError("parking spaces".to_string()));
+        }
+
+        Ok("Successful operation".to_string())
+    }
+
+    /// Changes one space of an open parking lot. The vehicle type of a space
+    /// only changes while it is free and not kept for a reservation or pass,
+    /// and a space going out of service must not be kept either; a vehicle
+    /// already parked on it stays until it leaves.
+    pub async fn update_parking_space(&self, parking_lot_id: &str, parking_space_id: &str, body: &UpdateParkingSpaceSchema) -> Result<ParkingSpaceResponse> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        let oid = ObjectId::from_str(parking_space_id).map_err(|_| InvalidIDError(parking_space_id.to_owned()))?;
+        let parking_space = self
+            .parking_space_collection
+            .find_one(doc! { "_id": oid, "parking_lot_id": parking_lot._id, "retired": { "$ne": true } }, None)
+            .await
+            .map_err(MongoQueryError)?
+            .ok_or_else(|| NotFoundError(format!("parking_space with id: {}", parking_space_id)))?;
+
+        let spaces = [parking_space];
+        let mut update = self.spaces_update(&parking_lot, &spaces, body.out_of_service, body.vehicle_type, &body.pricing).await?;
+        if let Some(ordinal_number) = body.ordinal_number {
+            self.check_ordinal_numbers_free(&parking_lot, &[oid], ordinal_number, 1).await?;
+            update.insert("location.no_space", ordinal_number);
+        }
+        if let Some(label) = &body.label {
+            let label = label.trim();
+            if label.is_empty() {
+                return Err(ValidationError("label cannot be empty".to_string()));
             }
+            update.insert("label", label);
+        }
+        if update.is_empty() {
+            return Err(ValidationError("nothing to update".to_string()));
         }
-        println!("json_result: {:?}", json_result);
 
-        let mut today_income = 0.0;
-        let mut now_income = 0.0;
-        let today_date_time = chrono::Utc::now().timestamp();
-        while let Some(doc) = cursor.next().await {
-            let ticket: Ticket = doc.unwrap();
+        let mut filter = doc! { "_id": oid };
+        if body.vehicle_type.is_some() {
+            filter.insert("occupied", false);
+        }
+        let parking_space = self
+            .parking_space_collection
+            .find_one_and_update(
+                filter,
+                doc! { "$set": update },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await
+            .map_err(|e| match is_duplicate_key(&e) {
+                true => ParkingSpaceInUseError(format!("label {} is taken on level {}", body.label.as_deref().unwrap_or_default(), spaces[0].location.no_level)),
+                false => MongoQueryError(e),
+            })?
+            .ok_or_else(|| ParkingSpaceInUseError(format!("a vehicle is parked on spot {}", spaces[0].location.no_space)))?;
 
-            let tariffs = self
-                .get_tariffs_by_parking_lot_id_ascending(&ticket.parking_lot_id)
-                .await?;
+        Ok(self.doc_to_parking_space(&parking_space))
+    }
 
-            let total_time = (today_date_time - ticket.issue_timestamp) / 36000 + 1;
+    /// Applies the same change to every space of a level in service, and
+    /// renumbers them in their current order when asked to.
+    pub async fn update_level_spaces(&self, parking_lot_id: &str, level: u32, body: &UpdateLevelSpacesSchema) -> Result<Vec<ParkingSpaceResponse>> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        let filter = doc! { "parking_lot_id": parking_lot._id, "location.no_level": level, "retired": { "$ne": true } };
+        let mut cursor = self
+            .parking_space_collection
+            .find(filter.clone(), FindOptions::builder().sort(doc! { "location.no_space": 1 }).build())
+            .await
+            .map_err(MongoQueryError)?;
+        let mut spaces: Vec<ParkingSpace> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            spaces.push(doc.map_err(MongoQueryError)?);
+        }
+        if spaces.is_empty() {
+            return Err(NotFoundError(format!("level {} of parking lot {}", level, parking_lot_id)));
+        }
 
-            let mut amount_paid = 0.0;
-            while let Some(tariff) = tariffs.iter().next() {
-                if total_time >= tariff.min_time && total_time <= tariff.max_time {
-                    amount_paid = parking_space.price_modifier * tariff.price_per_hour * total_time as f64;
-                    break;
-                }
+        let update = self.spaces_update(&parking_lot, &spaces, body.out_of_service, body.vehicle_type, &body.pricing).await?;
+        let space_ids: Vec<ObjectId> = spaces.iter().map(|space| space._id).collect();
+        if let Some(first) = body.first_ordinal_number {
+            self.check_ordinal_numbers_free(&parking_lot, &space_ids, first, spaces.len() as u32).await?;
+        } else if update.is_empty() {
+            return Err(ValidationError("nothing to update".to_string()));
+        }
+
+        if !update.is_empty() {
+            let mut filter = filter;
+            if body.vehicle_type.is_some() {
+                // spaces taken in the meantime keep their type
+                filter.insert("occupied", false);
             }
-    
-            if amount_paid == 0.0 {
-                amount_paid = parking_space.price_modifier * tariffs.last().unwrap().price_per_hour * total_time as f64;
+            self.parking_space_collection
+                .update_many(filter, doc! { "$set": update }, None)
+                .await
+                .map_err(MongoQueryError)?;
+        }
+        if let Some(first) = body.first_ordinal_number {
+            for (space_id, ordinal_number) in space_ids.iter().zip(first..) {
+                self.parking_space_collection
+                    .update_one(doc! { "_id": space_id }, doc! { "$set": { "location.no_space": ordinal_number } }, None)
+                    .await
+                    .map_err(MongoQueryError)?;
             }
+        }
 
-            today_income += amount_paid;
+        self.get_parking_spaces_by_parking_lot_id(parking_lot_id, level as i32).await
+    }
 
-            if ticket.end_timestamp == 0 {
-                now_income += amount_paid;
+    /// Fields to set on `spaces` for a change of service or vehicle type and
+    /// pricing, refused while the spaces are in use for it.
+    async fn spaces_update(
+        &self,
+        parking_lot: &ParkingLot,
+        spaces: &[ParkingSpace],
+        out_of_service: Option<bool>,
+        vehicle_type: Option<VehicleType>,
+        pricing: &UpdateSpotPricingSchema,
+    ) -> Result<bson::Document> {
+        let mut update = self.pricing_update(pricing)?;
+        if let Some(vehicle_type) = vehicle_type {
+            if let Some(space) = spaces.iter().find(|space| space.occupied) {
+                return Err(ParkingSpaceInUseError(format!("a vehicle is parked on spot {}", space.location.no_space)));
             }
+            update.insert("vehicle_type", bson::to_bson(&vehicle_type)?);
+        }
+        if vehicle_type.is_some() || out_of_service == Some(true) {
+            self.check_parking_spaces_not_kept(&parking_lot._id.to_hex(), spaces).await?;
+        }
+        if let Some(out_of_service) = out_of_service {
+            update.insert("out_of_service", out_of_service);
         }
 
-        Ok(IncomeStatsResponse {
-            stats: json_result,
-            today: today_income,
-            now: now_income,
-        })
+        Ok(update)
+    }
+
+    /// Fails when one of the spaces is held for a reservation or kept for a
+    /// pass now or later.
+    pub async fn check_parking_spaces_not_kept(&self, parking_lot_id: &str, spaces: &[ParkingSpace]) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let spot_number = |space_id: &str| {
+            spaces
+                .iter()
+                .find(|space| space._id.to_hex() == space_id)
+                .map_or(0, |space| space.location.no_space)
+        };
+
+        let space_hexes: Vec<String> = spaces.iter().map(|space| space._id.to_hex()).collect();
+        let filter = doc! { "parking_spot_id": { "$in": &space_hexes } };
+        if let Some(reservation) = self.find_holding_reservations(filter, &doc! { "end": { "$gt": now } }).await?.next().await {
+            let reservation = reservation.map_err(MongoQueryError)?;
+            return Err(ParkingSpaceInUseError(format!("spot {} is reserved", spot_number(&reservation.parking_spot_id))));
+        }
+        let pass_spaces = self.get_pass_parking_spaces(parking_lot_id, now, i64::MAX).await?;
+        if let Some(space_id) = pass_spaces.iter().find(|space_id| space_hexes.contains(&space_id.to_hex())) {
+            return Err(ParkingSpaceInUseError(format!("spot {} is kept for a pass", spot_number(&space_id.to_hex()))));
+        }
+
+        Ok(())
+    }
+
+    /// Fails when a space of the lot other than `space_ids` has a number in
+    /// `first..first + count`. Retired spaces keep their numbers.
+    async fn check_ordinal_numbers_free(&self, parking_lot: &ParkingLot, space_ids: &[ObjectId], first: u32, count: u32) -> Result<()> {
+        let taken = self
+            .parking_space_collection
+            .find_one(
+                doc! {
+                    "parking_lot_id": parking_lot._id,
+                    "_id": { "$nin": space_ids },
+                    "location.no_space": { "$gte": first, "$lt": first as i64 + count as i64 },
+                },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        match taken {
+            Some(space) => Err(ParkingSpaceInUseError(format!("spot number {} is taken", space.location.no_space))),
+            None => Ok(()),
+        }
+    }
+
+    /// Fields to set for a change of category or price modifier.
+    fn pricing_update(&self, body: &UpdateSpotPricingSchema) -> Result<bson::Document> {
+        let mut update = doc! {};
+        if let Some(category) = &body.category {
+            update.insert("category", bson::to_bson(category)?);
+        }
+        if let Some(price_modifier) = body.price_modifier {
+            if price_modifier.0 == 0 {
+                return Err(ValidationError("price modifier must be positive".to_string()));
+            }
+            update.insert("price_modifier", price_modifier.0);
+        }
+
+        Ok(update)
+    }
+}
+
+/// Orders the free spaces of a parking lot, the one to hand out first at the
+/// front. Ties are broken by level and space number so the order is stable.
+pub trait SpotStrategy: Send + Sync {
+    fn rank(&self, spaces: &mut [ParkingSpace]);
+}
+
+pub fn spot_strategy(allocation: &AllocationStrategy, preferred_level: Option<u32>) -> Box<dyn SpotStrategy> {
+    let strategy: Box<dyn SpotStrategy> = match allocation {
+        AllocationStrategy::LowestLevel => Box::new(LowestLevel),
+        AllocationStrategy::FillLevel => Box::new(FillLevel),
+        AllocationStrategy::SpreadLevels => Box::new(SpreadLevels),
+        AllocationStrategy::Nearest { level, space } => Box::new(Nearest { level: *level, space: *space }),
+    };
+
+    match preferred_level {
+        Some(level) => Box::new(PreferredLevel { level, fallback: strategy }),
+        None => strategy,
+    }
+}
+
+/// Ranks `spaces` with `strategy`, then moves the space types that fit the
+/// vehicle best to the front.
+pub fn rank_for_vehicle(strategy: &dyn SpotStrategy, space_types: &[VehicleType], spaces: &mut [ParkingSpace]) {
+    strategy.rank(spaces);
+    spaces.sort_by_key(|space| space_types.iter().position(|space_type| *space_type == space.vehicle_type));
+}
+
+fn position(space: &ParkingSpace) -> (u32, u32) {
+    (space.location.no_level, space.location.no_space)
+}
+
+fn free_per_level(spaces: &[ParkingSpace]) -> HashMap<u32, usize> {
+    let mut free: HashMap<u32, usize> = HashMap::new();
+    for space in spaces {
+        *free.entry(space.location.no_level).or_default() += 1;
+    }
+    free
+}
+
+pub struct LowestLevel;
+
+impl SpotStrategy for LowestLevel {
+    fn rank(&self, spaces: &mut [ParkingSpace]) {
+        spaces.sort_by_key(position);
+    }
+}
+
+pub struct FillLevel;
+
+impl SpotStrategy for FillLevel {
+    fn rank(&self, spaces: &mut [ParkingSpace]) {
+        let free = free_per_level(spaces);
+        spaces.sort_by_key(|space| (free[&space.location.no_level], position(space)));
+    }
+}
+
+pub struct SpreadLevels;
+
+impl SpotStrategy for SpreadLevels {
+    fn rank(&self, spaces: &mut [ParkingSpace]) {
+        let free = free_per_level(spaces);
+        spaces.sort_by_key(|space| (Reverse(free[&space.location.no_level]), position(space)));
+    }
+}
+
+pub struct Nearest {
+    pub level: u32,
+    pub space: u32,
+}
+
+impl SpotStrategy for Nearest {
+    fn rank(&self, spaces: &mut [ParkingSpace]) {
+        spaces.sort_by_key(|space| {
+            (
+                space.location.no_level.abs_diff(self.level),
+                space.location.no_space.abs_diff(self.space),
+                position(space),
+            )
+        });
+    }
+}
+
+/// Spaces on the level the driver asked for first, the others as `fallback`
+/// ranks them.
+pub struct PreferredLevel {
+    pub level: u32,
+    pub fallback: Box<dyn SpotStrategy>,
+}
+
+impl SpotStrategy for PreferredLevel {
+    fn rank(&self, spaces: &mut [ParkingSpace]) {
+        self.fallback.rank(spaces);
+        spaces.sort_by_key(|space| space.location.no_level != self.level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::model::{ParkingLocation, SpotCategory};
+
+    /// Free spaces: three on level 0, one on level 1, two on level 2.
+    fn free_spaces() -> Vec<ParkingSpace> {
+        [(2, 5), (0, 3), (1, 0), (0, 1), (2, 4), (0, 2)]
+            .into_iter()
+            .map(|(no_level, no_space)| ParkingSpace {
+                _id: ObjectId::new(),
+                parking_lot_id: ObjectId::new(),
+                location: ParkingLocation { no_level, no_space },
+                vehicle_type: VehicleType::Car,
+                occupied: false,
+                price_modifier: Multiplier::ONE,
+                category: SpotCategory::Standard,
+                retired: false,
+                out_of_service: false,
+                label: String::new(),
+                zone: None,
+                row: None,
+            })
+            .collect()
+    }
+
+    fn ranked(allocation: AllocationStrategy, preferred_level: Option<u32>) -> Vec<(u32, u32)> {
+        let mut spaces = free_spaces();
+        spot_strategy(&allocation, preferred_level).rank(&mut spaces);
+        spaces.iter().map(position).collect()
+    }
+
+    #[test]
+    fn lowest_level_goes_by_level_then_space() {
+        assert_eq!(
+            ranked(AllocationStrategy::LowestLevel, None),
+            vec![(0, 1), (0, 2), (0, 3), (1, 0), (2, 4), (2, 5)]
+        );
+    }
+
+    #[test]
+    fn fill_level_starts_with_the_fullest_level() {
+        assert_eq!(
+            ranked(AllocationStrategy::FillLevel, None),
+            vec![(1, 0), (2, 4), (2, 5), (0, 1), (0, 2), (0, 3)]
+        );
+    }
+
+    #[test]
+    fn spread_levels_starts_with_the_emptiest_level() {
+        assert_eq!(
+            ranked(AllocationStrategy::SpreadLevels, None),
+            vec![(0, 1), (0, 2), (0, 3), (2, 4), (2, 5), (1, 0)]
+        );
+    }
+
+    #[test]
+    fn nearest_prefers_the_entrance_level() {
+        assert_eq!(
+            ranked(AllocationStrategy::Nearest { level: 2, space: 5 }, None),
+            vec![(2, 5), (2, 4), (1, 0), (0, 3), (0, 2), (0, 1)]
+        );
+    }
+
+    #[test]
+    fn preferred_level_comes_first_and_falls_back() {
+        assert_eq!(
+            ranked(AllocationStrategy::SpreadLevels, Some(2)),
+            vec![(2, 4), (2, 5), (0, 1), (0, 2), (0, 3), (1, 0)]
+        );
+        assert_eq!(
+            ranked(AllocationStrategy::LowestLevel, Some(7)),
+            ranked(AllocationStrategy::LowestLevel, None)
+        );
     }
 }
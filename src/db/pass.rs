@@ -0,0 +1,492 @@
+use std::str::FromStr;
+
+use bson::{doc, oid::ObjectId, Document};
+use futures::StreamExt;
+use mongodb::{options::FindOptions, Cursor};
+
+use crate::{
+    pricing::{calculator::{PassCoverage, PricedSpot, Stay}, tariffs::validate_schedule},
+    structs::{
+        error::MyError::{self, *},
+        model::{ParkingLot, ParkingSpace, Pass, PassPlan, Ticket, TransactionKind, Vehicle},
+        money::Multiplier,
+        response::{PassPlanResponse, PassResponse},
+        schema::{BuyPassSchema, CreatePassPlanSchema},
+    },
+};
+
+use super::{common::DB, ledger::Transfer, parking_space::{rank_for_vehicle, spot_strategy}};
+
+type Result<T> = std::result::Result<T, MyError>;
+
+/// Passes set to renew automatically are renewed this long before they run out.
+const AUTO_RENEW_AHEAD: i64 = 24 * 60 * 60;
+
+impl DB {
+    pub async fn create_pass_plan(&self, parking_lot_id: &str, body: &CreatePassPlanSchema) -> Result<PassPlanResponse> {
+        let parking_lot = self.get_open_parking_lot(parking_lot_id).await?;
+        if !body.price.is_positive() {
+            return Err(ValidationError("pass price must be positive".to_string()));
+        }
+        let rate = body.rate.unwrap_or(Multiplier(0));
+        if rate.0 > Multiplier::ONE.0 {
+            return Err(ValidationError("a pass cannot raise the price of a stay".to_string()));
+        }
+        validate_schedule(&body.schedule)?;
+
+        let plan = PassPlan {
+            _id: ObjectId::new(),
+            parking_lot_id: parking_lot._id.to_hex(),
+            name: body.name.to_owned(),
+            period: body.period,
+            price: body.price,
+            schedule: body.schedule.to_owned(),
+            rate,
+            reserved_spot: body.reserved_spot,
+            active: true,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        self.pass_plan_collection
+            .insert_one(&plan, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(self.doc_to_pass_plan(&plan))
+    }
+
+    /// Plans of a parking lot still on sale.
+    pub async fn get_pass_plans(&self, parking_lot_id: &str) -> Result<Vec<PassPlanResponse>> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+        let mut cursor = self
+            .pass_plan_collection
+            .find(doc! { "parking_lot_id": parking_lot._id.to_hex(), "active": true }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<PassPlanResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            json_result.push(self.doc_to_pass_plan(&doc.map_err(MongoQueryError)?));
+        }
+
+        Ok(json_result)
+    }
+
+    /// Takes a plan off sale. Passes already bought stay valid until they end
+    /// but are not renewed.
+    pub async fn retire_pass_plan(&self, parking_lot_id: &str, plan_id: &str) -> Result<PassPlanResponse> {
+        let plan = self.get_pass_plan(plan_id).await?;
+        if plan.parking_lot_id != parking_lot_id {
+            return Err(NotFoundError(format!("pass plan {} of parking lot {}", plan_id, parking_lot_id)));
+        }
+
+        self.pass_plan_collection
+            .update_one(doc! { "_id": plan._id }, doc! { "$set": { "active": false } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(self.doc_to_pass_plan(&PassPlan { active: false, ..plan }))
+    }
+
+    async fn get_pass_plan(&self, plan_id: &str) -> Result<PassPlan> {
+        let oid = ObjectId::from_str(plan_id).map_err(|_| InvalidIDError(plan_id.to_owned()))?;
+        let plan = self
+            .pass_plan_collection
+            .find_one(doc! { "_id": oid }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        match plan {
+            Some(plan) => Ok(plan),
+            None => Err(NotFoundError(format!("pass plan with id: {}", plan_id))),
+        }
+    }
+
+    /// Buys a pass for one of the user's vehicles and charges its price to the
+    /// wallet. Reserved-spot plans keep a space for the vehicle as long as the
+    /// pass lasts.
+    pub async fn buy_pass(&self, user_id: &str, body: &BuyPassSchema) -> Result<PassResponse> {
+        let plan = self.get_pass_plan(&body.plan_id).await?;
+        if !plan.active {
+            return Err(ValidationError(format!("pass plan {} is no longer sold", body.plan_id)));
+        }
+        let parking_lot = self.get_open_parking_lot(&plan.parking_lot_id).await?;
+        let vehicle = self.get_vehicle_by_license_plate_number(&body.vehicle_license_number).await?;
+        if vehicle.user_id != user_id {
+            return Err(ForbiddenError(user_id.to_owned()));
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let valid_from = body.valid_from.unwrap_or(now);
+        if valid_from < now {
+            return Err(ValidationError("a pass cannot start in the past".to_string()));
+        }
+        let valid_until = plan.period.end(valid_from, &parking_lot.calendar);
+
+        let filter = doc! { "parking_lot_id": &plan.parking_lot_id, "vehicle_license_number": &body.vehicle_license_number };
+        if self.find_passes(filter, valid_from, valid_until).await?.next().await.is_some() {
+            return Err(PassConflictError(format!("vehicle {} already has a pass for this time", body.vehicle_license_number)));
+        }
+
+        let pass = Pass {
+            _id: ObjectId::new(),
+            plan_id: plan._id.to_hex(),
+            user_id: user_id.to_owned(),
+            parking_lot_id: plan.parking_lot_id.to_owned(),
+            vehicle_license_number: body.vehicle_license_number.to_owned(),
+            valid_from,
+            valid_until,
+            parking_spot_id: None,
+            auto_renew: body.auto_renew,
+            created_at: now,
+        };
+        let pass = match plan.reserved_spot {
+            true => self.hold_pass_parking_space(&parking_lot, &vehicle, pass).await?,
+            false => {
+                self.pass_collection
+                    .insert_one(&pass, None)
+                    .await
+                    .map_err(MongoQueryError)?;
+                pass
+            }
+        };
+
+        if let Err(e) = self.charge_pass(&pass, &plan).await {
+            self.pass_collection
+                .delete_one(doc! { "_id": pass._id }, None)
+                .await
+                .map_err(MongoQueryError)?;
+            return Err(e);
+        }
+
+        Ok(self.doc_to_pass(&pass))
+    }
+
+    /// Stores the pass with a space picked like one for an arriving vehicle
+    /// among those no other pass or reservation holds while it lasts. Of a
+    /// pass and another pass or reservation stored for the same space at once
+    /// at least one sees the other and moves on.
+    async fn hold_pass_parking_space(&self, parking_lot: &ParkingLot, vehicle: &Vehicle, pass: Pass) -> Result<Pass> {
+        let overlapping = doc! { "start": { "$lt": pass.valid_until }, "end": { "$gt": pass.valid_from } };
+        let mut taken = self.get_reserved_parking_spaces(parking_lot, &overlapping).await?;
+        taken.extend(self.get_pass_parking_spaces(&pass.parking_lot_id, pass.valid_from, pass.valid_until).await?);
+
+        let space_types = vehicle.vehicle_type.compatible_spaces(vehicle.dimensions.as_ref());
+        let mut cursor = self
+            .parking_space_collection
+            .find(
+                doc! {
+                    "_id": { "$nin": taken },
+                    "parking_lot_id": parking_lot._id,
+                    "vehicle_type": { "$in": bson::to_bson(&space_types)? },
+                    "retired": { "$ne": true },
+                    "out_of_service": { "$ne": true },
+                },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        let mut candidates: Vec<ParkingSpace> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            candidates.push(doc.map_err(MongoQueryError)?);
+        }
+
+        rank_for_vehicle(spot_strategy(&parking_lot.allocation, None).as_ref(), &space_types, &mut candidates);
+        for candidate in candidates {
+            let pass = Pass { parking_spot_id: Some(candidate._id.to_hex()), ..pass.clone() };
+            self.pass_collection
+                .insert_one(&pass, None)
+                .await
+                .map_err(MongoQueryError)?;
+
+            let parking_spot_id = candidate._id.to_hex();
+            let held = self.is_held_by_pass(&parking_spot_id, pass.valid_from, pass.valid_until, Some(pass._id)).await?
+                || self.find_holding_reservations(doc! { "parking_spot_id": &parking_spot_id }, &overlapping).await?.next().await.is_some();
+            if !held {
+                return Ok(pass);
+            }
+
+            self.pass_collection
+                .delete_one(doc! { "_id": pass._id }, None)
+                .await
+                .map_err(MongoQueryError)?;
+        }
+
+        Err(NoParkingSpaceError(parking_lot._id.to_hex()))
+    }
+
+    /// Whether a reserved-spot pass other than `except` keeps the space at
+    /// some point from `start` until `end`.
+    pub async fn is_held_by_pass(&self, parking_spot_id: &str, start: i64, end: i64, except: Option<ObjectId>) -> Result<bool> {
+        let mut filter = doc! { "parking_spot_id": parking_spot_id };
+        if let Some(pass_id) = except {
+            filter.insert("_id", doc! { "$ne": pass_id });
+        }
+
+        Ok(self.find_passes(filter, start, end).await?.next().await.is_some())
+    }
+
+    /// Passes matching `filter` valid at some point from `start` until `end`.
+    async fn find_passes(&self, mut filter: Document, start: i64, end: i64) -> Result<Cursor<Pass>> {
+        filter.insert("valid_from", doc! { "$lt": end });
+        filter.insert("valid_until", doc! { "$gt": start });
+
+        self.pass_collection
+            .find(filter, None)
+            .await
+            .map_err(MongoQueryError)
+    }
+
+    /// Spaces kept for reserved-spot passes at some point from `start` until
+    /// `end`. A pass that renews automatically keeps its space after it ends.
+    pub async fn get_pass_parking_spaces(&self, parking_lot_id: &str, start: i64, end: i64) -> Result<Vec<ObjectId>> {
+        let mut cursor = self
+            .pass_collection
+            .find(
+                doc! {
+                    "parking_lot_id": parking_lot_id,
+                    "parking_spot_id": { "$ne": null },
+                    "valid_from": { "$lte": end },
+                    "$or": [{ "valid_until": { "$gt": start } }, { "auto_renew": true }],
+                },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut parking_spot_ids: Vec<ObjectId> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            if let Some(parking_spot_id) = doc.map_err(MongoQueryError)?.parking_spot_id {
+                parking_spot_ids.push(ObjectId::from_str(&parking_spot_id).map_err(|_| InvalidIDError(parking_spot_id))?);
+            }
+        }
+
+        Ok(parking_spot_ids)
+    }
+
+    /// Space kept for the vehicle by a reserved-spot pass valid now.
+    pub async fn get_pass_parking_space(&self, parking_lot_id: &str, vehicle_license_number: &str) -> Result<Option<String>> {
+        let now = chrono::Utc::now().timestamp();
+        let filter = doc! {
+            "parking_lot_id": parking_lot_id,
+            "vehicle_license_number": vehicle_license_number,
+            "parking_spot_id": { "$ne": null },
+        };
+        let pass = self.find_passes(filter, now, now + 1).await?.next().await;
+
+        match pass {
+            Some(pass) => Ok(pass.map_err(MongoQueryError)?.parking_spot_id),
+            None => Ok(None),
+        }
+    }
+
+    /// The spot of a ticket as it is priced: with the pass of its vehicle, if
+    /// one is valid during the stay. Of several passes the cheapest rate wins.
+    pub async fn get_ticket_priced_spot(&self, ticket: &Ticket, parking_space: &ParkingSpace, stay: &Stay) -> Result<PricedSpot> {
+        let filter = doc! {
+            "parking_lot_id": &ticket.parking_lot_id,
+            "vehicle_license_number": &ticket.vehicle_license_number,
+        };
+        let mut cursor = self.find_passes(filter, stay.start, stay.end.max(stay.start + 1)).await?;
+
+        let mut coverage: Option<PassCoverage> = None;
+        while let Some(doc) = cursor.next().await {
+            let pass = doc.map_err(MongoQueryError)?;
+            let plan = self.get_pass_plan(&pass.plan_id).await?;
+            if coverage.as_ref().is_some_and(|coverage| coverage.rate.0 <= plan.rate.0) {
+                continue;
+            }
+            coverage = Some(PassCoverage {
+                name: plan.name,
+                valid_from: pass.valid_from,
+                valid_until: pass.valid_until,
+                schedule: plan.schedule,
+                rate: plan.rate,
+            });
+        }
+
+        Ok(PricedSpot { pass: coverage, ..parking_space.into() })
+    }
+
+    pub async fn get_user_passes(&self, user_id: &str) -> Result<Vec<PassResponse>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "valid_from": -1 })
+            .build();
+        let mut cursor = self
+            .pass_collection
+            .find(doc! { "user_id": user_id }, options)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<PassResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            json_result.push(self.doc_to_pass(&doc.map_err(MongoQueryError)?));
+        }
+
+        Ok(json_result)
+    }
+
+    async fn get_pass(&self, id: &str) -> Result<Pass> {
+        let oid = ObjectId::from_str(id).map_err(|_| InvalidIDError(id.to_owned()))?;
+        let pass = self
+            .pass_collection
+            .find_one(doc! { "_id": oid }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        match pass {
+            Some(pass) => Ok(pass),
+            None => Err(NotFoundError(format!("pass with id: {}", id))),
+        }
+    }
+
+    pub async fn renew_pass(&self, id: &str, user_id: &str) -> Result<PassResponse> {
+        let pass = self.get_pass(id).await?;
+        if pass.user_id != user_id {
+            return Err(ForbiddenError(user_id.to_owned()));
+        }
+
+        let pass = self.extend_pass(&pass).await?;
+        Ok(self.doc_to_pass(&pass))
+    }
+
+    pub async fn set_pass_auto_renew(&self, id: &str, user_id: &str, auto_renew: bool) -> Result<PassResponse> {
+        let pass = self.get_pass(id).await?;
+        if pass.user_id != user_id {
+            return Err(ForbiddenError(user_id.to_owned()));
+        }
+
+        self.pass_collection
+            .update_one(doc! { "_id": pass._id }, doc! { "$set": { "auto_renew": auto_renew } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(self.doc_to_pass(&Pass { auto_renew, ..pass }))
+    }
+
+    /// Renews the passes set to renew automatically that are about to run
+    /// out. A pass that cannot be renewed stops renewing. Returns how many
+    /// were renewed.
+    pub async fn renew_passes(&self) -> Result<u64> {
+        let now = chrono::Utc::now().timestamp();
+        let mut cursor = self
+            .pass_collection
+            .find(doc! { "auto_renew": true, "valid_until": { "$lte": now + AUTO_RENEW_AHEAD } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut renewed = 0;
+        while let Some(doc) = cursor.next().await {
+            let pass = doc.map_err(MongoQueryError)?;
+            match self.extend_pass(&pass).await {
+                Ok(_) => renewed += 1,
+                Err(e) => {
+                    println!("pass {} not renewed: {}", pass._id.to_hex(), e);
+                    self.pass_collection
+                        .update_one(doc! { "_id": pass._id }, doc! { "$set": { "auto_renew": false } }, None)
+                        .await
+                        .map_err(MongoQueryError)?;
+                }
+            }
+        }
+
+        Ok(renewed)
+    }
+
+    /// Extends a pass by one period of its plan and charges the price. A pass
+    /// that already ran out starts again now.
+    async fn extend_pass(&self, pass: &Pass) -> Result<Pass> {
+        let plan = self.get_pass_plan(&pass.plan_id).await?;
+        if !plan.active {
+            return Err(ValidationError(format!("pass plan {} is no longer sold", pass.plan_id)));
+        }
+        let parking_lot = self.get_open_parking_lot(&pass.parking_lot_id).await?;
+
+        let now = chrono::Utc::now().timestamp();
+        let (valid_from, start) = match pass.valid_until < now {
+            true => (now, now),
+            false => (pass.valid_from, pass.valid_until),
+        };
+        let valid_until = plan.period.end(start, &parking_lot.calendar);
+
+        if let Some(parking_spot_id) = &pass.parking_spot_id {
+            let overlapping = doc! { "start": { "$lt": valid_until }, "end": { "$gt": start } };
+            let filter = doc! { "parking_spot_id": parking_spot_id };
+            if self.find_holding_reservations(filter, &overlapping).await?.next().await.is_some() {
+                return Err(PassConflictError(format!("parking space {} is reserved after the pass ends", parking_spot_id)));
+            }
+        }
+
+        // matching the old end keeps two renewals of the same pass from both going through
+        let result = self
+            .pass_collection
+            .update_one(
+                doc! { "_id": pass._id, "valid_until": pass.valid_until },
+                doc! { "$set": { "valid_from": valid_from, "valid_until": valid_until } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        if result.matched_count == 0 {
+            return Err(PassConflictError(format!("pass {} changed, try again", pass._id.to_hex())));
+        }
+
+        let extended = Pass { valid_from, valid_until, ..pass.clone() };
+        if let Err(e) = self.charge_pass(&extended, &plan).await {
+            self.pass_collection
+                .update_one(
+                    doc! { "_id": pass._id, "valid_until": valid_until },
+                    doc! { "$set": { "valid_from": pass.valid_from, "valid_until": pass.valid_until } },
+                    None,
+                )
+                .await
+                .map_err(MongoQueryError)?;
+            return Err(e);
+        }
+
+        Ok(extended)
+    }
+
+    async fn charge_pass(&self, pass: &Pass, plan: &PassPlan) -> Result<()> {
+        let parking_lot = self.get_parking_lot(&pass.parking_lot_id).await?;
+
+        self.post_transfer(&Transfer {
+            from: &pass.user_id,
+            to: &parking_lot.operator_id,
+            amount: plan.price,
+            kind: TransactionKind::PassPurchase,
+            ticket_id: None,
+            parking_lot_id: Some(pass.parking_lot_id.to_owned()),
+            description: format!("Pass {} for {}", plan.name, pass.vehicle_license_number),
+        }).await?;
+
+        Ok(())
+    }
+
+    fn doc_to_pass_plan(&self, plan: &PassPlan) -> PassPlanResponse {
+        PassPlanResponse {
+            id: plan._id.to_hex(),
+            parking_lot_id: plan.parking_lot_id.to_owned(),
+            name: plan.name.to_owned(),
+            period: plan.period,
+            price: plan.price,
+            schedule: plan.schedule.to_owned(),
+            rate: plan.rate,
+            reserved_spot: plan.reserved_spot,
+            active: plan.active,
+        }
+    }
+
+    fn doc_to_pass(&self, pass: &Pass) -> PassResponse {
+        PassResponse {
+            id: pass._id.to_hex(),
+            plan_id: pass.plan_id.to_owned(),
+            parking_lot_id: pass.parking_lot_id.to_owned(),
+            vehicle_license_number: pass.vehicle_license_number.to_owned(),
+            valid_from: pass.valid_from,
+            valid_until: pass.valid_until,
+            parking_spot_id: pass.parking_spot_id.to_owned(),
+            auto_renew: pass.auto_renew,
+            created_at: pass.created_at,
+        }
+    }
+}
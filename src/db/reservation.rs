@@ -0,0 +1,425 @@
+use std::{collections::HashMap, str::FromStr};
+
+use bson::{doc, oid::ObjectId, Document};
+use futures::StreamExt;
+use mongodb::{options::FindOptions, Cursor};
+
+use crate::structs::{
+    error::MyError::{self, *},
+    model::{ParkingLot, ParkingSpace, Reservation, Role, TransactionKind, User},
+    money::Money,
+    reservation::ReservationStatus,
+    response::ReservationResponse,
+    schema::CreateReservationSchema,
+};
+
+use super::{common::DB, ledger::Transfer, parking_space::{rank_for_vehicle, spot_strategy}};
+
+type Result<T> = std::result::Result<T, MyError>;
+
+impl DB {
+    /// Books a parking space for a time window. The space is picked like one
+    /// for an arriving vehicle among those no other reservation holds in the
+    /// window; when the window is about to start it must also be free now.
+    pub async fn create_reservation(&self, user_id: &str, body: &CreateReservationSchema) -> Result<ReservationResponse> {
+        let now = chrono::Utc::now().timestamp();
+        let parking_lot = self.get_open_parking_lot(&body.parking_lot_id).await?;
+        let policy = &parking_lot.reservation_policy;
+        policy.validate_window(body.start, body.end, now)?;
+
+        let (vehicle_type, dimensions) = self
+            .get_vehicle_type(&body.vehicle_license_number, body.vehicle_type.as_ref())
+            .await?;
+
+        let overlapping = doc! { "start": { "$lt": body.end }, "end": { "$gt": body.start } };
+        if self.find_holding_reservations(doc! { "vehicle_license_number": &body.vehicle_license_number }, &overlapping).await?.next().await.is_some() {
+            return Err(ReservationConflictError(format!("vehicle {} is already booked for this time", body.vehicle_license_number)));
+        }
+
+        let space_types = vehicle_type.compatible_spaces(dimensions.as_ref());
+        let mut reserved = self.get_reserved_parking_spaces(&parking_lot, &overlapping).await?;
+        reserved.extend(self.get_pass_parking_spaces(&parking_lot._id.to_hex(), body.start, body.end).await?);
+        let mut filter = doc! {
+            "_id": { "$nin": reserved },
+            "parking_lot_id": parking_lot._id,
+            "vehicle_type": { "$in": bson::to_bson(&space_types)? },
+            "retired": { "$ne": true },
+            "out_of_service": { "$ne": true },
+        };
+        if policy.holds(body.start, now) {
+            filter.insert("occupied", false);
+        }
+
+        let mut cursor = self
+            .parking_space_collection
+            .find(filter, None)
+            .await
+            .map_err(MongoQueryError)?;
+        let mut candidates: Vec<ParkingSpace> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            candidates.push(doc.map_err(MongoQueryError)?);
+        }
+
+        let strategy = spot_strategy(&parking_lot.allocation, body.preferred_level);
+        rank_for_vehicle(strategy.as_ref(), &space_types, &mut candidates);
+        for candidate in candidates {
+            let reservation = Reservation {
+                _id: ObjectId::new(),
+                user_id: user_id.to_owned(),
+                parking_lot_id: body.parking_lot_id.to_owned(),
+                vehicle_license_number: body.vehicle_license_number.to_owned(),
+                vehicle_type,
+                parking_spot_id: candidate._id.to_hex(),
+                level: candidate.location.no_level,
+                spot_ordinal_number: candidate.location.no_space,
+                start: body.start,
+                end: body.end,
+                status: ReservationStatus::Booked,
+                ticket_code: None,
+                fee: None,
+                created_at: now,
+            };
+
+            if self.hold_parking_space(&reservation, &overlapping).await? {
+                return Ok(self.doc_to_reservation(&reservation));
+            }
+        }
+
+        Err(NoParkingSpaceError(body.parking_lot_id.to_owned()))
+    }
+
+    /// Stores the reservation and checks nobody booked the same space for an
+    /// overlapping window, or bought a pass keeping it, in the meantime. Of two
+    /// such bookings at least one sees the other and withdraws, so a space is
+    /// never booked twice.
+    async fn hold_parking_space(&self, reservation: &Reservation, overlapping: &Document) -> Result<bool> {
+        self.reservation_collection
+            .insert_one(reservation, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let filter = doc! { "_id": { "$ne": reservation._id }, "parking_spot_id": &reservation.parking_spot_id };
+        let held = self.find_holding_reservations(filter, overlapping).await?.next().await.is_some()
+            || self.is_held_by_pass(&reservation.parking_spot_id, reservation.start, reservation.end, None).await?;
+        if !held {
+            return Ok(true);
+        }
+
+        self.reservation_collection
+            .delete_one(doc! { "_id": reservation._id }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(false)
+    }
+
+    pub async fn find_holding_reservations(&self, mut filter: Document, overlapping: &Document) -> Result<Cursor<Reservation>> {
+        filter.insert("status", doc! { "$in": bson::to_bson(&ReservationStatus::HOLDING)? });
+        filter.extend(overlapping.clone());
+
+        self.reservation_collection
+            .find(filter, None)
+            .await
+            .map_err(MongoQueryError)
+    }
+
+    pub async fn get_reserved_parking_spaces(&self, parking_lot: &ParkingLot, overlapping: &Document) -> Result<Vec<ObjectId>> {
+        let cursor = self
+            .find_holding_reservations(doc! { "parking_lot_id": parking_lot._id.to_hex() }, overlapping)
+            .await?;
+
+        self.collect_parking_spot_ids(cursor).await
+    }
+
+    /// Spaces of a parking lot kept for booked reservations that start soon
+    /// or have started; arriving vehicles without a reservation do not get them.
+    pub async fn get_held_parking_spaces(&self, parking_lot: &ParkingLot) -> Result<Vec<ObjectId>> {
+        let now = chrono::Utc::now().timestamp();
+        let cursor = self
+            .reservation_collection
+            .find(
+                doc! {
+                    "parking_lot_id": parking_lot._id.to_hex(),
+                    "status": bson::to_bson(&ReservationStatus::Booked)?,
+                    "start": { "$lte": now + parking_lot.reservation_policy.hold_before },
+                    "end": { "$gt": now },
+                },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        self.collect_parking_spot_ids(cursor).await
+    }
+
+    async fn collect_parking_spot_ids(&self, mut cursor: Cursor<Reservation>) -> Result<Vec<ObjectId>> {
+        let mut parking_spot_ids: Vec<ObjectId> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let reservation = doc.map_err(MongoQueryError)?;
+            let oid = ObjectId::from_str(&reservation.parking_spot_id).map_err(|_| InvalidIDError(reservation.parking_spot_id))?;
+            parking_spot_ids.push(oid);
+        }
+
+        Ok(parking_spot_ids)
+    }
+
+    pub async fn get_reservation(&self, id: &str) -> Result<Reservation> {
+        let oid = ObjectId::from_str(id).map_err(|_| InvalidIDError(id.to_owned()))?;
+        let reservation = self
+            .reservation_collection
+            .find_one(doc! { "_id": oid }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        match reservation {
+            Some(reservation) => Ok(reservation),
+            None => Err(MongoNotFound(format!("reservation with id: {}", id))),
+        }
+    }
+
+    pub async fn get_user_reservations(&self, user_id: &str) -> Result<Vec<ReservationResponse>> {
+        self.fetch_reservations(doc! { "user_id": user_id }).await
+    }
+
+    pub async fn get_parking_lot_reservations(&self, parking_lot_id: &str) -> Result<Vec<ReservationResponse>> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+
+        self.fetch_reservations(doc! { "parking_lot_id": parking_lot._id.to_hex() }).await
+    }
+
+    async fn fetch_reservations(&self, filter: Document) -> Result<Vec<ReservationResponse>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "start": 1 })
+            .build();
+        let mut cursor = self
+            .reservation_collection
+            .find(filter, options)
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut json_result: Vec<ReservationResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            json_result.push(self.doc_to_reservation(&doc.map_err(MongoQueryError)?));
+        }
+
+        Ok(json_result)
+    }
+
+    /// Cancels a booked reservation. Its owner pays the cancellation fee when
+    /// cancelling late; an administrator cancels free of charge. Cancelling
+    /// again returns the reservation unchanged.
+    pub async fn cancel_reservation(&self, id: &str, user: &User) -> Result<ReservationResponse> {
+        let reservation = self.get_reservation(id).await?;
+        let is_admin = user.role == Role::Admin;
+        if reservation.user_id != user._id.to_hex() && !is_admin {
+            return Err(ForbiddenError(user._id.to_hex()));
+        }
+        if reservation.status == ReservationStatus::Cancelled {
+            return Ok(self.doc_to_reservation(&reservation));
+        }
+
+        let parking_lot = self.get_parking_lot(&reservation.parking_lot_id).await?;
+        let fee = match is_admin {
+            true => None,
+            false => parking_lot
+                .reservation_policy
+                .cancellation_fee(reservation.start, chrono::Utc::now().timestamp()),
+        };
+
+        let update = doc! { "fee": bson::to_bson(&fee)? };
+        if !self.transition_reservation(&reservation, ReservationStatus::Cancelled, update).await? {
+            return Err(ReservationConflictError(format!("reservation {} changed, try again", id)));
+        }
+
+        if let Some(fee) = fee {
+            let cancelled = Reservation { status: ReservationStatus::Cancelled, ..reservation.clone() };
+            if let Err(e) = self.charge_reservation_fee(&cancelled, &parking_lot, fee, "cancellation").await {
+                self.transition_reservation(&cancelled, ReservationStatus::Booked, doc! { "fee": null }).await?;
+                return Err(e);
+            }
+        }
+
+        Ok(self.doc_to_reservation(&self.get_reservation(id).await?))
+    }
+
+    /// Booked reservation of the vehicle it can check in with now, if any.
+    pub async fn find_arriving_reservation(&self, parking_lot: &ParkingLot, vehicle_license_number: &str) -> Result<Option<Reservation>> {
+        let now = chrono::Utc::now().timestamp();
+        let policy = &parking_lot.reservation_policy;
+        let reservation = self
+            .reservation_collection
+            .find_one(
+                doc! {
+                    "parking_lot_id": parking_lot._id.to_hex(),
+                    "vehicle_license_number": vehicle_license_number,
+                    "status": bson::to_bson(&ReservationStatus::Booked)?,
+                    "start": { "$lte": now + policy.hold_before, "$gte": now - policy.no_show_after },
+                },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(reservation)
+    }
+
+    /// Turns a reservation into a ticket when its vehicle arrives. The vehicle
+    /// gets the reserved space, or another free one if the reserved space is
+    /// still taken. Returns the code of the ticket.
+    pub async fn check_in_reservation(&self, id: &str, user_id: &str) -> Result<String> {
+        let reservation = self.get_reservation(id).await?;
+        if reservation.user_id != user_id {
+            return Err(ForbiddenError(user_id.to_owned()));
+        }
+
+        let parking_lot = self.get_parking_lot(&reservation.parking_lot_id).await?;
+        parking_lot
+            .reservation_policy
+            .can_check_in(reservation.start, chrono::Utc::now().timestamp())?;
+
+        if !self.transition_reservation(&reservation, ReservationStatus::CheckedIn, doc! {}).await? {
+            return Err(ReservationConflictError(format!("reservation {} changed, try again", id)));
+        }
+
+        let checked_in = Reservation { status: ReservationStatus::CheckedIn, ..reservation.clone() };
+        match self.issue_reserved_ticket(&reservation, &parking_lot).await {
+            Ok(code) => {
+                self.reservation_collection
+                    .update_one(
+                        doc! { "_id": reservation._id },
+                        doc! { "$set": { "ticket_code": &code } },
+                        None,
+                    )
+                    .await
+                    .map_err(MongoQueryError)?;
+                Ok(code)
+            }
+            Err(e) => {
+                self.transition_reservation(&checked_in, ReservationStatus::Booked, doc! {}).await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn issue_reserved_ticket(&self, reservation: &Reservation, parking_lot: &ParkingLot) -> Result<String> {
+        let parking_space = if self.set_parking_space_occupied(&reservation.parking_spot_id, true).await? {
+            self.get_parking_space_by_parking_spot_id(&reservation.parking_lot_id, &reservation.parking_spot_id)
+                .await?
+        } else {
+            let (vehicle_type, dimensions) = self
+                .get_vehicle_type(&reservation.vehicle_license_number, Some(&reservation.vehicle_type))
+                .await?;
+            self.claim_parking_space(parking_lot, &vehicle_type.compatible_spaces(dimensions.as_ref()), Some(reservation.level))
+                .await?
+        };
+
+        self.store_ticket(&reservation.user_id, &reservation.vehicle_license_number, &reservation.parking_lot_id, &parking_space)
+            .await
+    }
+
+    /// Marks booked reservations whose vehicle did not arrive in time as
+    /// no-shows and charges their fee. Returns how many expired.
+    pub async fn expire_reservations(&self) -> Result<u64> {
+        let now = chrono::Utc::now().timestamp();
+        let mut cursor = self
+            .reservation_collection
+            .find(
+                doc! {
+                    "status": bson::to_bson(&ReservationStatus::Booked)?,
+                    "start": { "$lt": now },
+                },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        let mut parking_lots: HashMap<String, ParkingLot> = HashMap::new();
+        let mut expired = 0;
+        while let Some(doc) = cursor.next().await {
+            let reservation = doc.map_err(MongoQueryError)?;
+            if !parking_lots.contains_key(&reservation.parking_lot_id) {
+                let parking_lot = self.get_parking_lot(&reservation.parking_lot_id).await?;
+                parking_lots.insert(reservation.parking_lot_id.to_owned(), parking_lot);
+            }
+            let parking_lot = &parking_lots[&reservation.parking_lot_id];
+            let policy = &parking_lot.reservation_policy;
+            if !policy.is_no_show(reservation.start, now) {
+                continue;
+            }
+
+            let fee = policy.no_show_fee.filter(|fee| fee.is_positive());
+            let update = doc! { "fee": bson::to_bson(&fee)? };
+            if !self.transition_reservation(&reservation, ReservationStatus::NoShow, update).await? {
+                continue;
+            }
+            expired += 1;
+
+            if let Some(fee) = fee {
+                // the reservation stays a no-show; an unpaid fee is left to an administrator
+                if let Err(e) = self.charge_reservation_fee(&reservation, parking_lot, fee, "no-show").await {
+                    println!("no-show fee of reservation {} not charged: {}", reservation._id.to_hex(), e);
+                    self.reservation_collection
+                        .update_one(doc! { "_id": reservation._id }, doc! { "$set": { "fee": null } }, None)
+                        .await
+                        .map_err(MongoQueryError)?;
+                }
+            }
+        }
+
+        Ok(expired)
+    }
+
+    async fn charge_reservation_fee(&self, reservation: &Reservation, parking_lot: &ParkingLot, fee: Money, reason: &str) -> Result<()> {
+        self.post_transfer(&Transfer {
+            from: &reservation.user_id,
+            to: &parking_lot.operator_id,
+            amount: fee,
+            kind: TransactionKind::ReservationFee,
+            ticket_id: None,
+            parking_lot_id: Some(reservation.parking_lot_id.to_owned()),
+            description: format!("Reservation {} {} fee", reservation._id.to_hex(), reason),
+        }).await?;
+
+        Ok(())
+    }
+
+    /// Sets the status of a reservation together with `update`, provided
+    /// nobody changed the status since `reservation` was read. Returns whether it did.
+    async fn transition_reservation(&self, reservation: &Reservation, status: ReservationStatus, mut update: Document) -> Result<bool> {
+        if !reservation.status.can_become(status) {
+            return Err(IllegalReservationTransitionError(reservation._id.to_hex(), reservation.status, status));
+        }
+
+        update.insert("status", bson::to_bson(&status)?);
+        let result = self
+            .reservation_collection
+            .update_one(
+                doc! { "_id": reservation._id, "status": bson::to_bson(&reservation.status)? },
+                doc! { "$set": update },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(result.matched_count == 1)
+    }
+
+    fn doc_to_reservation(&self, reservation: &Reservation) -> ReservationResponse {
+        ReservationResponse {
+            id: reservation._id.to_hex(),
+            user_id: reservation.user_id.to_owned(),
+            parking_lot_id: reservation.parking_lot_id.to_owned(),
+            vehicle_license_number: reservation.vehicle_license_number.to_owned(),
+            vehicle_type: reservation.vehicle_type.to_string(),
+            parking_spot_id: reservation.parking_spot_id.to_owned(),
+            level: reservation.level,
+            spot_ordinal_number: reservation.spot_ordinal_number,
+            start: reservation.start,
+            end: reservation.end,
+            status: reservation.status,
+            ticket_code: reservation.ticket_code.to_owned(),
+            fee: reservation.fee,
+            created_at: reservation.created_at,
+        }
+    }
+}
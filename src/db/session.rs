@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use bson::{oid::ObjectId, doc};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+
+use crate::{structs::{
+    error::MyError::{*, self},
+    model::Session,
+}, utils::secret};
+
+use super::common::DB;
+
+type Result<T> = std::result::Result<T, MyError>;
+
+impl DB {
+    /// Opens a new session and returns it together with the plain refresh token,
+    /// which is never stored and cannot be recovered later.
+    pub async fn create_session(&self, user_id: &str, refresh_token_ttl: chrono::Duration) -> Result<(Session, String)> {
+        let refresh_token = secret::generate_token(32);
+        let now = chrono::Utc::now();
+        let session = Session {
+            _id: ObjectId::new(),
+            user_id: user_id.to_owned(),
+            refresh_token_hash: secret::hash_token(&refresh_token),
+            created_at: now.timestamp(),
+            expires_at: (now + refresh_token_ttl).timestamp(),
+            revoked: false,
+        };
+
+        match self.session_collection.insert_one(&session, None).await {
+            Ok(result) => result,
+            Err(e) => {
+                if e.to_string()
+                    .contains("E110000 duplicate key error collection")
+                {
+                    return Err(MongoDuplicateError(e));
+                }
+                return Err(MongoQueryError(e));
+            }
+        };
+
+        Ok((session, refresh_token))
+    }
+
+    /// Exchanges a refresh token for a new one. The old token stops working
+    /// immediately, so a leaked token can be used at most once.
+    pub async fn rotate_session(&self, refresh_token: &str, refresh_token_ttl: chrono::Duration) -> Result<(Session, String)> {
+        let new_refresh_token = secret::generate_token(32);
+        let now = chrono::Utc::now();
+
+        let filter = doc! {
+            "refresh_token_hash": secret::hash_token(refresh_token),
+            "revoked": false,
+            "expires_at": { "$gt": now.timestamp() },
+        };
+        let update = doc! {
+            "$set": {
+                "refresh_token_hash": secret::hash_token(&new_refresh_token),
+                "expires_at": (now + refresh_token_ttl).timestamp(),
+            }
+        };
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let session = self
+            .session_collection
+            .find_one_and_update(filter, update, options)
+            .await
+            .map_err(MongoQueryError)?
+            .ok_or(UnauthorizedError("invalid refresh token".to_string()))?;
+
+        Ok((session, new_refresh_token))
+    }
+
+    pub async fn get_active_session(&self, session_id: &str) -> Result<Session> {
+        // the id comes from a token, so a malformed one fails authentication
+        let oid = ObjectId::from_str(session_id).map_err(|_| UnauthorizedError("invalid session".to_string()))?;
+        let filter = doc! {
+            "_id": oid,
+            "revoked": false,
+            "expires_at": { "$gt": chrono::Utc::now().timestamp() },
+        };
+
+        self.session_collection
+            .find_one(filter, None)
+            .await
+            .map_err(MongoQueryError)?
+            .ok_or(UnauthorizedError("session revoked or expired".to_string()))
+    }
+
+    pub async fn revoke_session(&self, session_id: &str) -> Result<String> {
+        let oid = ObjectId::from_str(session_id).map_err(|_| UnauthorizedError("invalid session".to_string()))?;
+        self.session_collection
+            .update_one(doc! { "_id": oid }, doc! { "$set": { "revoked": true } }, None)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok("Successful operation".to_string())
+    }
+
+    pub async fn revoke_user_sessions(&self, user_id: &str) -> Result<String> {
+        self.session_collection
+            .update_many(
+                doc! { "user_id": user_id, "revoked": false },
+                doc! { "$set": { "revoked": true } },
+                None,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok("Successful operation".to_string())
+    }
+}
@@ -1,19 +1,32 @@
 use bson::{oid::ObjectId, doc};
 use futures::StreamExt;
-use mongodb::Cursor;
-
-use crate::structs::{
-    error::MyError::{*, self}, 
-    model::Tariff,
-    response::TariffResponse,
-    schema::CreateTariffSchema
+use mongodb::{
+    options::{FindOneOptions, FindOptions},
+    ClientSession,
 };
 
+use crate::{
+    pricing::{
+        calculator::{Charge, PriceCalculator, PricedSpot, Stay},
+        tariffs::validate_tariffs,
+    },
+    structs::{
+        error::MyError::{*, self}, 
+        model::{ParkingLot, Tariff, TariffSet},
+        money::Multiplier,
+        query::QueryQuote,
+        response::{TariffResponse, TariffSetResponse, QuoteResponse},
+        schema::{CreateTariffSchema, CreateTariffSetSchema}
+    },
+};
 
-use super::common::DB;
+use super::common::{is_duplicate_key, DB};
 
 type Result<T> = std::result::Result<T, MyError>;
 
+/// Attempts at storing a tariff set while other edits take its version.
+const STORE_ATTEMPTS: usize = 5;
+
 impl DB {
     // pub async fn fetch_tariffs(&self) -> Result<Vec<TariffResponse>> {
     //     let mut cursor = self
@@ -25,35 +38,186 @@ impl DB {
     //     Ok(self.cursor_to_vec(&mut cursor).await?)
     // }
 
-    pub async fn create_tariff(&self, body: &CreateTariffSchema, parking_lot_id: &str) -> Result<String> {
-        let tariff = Tariff {
-            _id: ObjectId::new(),
-            parking_lot_id: parking_lot_id.to_owned(),
-            min_time: body.min_time,
-            max_time: body.max_time,
-            price_per_hour: body.price_per_hour,
-        };
+    /// Stores `bodies` as the next version of the tariffs of a parking lot,
+    /// in force from `effective_from` (now when left out).
+    pub async fn create_tariff_set(&self, parking_lot_id: &str, bodies: &[CreateTariffSchema], effective_from: Option<i64>) -> Result<TariffSetResponse> {
+        let now = chrono::Utc::now().timestamp();
+        let effective_from = effective_from.unwrap_or(now);
+        if effective_from < now {
+            return Err(ValidationError(format!("tariffs cannot take effect in the past ({})", effective_from)));
+        }
 
-        println!("{:?}", tariff);
+        let tariff_set_id = ObjectId::new();
+        let tariffs: Vec<Tariff> = bodies
+            .iter()
+            .map(|body| Tariff {
+                _id: ObjectId::new(),
+                parking_lot_id: parking_lot_id.to_owned(),
+                tariff_set_id: tariff_set_id.to_hex(),
+                min_time: body.min_time,
+                max_time: body.max_time,
+                price_per_hour: body.price_per_hour,
+                schedule: body.schedule.to_owned(),
+                scope: body.scope.to_owned(),
+            })
+            .collect();
+        validate_tariffs(&tariffs)?;
 
-        match self.tariff_collection.insert_one(tariff, None).await {
-            Ok(result) => result,
-            Err(e) => {
-                if e.to_string()
-                    .contains("E110000 duplicate key error collection")
-                {
-                    return Err(MongoDuplicateError(e));
+        // the version is unique per parking lot, so an edit racing another one
+        // is stored again as the version after it
+        let mut attempt = 1;
+        loop {
+            let mut session = self.client.start_session(None).await.map_err(MongoQueryError)?;
+            session.start_transaction(None).await.map_err(MongoQueryError)?;
+
+            match self.store_tariff_set(parking_lot_id, tariff_set_id, &tariffs, effective_from, &mut session).await {
+                Ok(tariff_set) => {
+                    session.commit_transaction().await.map_err(MongoQueryError)?;
+                    return Ok(self.doc_to_tariff_set(&tariff_set, &tariffs));
+                }
+                Err(e) => {
+                    let _ = session.abort_transaction().await;
+                    match e {
+                        MongoQueryError(e) if is_duplicate_key(&e) && attempt < STORE_ATTEMPTS => attempt += 1,
+                        e => return Err(e),
+                    }
                 }
-                return Err(MongoQueryError(e));
             }
+        }
+    }
+
+    /// Stores the tariffs and their set as the version after the latest one,
+    /// in one transaction.
+    async fn store_tariff_set(&self, parking_lot_id: &str, tariff_set_id: ObjectId, tariffs: &[Tariff], effective_from: i64, session: &mut ClientSession) -> Result<TariffSet> {
+        let latest = self
+            .tariff_set_collection
+            .find_one_with_session(
+                doc! { "parking_lot_id": parking_lot_id },
+                FindOneOptions::builder().sort(doc! { "version": -1 }).build(),
+                session,
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        let tariff_set = TariffSet {
+            _id: tariff_set_id,
+            parking_lot_id: parking_lot_id.to_owned(),
+            version: latest.map_or(1, |tariff_set| tariff_set.version + 1),
+            effective_from,
+            created_at: chrono::Utc::now().timestamp(),
         };
 
-        Ok("Successful operation".to_string())
+        self.tariff_collection
+            .insert_many_with_session(tariffs, None, session)
+            .await
+            .map_err(MongoQueryError)?;
+        self.tariff_set_collection
+            .insert_one_with_session(&tariff_set, None, session)
+            .await
+            .map_err(MongoQueryError)?;
+
+        Ok(tariff_set)
+    }
+
+    /// Replaces all tariffs of a parking lot, possibly from a future date.
+    pub async fn replace_tariffs(&self, parking_lot_id: &str, body: &CreateTariffSetSchema) -> Result<TariffSetResponse> {
+        self.get_parking_lot(parking_lot_id).await?;
+
+        self.create_tariff_set(parking_lot_id, &body.tariffs, body.effective_from).await
     }
 
+    pub async fn add_tariff(&self, parking_lot_id: &str, body: &CreateTariffSchema) -> Result<TariffSetResponse> {
+        self.edit_tariffs(parking_lot_id, None, Some(body)).await
+    }
+
+    pub async fn update_tariff(&self, parking_lot_id: &str, tariff_id: &str, body: &CreateTariffSchema) -> Result<TariffSetResponse> {
+        self.edit_tariffs(parking_lot_id, Some(tariff_id), Some(body)).await
+    }
+
+    pub async fn delete_tariff(&self, parking_lot_id: &str, tariff_id: &str) -> Result<TariffSetResponse> {
+        self.edit_tariffs(parking_lot_id, Some(tariff_id), None).await
+    }
+
+    /// Copies the tariffs in force now into a new version taking effect
+    /// immediately, with `tariff_id` replaced by `body` or removed, or with
+    /// `body` added. Stays already started keep the prices they began with.
+    async fn edit_tariffs(&self, parking_lot_id: &str, tariff_id: Option<&str>, body: Option<&CreateTariffSchema>) -> Result<TariffSetResponse> {
+        self.get_parking_lot(parking_lot_id).await?;
+
+        let tariffs = self.fetch_tariffs(parking_lot_id, chrono::Utc::now().timestamp()).await?;
+        let mut bodies: Vec<CreateTariffSchema> = Vec::new();
+        let mut found = false;
+        for tariff in &tariffs {
+            if Some(tariff._id.to_hex().as_str()) == tariff_id {
+                found = true;
+                continue;
+            }
+            bodies.push(CreateTariffSchema {
+                min_time: tariff.min_time,
+                max_time: tariff.max_time,
+                price_per_hour: tariff.price_per_hour,
+                schedule: tariff.schedule.to_owned(),
+                scope: tariff.scope.to_owned(),
+            });
+        }
+        if let Some(tariff_id) = tariff_id {
+            if !found {
+                return Err(NotFoundError(tariff_id.to_owned()));
+            }
+        }
+        bodies.extend(body.cloned());
+
+        self.create_tariff_set(parking_lot_id, &bodies, None).await
+    }
+
+    /// Tariffs in force now.
     pub async fn get_tariffs_by_parking_lot_id_ascending(&self, parking_lot_id: &str) -> Result<Vec<TariffResponse>> {
+        let tariffs = self.fetch_tariffs(parking_lot_id, chrono::Utc::now().timestamp()).await?;
+
+        Ok(tariffs.iter().map(|tariff| self.doc_to_tariff(tariff)).collect())
+    }
+
+    /// Every version of the tariffs of a parking lot, oldest first.
+    pub async fn get_tariff_sets(&self, parking_lot_id: &str) -> Result<Vec<TariffSetResponse>> {
         let filter = doc! { "parking_lot_id": parking_lot_id };
-        let options = mongodb::options::FindOptions::builder().sort(doc! { "min_time": 1 }).build();
+
+        let mut cursor = self
+            .tariff_collection
+            .find(filter.clone(), FindOptions::builder().sort(doc! { "min_time": 1 }).build())
+            .await
+            .map_err(MongoQueryError)?;
+        let mut tariffs: Vec<Tariff> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            tariffs.push(doc.map_err(MongoQueryError)?);
+        }
+
+        let mut cursor = self
+            .tariff_set_collection
+            .find(filter, FindOptions::builder().sort(doc! { "version": 1 }).build())
+            .await
+            .map_err(MongoQueryError)?;
+        let mut json_result: Vec<TariffSetResponse> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let tariff_set = doc.map_err(MongoQueryError)?;
+            let tariff_set_id = tariff_set._id.to_hex();
+            let set_tariffs: Vec<Tariff> = tariffs
+                .iter()
+                .filter(|tariff| tariff.tariff_set_id == tariff_set_id)
+                .cloned()
+                .collect();
+            json_result.push(self.doc_to_tariff_set(&tariff_set, &set_tariffs));
+        }
+
+        Ok(json_result)
+    }
+
+    /// Tariffs of the set in force at `timestamp`.
+    pub async fn fetch_tariffs(&self, parking_lot_id: &str, timestamp: i64) -> Result<Vec<Tariff>> {
+        let tariff_set = match self.get_tariff_set_at(parking_lot_id, timestamp).await? {
+            Some(tariff_set) => tariff_set,
+            None => return Ok(Vec::new()),
+        };
+        let filter = doc! { "tariff_set_id": tariff_set._id.to_hex() };
+        let options = FindOptions::builder().sort(doc! { "min_time": 1 }).build();
 
         let mut cursor = self
             .tariff_collection
@@ -61,24 +225,100 @@ impl DB {
             .await
             .map_err(MongoQueryError)?;
 
-        Ok(self.cursor_to_vec(&mut cursor).await?)
+        let mut tariffs: Vec<Tariff> = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            tariffs.push(doc.map_err(MongoQueryError)?);
+        }
+
+        Ok(tariffs)
+    }
+
+    async fn get_tariff_set_at(&self, parking_lot_id: &str, timestamp: i64) -> Result<Option<TariffSet>> {
+        let in_force = self
+            .tariff_set_collection
+            .find_one(
+                doc! { "parking_lot_id": parking_lot_id, "effective_from": { "$lte": timestamp } },
+                FindOneOptions::builder().sort(doc! { "effective_from": -1, "version": -1 }).build(),
+            )
+            .await
+            .map_err(MongoQueryError)?;
+        if in_force.is_some() {
+            return Ok(in_force);
+        }
+
+        // stays older than the first set are billed by it
+        self.tariff_set_collection
+            .find_one(
+                doc! { "parking_lot_id": parking_lot_id },
+                FindOneOptions::builder().sort(doc! { "version": 1 }).build(),
+            )
+            .await
+            .map_err(MongoQueryError)
+    }
+
+    /// Prices a stay on a spot of the given lot with the tariffs in force when
+    /// it started.
+    pub async fn price_stay(&self, parking_lot: &ParkingLot, spot: &PricedSpot, stay: &Stay) -> Result<Charge> {
+        let tariffs = self.fetch_tariffs(&parking_lot._id.to_hex(), stay.start).await?;
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &parking_lot.calendar, &parking_lot.pricing_policy, spot);
+
+        Ok(calculator.calculate(stay)?)
     }
 
-    fn doc_to_tariff(&self, tariff: &Tariff) -> Result<TariffResponse> {
-        Ok(TariffResponse {
+    /// Prices a stay without touching any ticket, parking space or wallet.
+    pub async fn quote_stay(&self, parking_lot: &ParkingLot, spot: &PricedSpot, stay: &Stay) -> Result<QuoteResponse> {
+        let charge = self.price_stay(parking_lot, spot, stay).await?;
+
+        Ok(QuoteResponse {
+            parking_lot_id: parking_lot._id.to_hex(),
+            from: stay.start,
+            to: stay.end,
+            billable_hours: stay.billable_hours()?,
+            total: charge.total()?,
+            items: charge.items,
+        })
+    }
+
+    pub async fn quote_parking_lot(&self, parking_lot_id: &str, query: &QueryQuote) -> Result<QuoteResponse> {
+        let parking_lot = self.get_parking_lot(parking_lot_id).await?;
+        let stay = Stay {
+            start: query.from,
+            end: query.to.unwrap_or_else(|| chrono::Utc::now().timestamp()),
+        };
+        if stay.end < stay.start {
+            return Err(ValidationError(format!("stay ends before it starts ({} < {})", stay.end, stay.start)));
+        }
+        stay.validate_quote().map_err(|e| ValidationError(e.to_string()))?;
+        let spot = PricedSpot {
+            vehicle_type: query.vehicle_type.to_owned(),
+            category: query.category.to_owned(),
+            price_modifier: Multiplier::ONE,
+            pass: None,
+        };
+
+        self.quote_stay(&parking_lot, &spot, &stay).await
+    }
+
+    fn doc_to_tariff(&self, tariff: &Tariff) -> TariffResponse {
+        TariffResponse {
+            id: tariff._id.to_hex(),
             parking_lot_id: tariff.parking_lot_id.to_owned(),
             min_time: tariff.min_time,
             max_time: tariff.max_time,
             price_per_hour: tariff.price_per_hour,
-        })
+            schedule: tariff.schedule.to_owned(),
+            scope: tariff.scope.to_owned(),
+        }
     }
 
-    async fn cursor_to_vec(&self, cursor: &mut Cursor<Tariff>) -> Result<Vec<TariffResponse>> {
-        let mut json_result: Vec<TariffResponse> = Vec::new();
-        while let Some(doc) = cursor.next().await {
-            json_result.push(self.doc_to_tariff(&doc.unwrap())?);
+    fn doc_to_tariff_set(&self, tariff_set: &TariffSet, tariffs: &[Tariff]) -> TariffSetResponse {
+        TariffSetResponse {
+            id: tariff_set._id.to_hex(),
+            parking_lot_id: tariff_set.parking_lot_id.to_owned(),
+            version: tariff_set.version,
+            effective_from: tariff_set.effective_from,
+            created_at: tariff_set.created_at,
+            tariffs: tariffs.iter().map(|tariff| self.doc_to_tariff(tariff)).collect(),
         }
-
-        Ok(json_result)
     }
-}
\ No newline at end of file
+}
@@ -1,17 +1,23 @@
 use std::str::FromStr;
 
-use bson::{oid::ObjectId, doc};
+use bson::{oid::ObjectId, doc, Document};
 use chrono::{Utc, TimeZone};
 use futures::StreamExt;
 
-use crate::structs::{
-    error::MyError::{*, self}, 
-    model::{Ticket, ParkingSpace},
-    response::{TicketResponse, TicketUserResponse}, 
-    schema::{CreateTicketSchema, CreateTicketUserSchema}
+use crate::{
+    pricing::calculator::Stay,
+    structs::{
+        error::MyError::{*, self}, 
+        model::{Ticket, User, Role, VehicleType, ParkingSpace},
+        money::{Currency, Money},
+        response::{TicketResponse, TicketUserResponse, QuoteResponse}, 
+        schema::{CreateTicketSchema, CreateTicketUserSchema},
+        ticket_status::TicketStatus,
+        access_code,
+    },
 };
 
-use super::common::DB;
+use super::common::{is_duplicate_key, DB};
 
 type Result<T> = std::result::Result<T, MyError>;
 
@@ -54,7 +60,7 @@ impl DB {
         let end_of_day = Utc.timestamp(end_time_stamp.into(), 0).date().and_hms(23, 59, 59);
 
         let end_timestamp_query = if active {
-            doc! { "$eq": 0 }
+            doc! { "$exists": true }
         } else {
             if end_time_stamp == 0 {
                 doc! { "$ne": 0 }
@@ -66,6 +72,12 @@ impl DB {
             }
         };
 
+        let status_query = if active {
+            doc! { "$in": bson::to_bson(&TicketStatus::ACTIVE)? }
+        } else {
+            doc! { "$nin": bson::to_bson(&TicketStatus::ACTIVE)? }
+        };
+
         let level_query = if level == 0 {
             doc! { "$ne": 0 }
         } else {
@@ -88,6 +100,7 @@ impl DB {
             "level": level_query,
             "spot_ordinal_number": spot_ordinal_number_query,
             "parking_lot_id": parking_lot_id_query,
+            "status": status_query,
         };
         let mut cursor = self
             .ticket_collection
@@ -104,44 +117,7 @@ impl DB {
     }
 
     pub async fn create_ticket(&self, body: &CreateTicketSchema) -> Result<String> {
-        let parking_lot = self
-            .get_parking_lot_by_id(&body.parking_lot_id)
-            .await?;
-
-        let parking_space = self
-            .get_new_parking_space_by_license_number(&body.vehicle_license_number, &parking_lot.id)
-            .await?;
-
-        println!("parking_space: {:?}", parking_space);
-
-        let ticket_id = ObjectId::new();
-        let ticket = Ticket {
-            _id: ticket_id,
-            user_id: body.user_id.to_owned(),
-            vehicle_license_number: body.vehicle_license_number.to_owned(),
-            parking_spot_id: parking_space._id.to_hex(),
-            spot_ordinal_number: parking_space.location.no_space,
-            issue_timestamp: chrono::Utc::now().timestamp(),
-            end_timestamp: 0,
-            amount_paid: 0.0,
-            level: parking_space.location.no_level,
-            parking_lot_id: body.parking_lot_id.to_owned(),
-            code: ticket_id.to_hex().chars().take(8).collect(),
-        };
-
-        match self.ticket_collection.insert_one(ticket, None).await {
-            Ok(result) => result,
-            Err(e) => {
-                if e.to_string()
-                    .contains("E110000 duplicate key error collection")
-                {
-                    return Err(MongoDuplicateError(e));
-                }
-                return Err(MongoQueryError(e));
-            }
-        };
-
-        Ok(ticket_id.to_hex().chars().take(8).collect())
+        self.issue_ticket(&body.user_id, &body.vehicle_license_number, body.vehicle_type.as_ref(), &body.parking_lot_id, None).await
     }
 
     pub async fn get_ticket_by_code(&self, code: &str) -> Result<Ticket> {
@@ -158,74 +134,170 @@ impl DB {
         }
     }
 
+    /// Charges the user for the stay and lets the vehicle out. Validating a
+    /// ticket again returns it unchanged instead of charging twice.
     pub async fn put_ticket(&self, code: &str) -> Result<TicketResponse> {
-        let ticket = self
-            .get_ticket_by_code(code)
-            .await?;
+        let ticket_id = self.get_ticket_by_code(code).await?._id.to_hex();
+        // every pass moves the ticket one step on, or sees another request did
+        loop {
+            let ticket = self.get_ticket_by_id(&ticket_id).await?;
+            match ticket.status {
+                TicketStatus::Parked | TicketStatus::Lost => {
+                    self.pay_ticket(&ticket).await?;
+                }
+                TicketStatus::Paid => {
+                    self.exit_ticket(&ticket).await?;
+                }
+                TicketStatus::Exited => return self.doc_to_ticket(&ticket),
+                TicketStatus::PendingPayment => return Err(TicketPaymentPendingError(code.to_owned())),
+                status => return Err(IllegalTicketTransitionError(code.to_owned(), status, TicketStatus::PendingPayment)),
+            }
+        }
+    }
+
+    /// Validates a ticket for its owner, or for an admin.
+    pub async fn put_user_ticket(&self, code: &str, user: &User) -> Result<TicketResponse> {
+        let ticket = self.get_ticket_by_code(code).await?;
+        if ticket.user_id != user._id.to_hex() && user.role != Role::Admin {
+            return Err(ForbiddenError(user._id.to_hex()));
+        }
+
+        self.put_ticket(code).await
+    }
+
+    /// Moves a ticket to `status` outside of payment: checks a vehicle in,
+    /// cancels a ticket, reports it lost or lets a paid vehicle out.
+    pub async fn set_ticket_status(&self, code: &str, status: TicketStatus) -> Result<TicketResponse> {
+        if matches!(status, TicketStatus::PendingPayment | TicketStatus::Paid) {
+            return Err(ValidationError("tickets are paid by validating them".to_string()));
+        }
 
+        let ticket_id = self.get_ticket_by_code(code).await?._id.to_hex();
+        loop {
+            let ticket = self.get_ticket_by_id(&ticket_id).await?;
+            if ticket.status == status {
+                return self.doc_to_ticket(&ticket);
+            }
+
+            let changed = match status {
+                TicketStatus::Exited => self.exit_ticket(&ticket).await?,
+                TicketStatus::Cancelled => {
+                    let update = doc! { "end_timestamp": chrono::Utc::now().timestamp() };
+                    let changed = self.transition_ticket(&ticket, status, update).await?;
+                    if changed {
+                        self.set_parking_space_occupied(&ticket.parking_spot_id, false).await?;
+                    }
+                    changed
+                }
+                _ => self.transition_ticket(&ticket, status, doc! {}).await?,
+            };
+            if changed {
+                return self.doc_to_ticket(&self.get_ticket_by_id(&ticket_id).await?);
+            }
+        }
+    }
+
+    /// Prices the stay and posts the charge. A failed charge returns the
+    /// ticket to its previous status so the stay goes on until it is paid.
+    async fn pay_ticket(&self, ticket: &Ticket) -> Result<bool> {
+        let parking_lot = self.get_parking_lot(&ticket.parking_lot_id).await?;
         let parking_space = self
             .get_parking_space_by_parking_spot_id(&ticket.parking_lot_id, &ticket.parking_spot_id)
             .await?;
+        let end_timestamp = chrono::Utc::now().timestamp();
+        let stay = Stay { start: ticket.issue_timestamp, end: end_timestamp };
+        let spot = self.get_ticket_priced_spot(ticket, &parking_space, &stay).await?;
+        let amount_paid = self
+            .price_stay(&parking_lot, &spot, &stay)
+            .await?
+            .total()?;
 
-        let ticket = self
-            .update_ticket(&ticket, &parking_space)
-            .await?;
-
-        Ok(ticket)
-    }
+        let update = doc! {
+            "end_timestamp": end_timestamp,
+            "amount_paid": bson::to_bson(&amount_paid)?,
+        };
+        if !self.transition_ticket(ticket, TicketStatus::PendingPayment, update).await? {
+            return Ok(false);
+        }
 
-    pub async fn update_ticket(&self, ticket: &Ticket, parking_space: &ParkingSpace) -> Result<TicketResponse> {
-        let filter = doc! { "_id": ticket._id.clone() };
+        let pending = Ticket {
+            status: TicketStatus::PendingPayment,
+            end_timestamp,
+            amount_paid,
+            ..ticket.clone()
+        };
+        // a stay fully covered by a pass costs nothing and posts no transfer
+        let charged = match amount_paid.is_positive() {
+            true => self.transfer_balance(&pending, amount_paid).await.map(|_| ()),
+            false => Ok(()),
+        };
+        if let Err(e) = charged {
+            let update = doc! {
+                "end_timestamp": 0,
+                "amount_paid": bson::to_bson(&Money::zero(amount_paid.currency))?,
+            };
+            self.transition_ticket(&pending, ticket.status, update).await?;
+            return Err(e);
+        }
 
-        let tariffs = self
-            .get_tariffs_by_parking_lot_id_ascending(&ticket.parking_lot_id)
-            .await?;
+        self.transition_ticket(&pending, TicketStatus::Paid, doc! {}).await
+    }
 
-        let mut amount_paid = 0.0;
-        let end_timestamp = chrono::Utc::now().timestamp();
-        let total_time = (end_timestamp - ticket.issue_timestamp) / 36000 + 1;
-        while let Some(tariff) = tariffs.iter().next() {
-            if total_time >= tariff.min_time && total_time <= tariff.max_time {
-                amount_paid = parking_space.price_modifier * tariff.price_per_hour * total_time as f64;
-                break;
-            }
+    async fn exit_ticket(&self, ticket: &Ticket) -> Result<bool> {
+        let changed = self.transition_ticket(ticket, TicketStatus::Exited, doc! {}).await?;
+        if changed {
+            self.set_parking_space_occupied(&ticket.parking_spot_id, false).await?;
         }
 
-        if amount_paid == 0.0 {
-            amount_paid = parking_space.price_modifier * tariffs.last().unwrap().price_per_hour * total_time as f64;
+        Ok(changed)
+    }
+
+    /// Sets the status of a ticket together with `update`, provided nobody
+    /// changed the status since `ticket` was read. Returns whether it did.
+    async fn transition_ticket(&self, ticket: &Ticket, status: TicketStatus, mut update: Document) -> Result<bool> {
+        if !ticket.status.can_become(status) {
+            return Err(IllegalTicketTransitionError(ticket.code.to_owned(), ticket.status, status));
         }
-        
-        let update = doc! { 
-            "$set": { 
-                "end_timestamp": end_timestamp,
-                "amount_paid": amount_paid,
-        }};
 
-        self
+        update.insert("status", bson::to_bson(&status)?);
+        let result = self
             .ticket_collection
-            .update_one(filter, update, None)
+            .update_one(
+                doc! { "_id": ticket._id, "status": bson::to_bson(&ticket.status)? },
+                doc! { "$set": update },
+                None,
+            )
             .await
             .map_err(MongoQueryError)?;
 
+        Ok(result.matched_count == 1)
+    }
 
-        let ticket = self
-            .get_ticket_by_id(&ticket._id.to_hex())
-            .await;
-
-        println!("ticket: {:?}", ticket);
-
-        self.toggle_occupied_parking_space(&parking_space._id.to_hex()).await?;
+    /// What the ticket would cost if it was closed now; a closed ticket is
+    /// quoted for the stay it was charged for.
+    pub async fn quote_ticket(&self, code: &str, user: &User) -> Result<QuoteResponse> {
+        let ticket = self.get_ticket_by_code(code).await?;
+        if ticket.user_id != user._id.to_hex() && user.role != Role::Admin {
+            return Err(ForbiddenError(user._id.to_hex()));
+        }
 
-        self.transfer_balance(&ticket.as_ref().unwrap().user_id, amount_paid).await?;
+        let parking_lot = self.get_parking_lot(&ticket.parking_lot_id).await?;
+        let parking_space = self
+            .get_parking_space_by_parking_spot_id(&ticket.parking_lot_id, &ticket.parking_spot_id)
+            .await?;
+        let end_timestamp = match ticket.end_timestamp {
+            0 => chrono::Utc::now().timestamp(),
+            end_timestamp => end_timestamp,
+        };
+        let stay = Stay { start: ticket.issue_timestamp, end: end_timestamp };
 
-        match ticket {
-            Ok(ticket) => Ok(self.doc_to_ticket(&ticket)?),
-            Err(_) => Err(MongoNotFound(format!("ticket with code: {}", ticket.unwrap().code))),
-        }
+        let spot = self.get_ticket_priced_spot(&ticket, &parking_space, &stay).await?;
+        self.quote_stay(&parking_lot, &spot, &stay).await
     }
 
     pub async fn get_ticket_by_id(&self, id: &str) -> Result<Ticket> {
-        let filter = doc! { "_id": ObjectId::from_str(id).unwrap() };
+        let oid = ObjectId::from_str(id).map_err(|_| InvalidIDError(id.to_owned()))?;
+        let filter = doc! { "_id": oid };
         let ticket = self
             .ticket_collection
             .find_one(filter, None)
@@ -251,6 +323,7 @@ impl DB {
             spot_ordinal_number: ticket.spot_ordinal_number,
             parking_lot_id: ticket.parking_lot_id.to_owned(),
             code: ticket.code.to_owned(),
+            status: ticket.status,
         };
 
         Ok(ticket_response)
@@ -259,7 +332,7 @@ impl DB {
     pub async fn get_user_active_tickets(&self, user_id: &str) -> Result<Vec<TicketUserResponse>> {
         let filter = doc! { 
             "user_id": user_id,
-            "end_timestamp": 0,
+            "status": { "$in": bson::to_bson(&TicketStatus::ACTIVE)? },
         };
         let mut cursor = self
             .ticket_collection
@@ -288,49 +361,85 @@ impl DB {
             level: ticket.level,
             parking_lot_id: ticket.parking_lot_id.to_owned(),
             code: ticket.code.to_owned(),
+            status: ticket.status,
         };
 
         Ok(ticket_response)
     }
 
     pub async fn create_user_ticket(&self, user_id: &str, body: &CreateTicketUserSchema) -> Result<String> {
+        self.issue_ticket(user_id, &body.vehicle_license_number, body.vehicle_type.as_ref(), &body.parking_lot_id, body.preferred_level).await
+    }
+
+    /// Claims a parking space and stores a ticket for it.
+    pub async fn issue_ticket(
+        &self,
+        user_id: &str,
+        vehicle_license_number: &str,
+        vehicle_type: Option<&VehicleType>,
+        parking_lot_id: &str,
+        preferred_level: Option<u32>,
+    ) -> Result<String> {
         let parking_lot = self
-            .get_parking_lot_by_id(&body.parking_lot_id)
+            .get_open_parking_lot(parking_lot_id)
             .await?;
 
-        let parking_space = self
-            .get_new_parking_space_by_license_number(&body.vehicle_license_number, &parking_lot.id)
+        let (vehicle_type, dimensions) = self
+            .get_vehicle_type(vehicle_license_number, vehicle_type)
             .await?;
 
-        println!("parking_space: {:?}", parking_space);
-
-        let ticket_id = ObjectId::new();
-        let ticket = Ticket {
-            _id: ticket_id,
-            user_id: user_id.to_owned(),
-            vehicle_license_number: body.vehicle_license_number.to_owned(),
-            parking_spot_id: parking_space._id.to_hex(),
-            issue_timestamp: chrono::Utc::now().timestamp(),
-            end_timestamp: 0,
-            amount_paid: 0.0,
-            level: parking_space.location.no_level,
-            spot_ordinal_number: parking_space.location.no_space,
-            parking_lot_id: body.parking_lot_id.to_owned(),
-            code: ticket_id.to_hex().chars().take(8).collect(),
+        // a pass with a reserved spot parks the vehicle there while the space is free
+        let pass_spot = self.get_pass_parking_space(parking_lot_id, vehicle_license_number).await?;
+        let parking_space = match pass_spot {
+            Some(parking_spot_id) if self.set_parking_space_occupied(&parking_spot_id, true).await? => {
+                self.get_parking_space_by_parking_spot_id(parking_lot_id, &parking_spot_id).await?
+            }
+            _ => {
+                self.claim_parking_space(&parking_lot, &vehicle_type.compatible_spaces(dimensions.as_ref()), preferred_level)
+                    .await?
+            }
         };
 
-        match self.ticket_collection.insert_one(ticket, None).await {
-            Ok(result) => result,
-            Err(e) => {
-                if e.to_string()
-                    .contains("E110000 duplicate key error collection")
-                {
-                    return Err(MongoDuplicateError(e));
+        self.store_ticket(user_id, vehicle_license_number, parking_lot_id, &parking_space).await
+    }
+
+    /// Stores a ticket for a parking space already claimed, releasing the
+    /// space when the ticket cannot be stored.
+    pub async fn store_ticket(
+        &self,
+        user_id: &str,
+        vehicle_license_number: &str,
+        parking_lot_id: &str,
+        parking_space: &ParkingSpace,
+    ) -> Result<String> {
+        let mut attempt = 1;
+        loop {
+            let ticket = Ticket {
+                _id: ObjectId::new(),
+                user_id: user_id.to_owned(),
+                vehicle_license_number: vehicle_license_number.to_owned(),
+                parking_spot_id: parking_space._id.to_hex(),
+                issue_timestamp: chrono::Utc::now().timestamp(),
+                end_timestamp: 0,
+                amount_paid: Money::zero(Currency::default()),
+                level: parking_space.location.no_level,
+                spot_ordinal_number: parking_space.location.no_space,
+                parking_lot_id: parking_lot_id.to_owned(),
+                code: access_code::generate_code(),
+                status: TicketStatus::Parked,
+            };
+            match self.ticket_collection.insert_one(&ticket, None).await {
+                Ok(_) => return Ok(ticket.code),
+                // codes are unique, so draw again in the unlikely case it is taken
+                Err(e) if is_duplicate_key(&e) && attempt < access_code::GENERATE_ATTEMPTS => attempt += 1,
+                Err(e) => {
+                    self.set_parking_space_occupied(&parking_space._id.to_hex(), false).await?;
+                    if is_duplicate_key(&e) {
+                        return Err(MongoDuplicateError(e));
+                    }
+                    return Err(MongoQueryError(e));
                 }
-                return Err(MongoQueryError(e));
             }
-        };
-
-        Ok(ticket_id.to_hex().chars().take(8).collect())
+        }
     }
-}
\ No newline at end of file
+}
@@ -4,14 +4,15 @@ use bcrypt::hash;
 use bson::{oid::ObjectId, doc};
 use futures::StreamExt;
 
-use crate::{structs::{
+use crate::structs::{
     error::MyError::{*, self}, 
-    model::{User, Role},
+    model::{User, Role, Ticket, TransactionKind},
+    money::{Currency, Money},
     response::{UserResponse, UserBalance}, 
     schema::{CreateUserSchema, RegisterUserSchema, LoginUserSchema}
-}, utils::jwt};
+};
 
-use super::common::DB;
+use super::{common::DB, ledger::{Transfer, EXTERNAL_ACCOUNT}};
 
 type Result<T> = std::result::Result<T, MyError>;
 
@@ -39,7 +40,7 @@ impl DB {
             account_balance: body.account_balance,
             email: body.name.to_owned() + "." + &body.surname.to_owned() + "@gmail.com",
             password: "123".to_string(),
-            role: Role::User,
+            role: body.role.to_owned(),
             blocked: body.blocked,
         };
 
@@ -59,7 +60,8 @@ impl DB {
     }
 
     pub async fn get_user_by_id(&self, user_id: &str) -> Result<User> {
-        let filter = doc! { "_id": ObjectId::from_str(user_id).unwrap() };
+        let oid = ObjectId::from_str(user_id).map_err(|_| InvalidIDError(user_id.to_owned()))?;
+        let filter = doc! { "_id": oid };
         let user = self
             .user_collection
             .find_one(filter, None)
@@ -70,28 +72,20 @@ impl DB {
         Ok(user)
     }
 
-    pub async fn transfer_balance(&self, user_id: &str, amount: f64) -> Result<String> {
-        let user = self.get_user_by_id(user_id).await?;
-        let admin = self.get_user_by_id("5f9b3b9b9d9b9d9b9d9b9d9b").await?;
-        let new_balance_admin = admin.account_balance + amount;
-        let new_balance = match user.account_balance - amount {
-            x if x < 0.0 => return Err(NotEnoughBalanceError("Not enough balance".to_string())),
-            x => x,
-        };
-
-        let filter = doc! { "_id": ObjectId::from_str(user_id).unwrap() };
-        let update = doc! { "$set": { "account_balance": new_balance } };
-        self.user_collection
-            .update_one(filter, update, None)
-            .await
-            .map_err(MongoQueryError)?;
-
-        let filter = doc! { "_id": ObjectId::from_str("5f9b3b9b9d9b9d9b9d9b9d9b").unwrap() };
-        let update = doc! { "$set": { "account_balance": new_balance_admin } };
-        self.user_collection
-            .update_one(filter, update, None)
-            .await
-            .map_err(MongoQueryError)?;
+    /// Charges a ticket to the user's wallet and credits the operator of the
+    /// parking lot the ticket was issued for.
+    pub async fn transfer_balance(&self, ticket: &Ticket, amount: Money) -> Result<String> {
+        let parking_lot = self.get_parking_lot(&ticket.parking_lot_id).await?;
+
+        self.post_transfer(&Transfer {
+            from: &ticket.user_id,
+            to: &parking_lot.operator_id,
+            amount,
+            kind: TransactionKind::TicketCharge,
+            ticket_id: Some(ticket._id.to_hex()),
+            parking_lot_id: Some(ticket.parking_lot_id.to_owned()),
+            description: "Parking ticket".to_string(),
+        }).await?;
 
         Ok("Successful operation".to_string())
     }
@@ -104,25 +98,26 @@ impl DB {
             email: user.email.to_owned(),
             account_balance: user.account_balance.to_owned(),
             blocked: user.blocked.to_owned(),
+            role: user.role.to_owned(),
         };
 
         Ok(user_response)
     }
 
-    pub async fn register_user(&self, body: &RegisterUserSchema) -> Result<String> {
+    pub async fn register_user(&self, body: &RegisterUserSchema) -> Result<User> {
         let new_user_id = ObjectId::new();
         let user = User {
             _id: new_user_id,
             name: body.name.to_owned(),
             surname: body.surname.to_owned(),
-            account_balance: 0.0,
+            account_balance: Money::zero(Currency::default()),
             email: body.email.to_owned(),
             password: hash(&body.password, 10).unwrap(),
             role: Role::User,
             blocked: false,
         };
 
-        match self.user_collection.insert_one(user, None).await {
+        match self.user_collection.insert_one(&user, None).await {
             Ok(result) => result,
             Err(e) => {
                 println!("{:?}", e);
@@ -135,19 +130,10 @@ impl DB {
             }
         };
 
-        // create jwt token
-        let jwt_user = jwt::User {
-            name: body.name.to_owned(),
-            surname: body.surname.to_owned(),
-            email: body.email.to_owned(),
-            role: Role::User,
-        };
-        let token = jwt::create_token(&new_user_id.to_hex(), jwt_user);
-
-        Ok(token)
+        Ok(user)
     }
 
-    pub async fn login_user(&self, body: &LoginUserSchema) -> Result<String> {
+    pub async fn login_user(&self, body: &LoginUserSchema) -> Result<User> {
         let filter = doc! { "email": body.email.to_owned() };
         let user = self
             .user_collection
@@ -160,16 +146,11 @@ impl DB {
             return Err(InvalidCredentialsError("Invalid credentials".to_string()));
         }
 
-        // create jwt token
-        let jwt_user = jwt::User {
-            name: user.name.to_owned(),
-            surname: user.surname.to_owned(),
-            email: user.email.to_owned(),
-            role: user.role.to_owned(),
-        };
-        let token = jwt::create_token(&user._id.to_hex(), jwt_user);
+        if user.blocked {
+            return Err(UserBlockedError(user._id.to_hex()));
+        }
 
-        Ok(token)
+        Ok(user)
     }
 
     pub async fn get_user_balance(&self, user_id: &str) -> Result<UserBalance> {
@@ -182,16 +163,16 @@ impl DB {
         Ok(user_balance)
     }
 
-    pub async fn deposit_balance(&self, user_id: &str, amount: f64) -> Result<String> {
-        let user = self.get_user_by_id(user_id).await?;
-        let new_balance = user.account_balance + amount;
-
-        let filter = doc! { "_id": ObjectId::from_str(user_id).unwrap() };
-        let update = doc! { "$set": { "account_balance": new_balance } };
-        self.user_collection
-            .update_one(filter, update, None)
-            .await
-            .map_err(MongoQueryError)?;
+    pub async fn deposit_balance(&self, user_id: &str, amount: Money) -> Result<String> {
+        self.post_transfer(&Transfer {
+            from: EXTERNAL_ACCOUNT,
+            to: user_id,
+            amount,
+            kind: TransactionKind::Deposit,
+            ticket_id: None,
+            parking_lot_id: None,
+            description: "Wallet deposit".to_string(),
+        }).await?;
 
         Ok("Successful operation".to_string())
     }
@@ -207,6 +188,10 @@ impl DB {
             .await
             .map_err(MongoQueryError)?;
 
+        if new_blocked {
+            self.revoke_user_sessions(user_id).await?;
+        }
+
         Ok("Successful operation".to_string())
     }
 }
\ No newline at end of file
@@ -3,7 +3,7 @@ use futures::StreamExt;
 
 use crate::structs::{
     error::MyError::{*, self}, 
-    model::{VehicleType, Vehicle},
+    model::{Dimensions, VehicleType, Vehicle},
     response::VehicleResponse, schema::{CreateVehicleSchema, CreateVehicleUserSchema},  
 };
 
@@ -47,15 +47,15 @@ impl DB {
         let new_vehicle_id = ObjectId::new();
         let vehicle = Vehicle {
             _id: new_vehicle_id,
-            vehicle_type: match body.vehicle_type.as_str() {
-                "Car" => VehicleType::Car,
-                "Truck" => VehicleType::Truck,
-                _ => return Err(InvalidVehicleTypeError(body.vehicle_type.to_owned())),
-            },
+            vehicle_type: body
+                .vehicle_type
+                .parse()
+                .map_err(|_| InvalidVehicleTypeError(body.vehicle_type.to_owned()))?,
             user_id: body.user_id.to_owned(),
             brand: body.brand.to_owned(),
             model: body.model.to_owned(),
             license_plate_number: body.license_plate_number.to_owned(),
+            dimensions: body.dimensions.to_owned(),
         };
 
         match self.vehicle_collection.insert_one(vehicle, None).await {
@@ -77,15 +77,15 @@ impl DB {
         let new_vehicle_id = ObjectId::new();
         let vehicle = Vehicle {
             _id: new_vehicle_id,
-            vehicle_type: match body.vehicle_type.as_str() {
-                "Car" => VehicleType::Car,
-                "Truck" => VehicleType::Truck,
-                _ => return Err(InvalidVehicleTypeError(body.vehicle_type.to_owned())),
-            },
+            vehicle_type: body
+                .vehicle_type
+                .parse()
+                .map_err(|_| InvalidVehicleTypeError(body.vehicle_type.to_owned()))?,
             user_id: user_id.to_owned(),
             brand: body.brand.to_owned(),
             model: body.model.to_owned(),
             license_plate_number: body.license_plate_number.to_owned(),
+            dimensions: body.dimensions.to_owned(),
         };
 
         match self.vehicle_collection.insert_one(vehicle, None).await {
@@ -106,16 +106,38 @@ impl DB {
     fn doc_to_vehicle(&self, vehicle: &Vehicle) -> Result<VehicleResponse> {
         Ok(VehicleResponse {
             user_id: vehicle.user_id.to_owned(),
-            vehicle_type: match vehicle.vehicle_type.to_owned() {
-                VehicleType::Car => "Car".to_owned(),
-                VehicleType::Truck => "Truck".to_owned(),
-            },
+            vehicle_type: vehicle.vehicle_type.to_string(),
             brand: vehicle.brand.to_owned(),
             model: vehicle.model.to_owned(),
             license_plate_number: vehicle.license_plate_number.to_owned(),
+            dimensions: vehicle.dimensions.to_owned(),
         })
     }
 
+    /// Type and dimensions of the vehicle to park: the registered ones, or
+    /// `requested` for a vehicle that is not registered. A different
+    /// `requested` type is an error.
+    pub async fn get_vehicle_type(
+        &self,
+        license_plate_number: &str,
+        requested: Option<&VehicleType>,
+    ) -> Result<(VehicleType, Option<Dimensions>)> {
+        if license_plate_number.trim().is_empty() {
+            return Err(ValidationError("vehicle license number is empty".to_string()));
+        }
+
+        match (self.get_vehicle_by_license_plate_number(license_plate_number).await, requested) {
+            (Ok(vehicle), Some(requested)) if vehicle.vehicle_type != *requested => Err(VehicleTypeMismatchError(
+                license_plate_number.to_owned(),
+                vehicle.vehicle_type,
+                requested.to_owned(),
+            )),
+            (Ok(vehicle), _) => Ok((vehicle.vehicle_type, vehicle.dimensions)),
+            (Err(VehicleNotFoundError(_)), Some(requested)) => Ok((requested.to_owned(), None)),
+            (Err(e), _) => Err(e),
+        }
+    }
+
     pub async fn get_vehicle_by_license_plate_number(&self, license_plate_number: &str) -> Result<Vehicle> {
         let filter = doc! { "license_plate_number": license_plate_number };
         let vehicle = self.vehicle_collection.find_one(filter, None).await.map_err(MongoQueryError)?;
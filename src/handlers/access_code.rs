@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
+
+use crate::AppState;
+use crate::structs::schema::CreateAccessCodeSchema;
+
+pub async fn get_access_codes(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .get_access_codes(&parking_lot_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn create_access_code(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<CreateAccessCodeSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .create_access_code(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn rotate_access_code(
+    Path((parking_lot_id, code_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .rotate_access_code(&parking_lot_id, &code_id)
+        .await
+    {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn revoke_access_code(
+    Path((parking_lot_id, code_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .revoke_access_code(&parking_lot_id, &code_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
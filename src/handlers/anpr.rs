@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Query};
+use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
+
+use crate::AppState;
+use crate::utils::auth::{AuthGate, AuthUser};
+use crate::structs::{query::QueryAnprReads, schema::{AnprReadSchema, ReviewAnprReadSchema}};
+
+pub async fn ingest_anpr_read(
+    AuthGate { gate }: AuthGate,
+    Path(gate_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<AnprReadSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.ingest_anpr_read(&gate, &gate_id, &body).await {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_anpr_reads(
+    Path(parking_lot_id): Path<String>,
+    Query(QueryAnprReads { status, limit, skip }): Query<QueryAnprReads>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.get_anpr_reads(&parking_lot_id, status, limit, skip).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn review_anpr_read(
+    AuthUser { user_id, .. }: AuthUser,
+    Path((parking_lot_id, id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<ReviewAnprReadSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.review_anpr_read(&parking_lot_id, &id, &user_id, &body).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use axum::{extract::State, response::IntoResponse, http::StatusCode, Json};
+
+use crate::AppState;
+use crate::utils::auth::AuthUser;
+use crate::structs::{
+    error::MyError,
+    model::{Session, User},
+    response::AuthTokensResponse,
+    schema::RefreshTokenSchema,
+};
+
+/// Signs a short-lived access token bound to `session` and pairs it with the refresh token.
+pub fn issue_tokens(
+    app_state: &AppState,
+    user: &User,
+    session: &Session,
+    refresh_token: String,
+) -> Result<AuthTokensResponse, jsonwebtoken::errors::Error>
+{
+    let access_token = app_state
+        .jwt
+        .create_token(&user._id.to_hex(), &session._id.to_hex(), user.into())?;
+
+    Ok(AuthTokensResponse {
+        access_token,
+        refresh_token,
+        expires_in: app_state.jwt.token_ttl.num_seconds(),
+    })
+}
+
+pub async fn refresh_token(
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<RefreshTokenSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)>
+{
+    let (session, refresh_token) = match app_state
+        .db
+        .rotate_session(&body.refresh_token, app_state.jwt.refresh_token_ttl)
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => return Err(e.into()),
+    };
+
+    let user = match app_state.db.get_user_by_id(&session.user_id).await {
+        Ok(user) if !user.blocked => user,
+        Ok(user) => return Err(MyError::UserBlockedError(user._id.to_hex()).into()),
+        Err(e) => return Err(e.into()),
+    };
+
+    match issue_tokens(&app_state, &user, &session, refresh_token) {
+        Ok(tokens) => Ok((StatusCode::CREATED, Json(tokens))),
+        Err(e) => Err(MyError::from(e).into()),
+    }
+}
+
+pub async fn logout(
+    auth_user: AuthUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)>
+{
+    match app_state
+        .db
+        .revoke_session(&auth_user.session_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn logout_all(
+    AuthUser { user_id, .. }: AuthUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)>
+{
+    match app_state
+        .db
+        .revoke_user_sessions(&user_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_jwks(
+    State(app_state): State<Arc<AppState>>,
+) -> impl IntoResponse
+{
+    Json(app_state.jwt.jwks())
+}
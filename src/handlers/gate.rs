@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Query};
+use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
+
+use crate::AppState;
+use crate::utils::auth::AuthGate;
+use crate::structs::{gate::GateDirection, query::QueryGateEvents, schema::{CreateGateSchema, GateEventSchema}};
+
+pub async fn get_gates(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.get_gates(&parking_lot_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn create_gate(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<CreateGateSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.create_gate(&parking_lot_id, &body).await {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn deactivate_gate(
+    Path((parking_lot_id, gate_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.deactivate_gate(&parking_lot_id, &gate_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn rotate_gate_key(
+    Path((parking_lot_id, gate_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.rotate_gate_key(&parking_lot_id, &gate_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_gate_events(
+    Path(parking_lot_id): Path<String>,
+    Query(QueryGateEvents { gate_id, limit, skip }): Query<QueryGateEvents>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.get_gate_events(&parking_lot_id, gate_id.as_deref(), limit, skip).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn gate_entry(
+    AuthGate { gate }: AuthGate,
+    Path(gate_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<GateEventSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.handle_gate_event(&gate, &gate_id, GateDirection::Entry, &body).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn gate_exit(
+    AuthGate { gate }: AuthGate,
+    Path(gate_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<GateEventSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.handle_gate_event(&gate, &gate_id, GateDirection::Exit, &body).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Query};
+use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
+
+use crate::AppState;
+use crate::utils::auth::AuthUser;
+use crate::structs::{
+    query::QueryTransactions,
+    schema::CreateTransactionSchema,
+};
+
+pub async fn get_user_transactions(
+    AuthUser { user_id, .. }: AuthUser,
+    Query(QueryTransactions { limit, skip }): Query<QueryTransactions>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .fetch_account_transactions(&user_id, limit, skip)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn create_user_transaction(
+    Path(user_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<CreateTransactionSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .create_user_transaction(&user_id, &body)
+        .await
+    {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_user_balance_reconciliation(
+    Path(user_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .reconcile_balance(&user_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
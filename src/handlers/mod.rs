@@ -1,8 +1,15 @@
 pub mod sample;
 pub mod common;
+pub mod auth;
 pub mod parking_lot;
 pub mod users;
+pub mod ledger;
 pub mod vehicle;
 pub mod ticket;
 pub mod tariff;
-pub mod parking_space;
\ No newline at end of file
+pub mod parking_space;
+pub mod reservation;
+pub mod pass;
+pub mod gate;
+pub mod anpr;
+pub mod access_code;
\ No newline at end of file
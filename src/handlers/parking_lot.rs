@@ -6,17 +6,21 @@ use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
 use crate::AppState;
 use crate::structs::{
     error::MyError,
+    layout::LevelDescription,
+    model::{AllocationStrategy, Calendar, PricingPolicy},
+    reservation::ReservationPolicy,
     schema::*,
-    query::QueryParkingLotCode,
+    query::{QueryNearbyParkingLots, QueryParkingLotCode, QueryParkingLots},
 };
 
 pub async fn get_parkings(
+    Query(query): Query<QueryParkingLots>,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
 {
     match app_state
         .db
-        .fetch_parkings()
+        .fetch_parkings(query.archived)
         .await
         .map_err(MyError::from)
     {
@@ -25,6 +29,21 @@ pub async fn get_parkings(
     }
 }
 
+pub async fn get_nearby_parkings(
+    Query(query): Query<QueryNearbyParkingLots>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .get_nearby_parking_lots(&query)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
 pub async fn get_parking(
     Path(parking_lot_id): Path<String>,
     State(app_state): State<Arc<AppState>>,
@@ -73,6 +92,54 @@ pub async fn get_parking_lot_income(
     }
 }
 
+pub async fn set_parking_lot_operator(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<SetParkingLotOperatorSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .set_parking_lot_operator(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set_parking_lot_pricing_policy(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<PricingPolicy>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .set_parking_lot_pricing_policy(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set_parking_lot_calendar(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<Calendar>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .set_parking_lot_calendar(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
 pub async fn create_parking(
     State(app_state): State<Arc<AppState>>,
     Json(body): Json<CreateParkingSchema>,
@@ -87,34 +154,138 @@ pub async fn create_parking(
 pub async fn generate_parking_lot_code(
     Path(parking_lot_id): Path<String>,
     State(app_state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, String)>
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)>
 {
     match app_state
         .db
-        .get_parking_lot_by_id(&parking_lot_id)
+        .get_parking_lot_code(&parking_lot_id)
         .await
-        .map_err(MyError::from)
     {
-        Ok(res) => {
-            let res: String = res.id.chars().take(8).collect();
-            Ok((StatusCode::CREATED, Json(res)))
-        },
-        Err(_) => Err((StatusCode::BAD_REQUEST, "Invalid input".to_string())),
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
     }
 }
 
 pub async fn get_parking_by_code(
     Query(QueryParkingLotCode { code }): Query<QueryParkingLotCode>,
     State(app_state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, String)>
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)>
 {
     match app_state
         .db
         .get_parking_lot_by_code(&code)
         .await
-        .map_err(MyError::from)
     {
         Ok(res) => Ok(Json(res)),
-        Err(_) => Err((StatusCode::BAD_REQUEST, "Invalid input".to_string())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set_parking_lot_allocation(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<AllocationStrategy>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .set_parking_lot_allocation(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set_parking_lot_reservation_policy(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<ReservationPolicy>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .set_parking_lot_reservation_policy(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn update_parking_lot(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<UpdateParkingLotSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .update_parking_lot(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
     }
-}
\ No newline at end of file
+}
+
+pub async fn archive_parking_lot(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .archive_parking_lot(&parking_lot_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn restore_parking_lot(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .restore_parking_lot(&parking_lot_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn add_parking_lot_level(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<LevelDescription>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .add_parking_lot_level(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn remove_parking_lot_level(
+    Path((parking_lot_id, level)): Path<(String, u32)>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .remove_parking_lot_level(&parking_lot_id, level)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
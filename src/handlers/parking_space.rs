@@ -6,7 +6,7 @@ use axum::{
 };
 
 use crate::{
-    structs::{error::MyError, query::QueryParkingSpaceCode}, 
+    structs::{error::MyError, query::QueryParkingSpaceCode, schema::{UpdateLevelSpacesSchema, UpdateParkingSpaceSchema, UpdateSpotPricingSchema}}, 
     AppState
 };
 
@@ -41,4 +41,68 @@ pub async fn get_parking_space_income(
         Ok(res) => Ok(Json(res)),
         Err(e) => Err(e.into()),
     }
-}
\ No newline at end of file
+}
+
+pub async fn update_spot_pricing(
+    Path((parking_lot_id, parking_space_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<UpdateSpotPricingSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .update_spot_pricing(&parking_lot_id, &parking_space_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn update_level_pricing(
+    Path((parking_lot_id, level)): Path<(String, u32)>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<UpdateSpotPricingSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .update_level_pricing(&parking_lot_id, level, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn update_parking_space(
+    Path((parking_lot_id, parking_space_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<UpdateParkingSpaceSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .update_parking_space(&parking_lot_id, &parking_space_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn update_level_spaces(
+    Path((parking_lot_id, level)): Path<(String, u32)>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<UpdateLevelSpacesSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .update_level_spaces(&parking_lot_id, level, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
+
+use crate::AppState;
+use crate::utils::auth::AuthUser;
+use crate::structs::schema::{BuyPassSchema, CreatePassPlanSchema, SetPassAutoRenewSchema};
+
+pub async fn get_pass_plans(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.get_pass_plans(&parking_lot_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn create_pass_plan(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<CreatePassPlanSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.create_pass_plan(&parking_lot_id, &body).await {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn retire_pass_plan(
+    Path((parking_lot_id, plan_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.retire_pass_plan(&parking_lot_id, &plan_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn buy_user_pass(
+    AuthUser { user_id, .. }: AuthUser,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<BuyPassSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.buy_pass(&user_id, &body).await {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_user_passes(
+    AuthUser { user_id, .. }: AuthUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.get_user_passes(&user_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn renew_user_pass(
+    AuthUser { user_id, .. }: AuthUser,
+    Path(id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.renew_pass(&id, &user_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set_user_pass_auto_renew(
+    AuthUser { user_id, .. }: AuthUser,
+    Path(id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<SetPassAutoRenewSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.set_pass_auto_renew(&id, &user_id, body.auto_renew).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
+
+use crate::AppState;
+use crate::utils::auth::AuthUser;
+use crate::structs::schema::CreateReservationSchema;
+
+pub async fn create_user_reservation(
+    AuthUser { user_id, .. }: AuthUser,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<CreateReservationSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.create_reservation(&user_id, &body).await {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_user_reservations(
+    AuthUser { user_id, .. }: AuthUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.get_user_reservations(&user_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn cancel_reservation(
+    AuthUser { user, .. }: AuthUser,
+    Path(id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.cancel_reservation(&id, &user).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn check_in_reservation(
+    AuthUser { user_id, .. }: AuthUser,
+    Path(id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.check_in_reservation(&id, &user_id).await {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_parking_lot_reservations(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.get_parking_lot_reservations(&parking_lot_id).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
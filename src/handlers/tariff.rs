@@ -1,8 +1,8 @@
 use std::sync::Arc;
 
-use axum::{extract::{State, Path}, response::IntoResponse, http::StatusCode, Json};
+use axum::{extract::{State, Path, Query}, response::IntoResponse, http::StatusCode, Json};
 
-use crate::{AppState, structs::error::MyError};
+use crate::{AppState, structs::{error::MyError, query::QueryQuote, schema::{CreateTariffSchema, CreateTariffSetSchema}}};
 
 pub async fn get_tariffs_by_parking_lot_id(
     Path(parking_lot_id): Path<String>,
@@ -18,4 +18,98 @@ pub async fn get_tariffs_by_parking_lot_id(
         Ok(res) => Ok(Json(res)),
         Err(e) => Err(e.into()),
     }
-}
\ No newline at end of file
+}
+
+pub async fn get_parking_lot_quote(
+    Path(parking_lot_id): Path<String>,
+    Query(query): Query<QueryQuote>,
+    State(app_state): State<Arc<AppState>>
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .quote_parking_lot(&parking_lot_id, &query)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_tariff_sets(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .get_tariff_sets(&parking_lot_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn replace_tariffs(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<CreateTariffSetSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .replace_tariffs(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn create_tariff(
+    Path(parking_lot_id): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<CreateTariffSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .add_tariff(&parking_lot_id, &body)
+        .await
+    {
+        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn update_tariff(
+    Path((parking_lot_id, tariff_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<CreateTariffSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .update_tariff(&parking_lot_id, &tariff_id, &body)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn delete_tariff(
+    Path((parking_lot_id, tariff_id)): Path<(String, String)>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state
+        .db
+        .delete_tariff(&parking_lot_id, &tariff_id)
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
 use axum::extract::{Query, Path};
-use axum::http::HeaderMap;
 use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
 
 use crate::AppState;
+use crate::utils::auth::AuthUser;
 use crate::structs::query::QueryTicket;
 use crate::structs::{
     error::MyError,
@@ -30,43 +30,55 @@ pub async fn get_tickets(
 pub async fn create_ticket(
     State(app_state): State<Arc<AppState>>,
     Json(body): Json<CreateTicketSchema>,
-) -> Result<impl IntoResponse, (StatusCode, String)> 
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
 {
-    match app_state.db.create_ticket(&body).await.map_err(MyError::from) {
+    match app_state.db.create_ticket(&body).await {
         Ok(res) => Ok((StatusCode::CREATED, Json(res))),
-        Err(_) => Err((StatusCode::BAD_REQUEST, "Invalid input".to_string())),
+        Err(e) => Err(e.into()),
     }
 }
 
 pub async fn put_ticket(
+    AuthUser { user, .. }: AuthUser,
     Path(code): Path<String>,
     State(app_state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, String)> 
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
 {
-    match app_state.db.put_ticket(&code).await.map_err(MyError::from) {
+    match app_state.db.put_user_ticket(&code, &user).await {
         Ok(res) => Ok((StatusCode::CREATED, Json(res))),
-        Err(e) => Err((StatusCode::BAD_REQUEST, "Invalid input ".to_string() + &e.to_string())),
+        Err(e) => Err(e.into()),
     }
 }
 
-pub async fn get_user_active_tickets(
-    headers: HeaderMap,
+pub async fn set_ticket_status(
+    Path(code): Path<String>,
     State(app_state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, String)> 
+    Json(body): Json<SetTicketStatusSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
 {
-    let authorization_header = match headers.get("Authorization") {
-        Some(header) => header.to_str().unwrap(),
-        None => return Err((StatusCode::BAD_REQUEST, "Invalid header".to_string())),
-    };
+    match app_state.db.set_ticket_status(&code, body.status).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
 
-    let user_id = match crate::utils::jwt::decode_token(authorization_header) {
-        Ok(claims) => claims.sub,
-        Err(e) => {
-            println!("{}", e);
-            return Err((StatusCode::BAD_REQUEST, "Invalid token".to_string()))
-        },
-    };
+pub async fn get_ticket_quote(
+    AuthUser { user, .. }: AuthUser,
+    Path(code): Path<String>,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
+{
+    match app_state.db.quote_ticket(&code, &user).await {
+        Ok(res) => Ok(Json(res)),
+        Err(e) => Err(e.into()),
+    }
+}
 
+pub async fn get_user_active_tickets(
+    AuthUser { user_id, .. }: AuthUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> 
+{
     match app_state
         .db
         .get_user_active_tickets(&user_id)
@@ -79,23 +91,13 @@ pub async fn get_user_active_tickets(
 }
 
 pub async fn create_user_ticket(
-    headers: HeaderMap,
+    AuthUser { user_id, .. }: AuthUser,
     State(app_state): State<Arc<AppState>>,
     Json(body): Json<CreateTicketUserSchema>,
-) -> Result<impl IntoResponse, (StatusCode, String)> 
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> 
 {
-    let authorization_header = match headers.get("Authorization") {
-        Some(header) => header.to_str().unwrap(),
-        None => return Err((StatusCode::BAD_REQUEST, "Invalid header".to_string())),
-    };
-
-    let user_id = match crate::utils::jwt::decode_token(authorization_header) {
-        Ok(claims) => claims.sub,
-        Err(_) => return Err((StatusCode::BAD_REQUEST, "Invalid token".to_string())),
-    };
-
-    match app_state.db.create_user_ticket(&user_id, &body).await.map_err(MyError::from) {
+    match app_state.db.create_user_ticket(&user_id, &body).await {
         Ok(res) => Ok((StatusCode::CREATED, Json(res))),
-        Err(_) => Err((StatusCode::BAD_REQUEST, "Invalid input".to_string())),
+        Err(e) => Err(e.into()),
     }
 }
\ No newline at end of file
@@ -1,10 +1,11 @@
 use std::sync::Arc;
 
 use axum::extract::{Query, Path};
-use axum::http::HeaderMap;
 use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
 
 use crate::AppState;
+use crate::handlers::auth::issue_tokens;
+use crate::utils::auth::AuthUser;
 use crate::structs::error::MyError;
 use crate::structs::query::UserBalance;
 use crate::structs::schema::{CreateUserSchema, RegisterUserSchema, LoginUserSchema};
@@ -40,9 +41,23 @@ pub async fn register_user(
     Json(body): Json<RegisterUserSchema>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> 
 {
-    match app_state.db.register_user(&body).await.map_err(MyError::from) {
-        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
-        Err(_) => Err((StatusCode::BAD_REQUEST, "Invalid input".to_string())),
+    let user = match app_state.db.register_user(&body).await.map_err(MyError::from) {
+        Ok(user) => user,
+        Err(_) => return Err((StatusCode::BAD_REQUEST, "Invalid input".to_string())),
+    };
+
+    let (session, refresh_token) = match app_state
+        .db
+        .create_session(&user._id.to_hex(), app_state.jwt.refresh_token_ttl)
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    };
+
+    match issue_tokens(&app_state, &user, &session, refresh_token) {
+        Ok(tokens) => Ok((StatusCode::CREATED, Json(tokens))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -51,28 +66,31 @@ pub async fn login_user(
     Json(body): Json<LoginUserSchema>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> 
 {
-    match app_state.db.login_user(&body).await.map_err(MyError::from) {
-        Ok(res) => Ok((StatusCode::CREATED, Json(res))),
-        Err(e) => Err((StatusCode::BAD_REQUEST, "Invalid input ".to_string() + &e.to_string())),
+    let user = match app_state.db.login_user(&body).await.map_err(MyError::from) {
+        Ok(user) => user,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, "Invalid input ".to_string() + &e.to_string())),
+    };
+
+    let (session, refresh_token) = match app_state
+        .db
+        .create_session(&user._id.to_hex(), app_state.jwt.refresh_token_ttl)
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    };
+
+    match issue_tokens(&app_state, &user, &session, refresh_token) {
+        Ok(tokens) => Ok((StatusCode::CREATED, Json(tokens))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
 pub async fn get_user_balance(
-    headers: HeaderMap,
+    AuthUser { user_id, .. }: AuthUser,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> 
 {
-    let authorization_header = match headers.get("Authorization") {
-        Some(header) => header.to_str().unwrap(),
-        None => return Err((StatusCode::BAD_REQUEST, "Invalid header".to_string())),
-    };
-
-    let user_id = match crate::utils::jwt::decode_token(authorization_header) {
-        Ok(claims) => claims.sub,
-        Err(_) => return Err((StatusCode::BAD_REQUEST, "Invalid token".to_string())),
-    };
-
-
     match app_state
         .db
         .get_user_balance(&user_id)
@@ -85,24 +103,14 @@ pub async fn get_user_balance(
 }
 
 pub async fn deposit_balance(
-    headers: HeaderMap,
+    AuthUser { user_id, .. }: AuthUser,
     Query(UserBalance { balance }): Query<UserBalance>,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> 
 {
-    let authorization_header = match headers.get("Authorization") {
-        Some(header) => header.to_str().unwrap(),
-        None => return Err((StatusCode::BAD_REQUEST, "Invalid header".to_string())),
-    };
-
-    let user_id = match crate::utils::jwt::decode_token(authorization_header) {
-        Ok(claims) => claims.sub,
-        Err(_) => return Err((StatusCode::BAD_REQUEST, "Invalid token".to_string())),
-    };
-
     match app_state
         .db
-        .deposit_balance(&user_id, balance)
+        .deposit_balance(&user_id, balance.parse().map_err(|_| (StatusCode::BAD_REQUEST, "Invalid amount".to_string()))?)
         .await
         .map_err(MyError::from)
     {
@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
 use axum::extract::Path;
-use axum::http::HeaderMap;
 use axum::{response::IntoResponse, http::StatusCode, extract::State, Json};
 
 use crate::AppState;
+use crate::utils::auth::AuthUser;
 use crate::structs::{
     error::MyError,
     schema::*,
@@ -53,20 +53,10 @@ pub async fn get_vehicle_by_license_plate_number(
 }
 
 pub async fn get_user_vehicles(
-    headers: HeaderMap,
+    AuthUser { user_id, .. }: AuthUser,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> 
 {
-    let authorization_header = match headers.get("Authorization") {
-        Some(header) => header.to_str().unwrap(),
-        None => return Err((StatusCode::BAD_REQUEST, "Invalid header".to_string())),
-    };
-
-    let user_id = match crate::utils::jwt::decode_token(authorization_header) {
-        Ok(claims) => claims.sub,
-        Err(_) => return Err((StatusCode::BAD_REQUEST, "Invalid token".to_string())),
-    };
-
     match app_state
         .db
         .fetch_user_vehicles(&user_id)
@@ -79,21 +69,11 @@ pub async fn get_user_vehicles(
 }
 
 pub async fn create_user_vehicle(
-    headers: HeaderMap,
+    AuthUser { user_id, .. }: AuthUser,
     State(app_state): State<Arc<AppState>>,
     Json(body): Json<CreateVehicleUserSchema>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> 
 {
-    let authorization_header = match headers.get("Authorization") {
-        Some(header) => header.to_str().unwrap(),
-        None => return Err((StatusCode::BAD_REQUEST, "Invalid header".to_string())),
-    };
-
-    let user_id = match crate::utils::jwt::decode_token(authorization_header) {
-        Ok(claims) => claims.sub,
-        Err(_) => return Err((StatusCode::BAD_REQUEST, "Invalid token".to_string())),
-    };
-
     match app_state.db.create_user_vehicle(&user_id, &body).await.map_err(MyError::from) {
         Ok(_) => Ok((StatusCode::CREATED, "successful operation")),
         Err(_) => Err((StatusCode::BAD_REQUEST, "Invalid input".to_string())),
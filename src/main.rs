@@ -2,12 +2,15 @@ mod structs;
 mod handlers;
 mod db;
 mod utils;
+mod pricing;
 
 use std::{time::Duration, sync::Arc};
 use axum::{
     http::{header, HeaderValue, Method,
             header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},},
-    routing::{get, post, put},
+    handler::Handler,
+    middleware::from_extractor_with_state,
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use dotenv::dotenv;
@@ -22,18 +25,28 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use handlers::{
     common::handler_404,
+    auth::{get_jwks, refresh_token, logout, logout_all},
     sample::{create_sample_user, root},
     users::{create_user, get_users, register_user, login_user, get_user_balance, deposit_balance, block_user}, 
-    parking_lot::{create_parking, get_parkings, get_parking_by_code, generate_parking_lot_code, get_parking, get_parking_lot_levels, get_parking_lot_income},
+    ledger::{get_user_transactions, create_user_transaction, get_user_balance_reconciliation},
+    parking_lot::{create_parking, get_parkings, get_nearby_parkings, get_parking_by_code, generate_parking_lot_code, get_parking, get_parking_lot_levels, get_parking_lot_income, set_parking_lot_operator, set_parking_lot_pricing_policy, set_parking_lot_calendar, set_parking_lot_allocation, set_parking_lot_reservation_policy, update_parking_lot, archive_parking_lot, restore_parking_lot, add_parking_lot_level, remove_parking_lot_level},
     vehicle::{create_vehicle, get_vehicles, get_vehicle_by_license_plate_number, get_user_vehicles, create_user_vehicle}, 
-    ticket::{get_tickets, create_ticket, put_ticket, get_user_active_tickets, create_user_ticket},
-    tariff::get_tariffs_by_parking_lot_id,
-    parking_space::{get_parking_spaces_by_parking_lot_id, get_parking_space_income},
+    ticket::{get_tickets, create_ticket, put_ticket, set_ticket_status, get_ticket_quote, get_user_active_tickets, create_user_ticket},
+    tariff::{get_tariffs_by_parking_lot_id, get_parking_lot_quote, get_tariff_sets, replace_tariffs, create_tariff, update_tariff, delete_tariff},
+    parking_space::{get_parking_spaces_by_parking_lot_id, get_parking_space_income, update_spot_pricing, update_level_pricing, update_parking_space, update_level_spaces},
+    reservation::{create_user_reservation, get_user_reservations, cancel_reservation, check_in_reservation, get_parking_lot_reservations},
+    anpr::{ingest_anpr_read, get_anpr_reads, review_anpr_read},
+    access_code::{get_access_codes, create_access_code, rotate_access_code, revoke_access_code},
+    gate::{get_gates, create_gate, deactivate_gate, rotate_gate_key, get_gate_events, gate_entry, gate_exit},
+    pass::{get_pass_plans, create_pass_plan, retire_pass_plan, buy_user_pass, get_user_passes, renew_user_pass, set_user_pass_auto_renew},
 };
 use db::common::DB;
+use structs::layout::LAYOUT_BODY_LIMIT;
+use utils::{auth::{Admin, AuthUser, RequireRole}, jwt::JwtKeys};
 
 pub struct AppState {
     db: DB,
+    jwt: JwtKeys,
 }
 
 #[tokio::main]
@@ -51,6 +64,7 @@ async fn main() {
         .init();
 
     let db = DB::new().await.unwrap();
+    let jwt = JwtKeys::from_env();
 
     let cors = CorsLayer::new()
         .allow_origin("http://0.0.0.0:3000".parse::<HeaderValue>().unwrap())
@@ -58,39 +72,113 @@ async fn main() {
         .allow_credentials(true)
         .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
 
-    let app = app(Arc::new(AppState { db: db.clone() })).await.layer(cors);
+    // reservations whose vehicle did not arrive are expired and passes set to renew are renewed once a minute
+    let expiry_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = expiry_db.expire_reservations().await {
+                println!("reservation expiry failed: {}", e);
+            }
+            if let Err(e) = expiry_db.renew_passes().await {
+                println!("pass renewal failed: {}", e);
+            }
+        }
+    });
+
+    let app = app(Arc::new(AppState { db: db.clone(), jwt })).await.layer(cors);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
 
 pub async fn app(app_state: Arc<AppState>) -> Router {
+    // routes without a layer are public, `/me/*` handlers authenticate through the `AuthUser` extractor
+    // and `/gates/*` handlers through the `AuthGate` extractor
+    let admin = from_extractor_with_state::<RequireRole<Admin>, _>(app_state.clone());
+    let authenticated = from_extractor_with_state::<AuthUser, _>(app_state.clone());
+
+    // floor plans of whole levels don't fit in the limit of other requests
+    let layouts = Router::new()
+        .route("/parking-lots", get(get_parkings).post(create_parking.layer(admin.clone())))
+        .route("/parking-lots/:id/levels", get(get_parking_lot_levels).post(add_parking_lot_level.layer(admin.clone())))
+        .layer(RequestBodyLimitLayer::new(LAYOUT_BODY_LIMIT));
+
     let app = Router::new()
         .route("/sample/", get(root))
         .route("/sample/users/", post(create_sample_user))
-        .route("/users", get(get_users).post(create_user))
+        .route("/users", get(get_users).post(create_user).route_layer(admin.clone()))
         .route("/user", post(register_user))
-        .route("/users/:id/block", put(block_user))
+        .route("/users/:id/block", put(block_user).route_layer(admin.clone()))
         .route("/login", post(login_user))
+        .route("/auth/refresh", post(refresh_token))
+        .route("/auth/logout", post(logout))
+        .route("/auth/logout-all", post(logout_all))
+        .route("/.well-known/jwks.json", get(get_jwks))
+        .route("/users/:id/transactions", post(create_user_transaction).route_layer(admin.clone()))
+        .route("/users/:id/reconciliation", get(get_user_balance_reconciliation).route_layer(admin.clone()))
         .route("/me/balance", get(get_user_balance).put(deposit_balance))
-        .route("/parking-lots", get(get_parkings).post(create_parking))
-        .route("/parking-lots/:id/code", get(generate_parking_lot_code))
+        .route("/me/transactions", get(get_user_transactions))
+        .route("/parking-lots/:id/code", get(generate_parking_lot_code).route_layer(admin.clone()))
+        .route("/parking-lots/:id/codes", get(get_access_codes).post(create_access_code).route_layer(admin.clone()))
+        .route("/parking-lots/:id/codes/:id", delete(revoke_access_code).route_layer(admin.clone()))
+        .route("/parking-lots/:id/codes/:id/rotate", post(rotate_access_code).route_layer(admin.clone()))
         .route("/parking-lots/", get(get_parking_by_code))
-        .route("/parking-lots/:id", get(get_parking))
-        .route("/parking-lots/:id/levels", get(get_parking_lot_levels))
-        .route("/parking-lots/:id/tariffs", get(get_tariffs_by_parking_lot_id))
-        .route("/parking-lots/:id/income", get(get_parking_lot_income))
-        .route("/vehicles", get(get_vehicles).post(create_vehicle))
-        .route("/vehicles/:license_plate_number", get(get_vehicle_by_license_plate_number))
+        .route("/parking-lots/nearby", get(get_nearby_parkings))
+        .route("/parking-lots/:id", get(get_parking)
+            .patch(update_parking_lot.layer(admin.clone()))
+            .delete(archive_parking_lot.layer(admin.clone())))
+        .route("/parking-lots/:id/restore", post(restore_parking_lot).route_layer(admin.clone()))
+        .route("/parking-lots/:id/levels/:level", delete(remove_parking_lot_level).route_layer(admin.clone()))
+        .route("/parking-lots/:id/tariffs", get(get_tariffs_by_parking_lot_id)
+            .post(create_tariff.layer(admin.clone()))
+            .put(replace_tariffs.layer(admin.clone())))
+        .route("/parking-lots/:id/tariffs/:id", put(update_tariff).delete(delete_tariff).route_layer(admin.clone()))
+        .route("/parking-lots/:id/tariff-sets", get(get_tariff_sets).route_layer(admin.clone()))
+        .route("/parking-lots/:id/quote", get(get_parking_lot_quote))
+        .route("/parking-lots/:id/income", get(get_parking_lot_income).route_layer(admin.clone()))
+        .route("/parking-lots/:id/operator", put(set_parking_lot_operator).route_layer(admin.clone()))
+        .route("/parking-lots/:id/pricing-policy", put(set_parking_lot_pricing_policy).route_layer(admin.clone()))
+        .route("/parking-lots/:id/calendar", put(set_parking_lot_calendar).route_layer(admin.clone()))
+        .route("/parking-lots/:id/allocation", put(set_parking_lot_allocation).route_layer(admin.clone()))
+        .route("/parking-lots/:id/reservation-policy", put(set_parking_lot_reservation_policy).route_layer(admin.clone()))
+        .route("/parking-lots/:id/reservations", get(get_parking_lot_reservations).route_layer(admin.clone()))
+        .route("/parking-lots/:id/pass-plans", get(get_pass_plans).post(create_pass_plan.layer(admin.clone())))
+        .route("/parking-lots/:id/pass-plans/:id", delete(retire_pass_plan).route_layer(admin.clone()))
+        .route("/parking-lots/:id/gates", get(get_gates).post(create_gate).route_layer(admin.clone()))
+        .route("/parking-lots/:id/gates/:id", delete(deactivate_gate).route_layer(admin.clone()))
+        .route("/parking-lots/:id/gates/:id/key", post(rotate_gate_key).route_layer(admin.clone()))
+        .route("/parking-lots/:id/gate-events", get(get_gate_events).route_layer(admin.clone()))
+        .route("/gates/:id/entry", post(gate_entry))
+        .route("/gates/:id/exit", post(gate_exit))
+        .route("/gates/:id/anpr", post(ingest_anpr_read))
+        .route("/parking-lots/:id/anpr-reads", get(get_anpr_reads).route_layer(admin.clone()))
+        .route("/parking-lots/:id/anpr-reads/:id", put(review_anpr_read).route_layer(admin.clone()))
+        .route("/vehicles", get(get_vehicles).post(create_vehicle).route_layer(admin.clone()))
+        .route("/vehicles/:license_plate_number", get(get_vehicle_by_license_plate_number).route_layer(authenticated.clone()))
         .route("/me/vehicles", get(get_user_vehicles).post(create_user_vehicle))
-        .route("/tickets", get(get_tickets).post(create_ticket))
+        .route("/tickets", get(get_tickets).post(create_ticket).route_layer(admin.clone()))
         .route("/tickets/:code", put(put_ticket))
+        .route("/tickets/:code/status", put(set_ticket_status).route_layer(admin.clone()))
+        .route("/tickets/:code/quote", get(get_ticket_quote))
         .route("/me/ticket", get(get_user_active_tickets).post(create_user_ticket))
-        .route("/parking-lots/:id/parking-spots", get(get_parking_spaces_by_parking_lot_id))
-        .route("/parking-lots/:id/parking-spots/:id/income", get(get_parking_space_income))
-        .layer(TimeoutLayer::new(Duration::from_secs(10)))
+        .route("/me/reservations", get(get_user_reservations).post(create_user_reservation))
+        .route("/me/reservations/:id", delete(cancel_reservation))
+        .route("/me/reservations/:id/check-in", post(check_in_reservation))
+        .route("/me/passes", get(get_user_passes).post(buy_user_pass))
+        .route("/me/passes/:id", put(set_user_pass_auto_renew))
+        .route("/me/passes/:id/renew", post(renew_user_pass))
+        .route("/parking-lots/:id/parking-spots", get(get_parking_spaces_by_parking_lot_id).route_layer(admin.clone()))
+        .route("/parking-lots/:id/parking-spots/:id", patch(update_parking_space).route_layer(admin.clone()))
+        .route("/parking-lots/:id/parking-spots/:id/income", get(get_parking_space_income).route_layer(admin.clone()))
+        .route("/parking-lots/:id/parking-spots/:id/pricing", put(update_spot_pricing).route_layer(admin.clone()))
+        .route("/parking-lots/:id/levels/:level/pricing", put(update_level_pricing).route_layer(admin.clone()))
+        .route("/parking-lots/:id/levels/:level/parking-spots", patch(update_level_spaces).route_layer(admin.clone()))
         // don't allow request bodies larger than 1024 bytes, returning 413 status code
         .layer(RequestBodyLimitLayer::new(1024))
+        .merge(layouts)
+        .layer(TimeoutLayer::new(Duration::from_secs(10)))
         .layer(TraceLayer::new_for_http())
         .layer(SetResponseHeaderLayer::if_not_present(
             header::SERVER, 
@@ -118,7 +206,7 @@ mod tests {
         dotenv().ok();
 
         let db = DB::new().await.unwrap();
-        let app = app(Arc::new(AppState { db: db.clone() })).await;
+        let app = app(Arc::new(AppState { db: db.clone(), jwt: JwtKeys::from_env() })).await;
 
         let response = app
             .oneshot(Request::builder().uri("/sample/").body(Body::empty()).unwrap())
@@ -136,7 +224,7 @@ mod tests {
         dotenv().ok();
 
         let db = DB::new().await.unwrap();
-        let app = app(Arc::new(AppState { db: db.clone() })).await;
+        let app = app(Arc::new(AppState { db: db.clone(), jwt: JwtKeys::from_env() })).await;
 
         let response = app
             .oneshot(
@@ -169,7 +257,7 @@ mod tests {
         dotenv().ok();
 
         let db = DB::new().await.unwrap();
-        let app = app(Arc::new(AppState { db: db.clone() })).await;
+        let app = app(Arc::new(AppState { db: db.clone(), jwt: JwtKeys::from_env() })).await;
 
         let response = app
             .oneshot(
@@ -192,7 +280,7 @@ mod tests {
         dotenv().ok();
 
         let db = DB::new().await.unwrap();
-        let mut app = app(Arc::new(AppState { db: db.clone() })).await.into_service();
+        let mut app = app(Arc::new(AppState { db: db.clone(), jwt: JwtKeys::from_env() })).await.into_service();
 
         let request = Request::builder().uri("/sample/").body(Body::empty()).unwrap();
         let response = ServiceExt::<Request<Body>>::ready(&mut app)
@@ -212,4 +300,282 @@ mod tests {
             .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn floor_plans_pass_the_body_limit() {
+        use bson::{doc, oid::ObjectId};
+        use structs::{
+            model::{Role, User},
+            money::{Currency, Money},
+        };
+
+        dotenv().ok();
+
+        let db = DB::new().await.unwrap();
+        let jwt = JwtKeys::from_env();
+        let user = |role: Role| User {
+            _id: ObjectId::new(),
+            name: "Test".to_string(),
+            surname: "Test".to_string(),
+            email: format!("{}@test.com", ObjectId::new().to_hex()),
+            password: String::new(),
+            account_balance: Money::zero(Currency::default()),
+            role,
+            blocked: false,
+        };
+        let (admin, operator) = (user(Role::Admin), user(Role::Operator));
+        db.user_collection.insert_many([&admin, &operator], None).await.unwrap();
+        let (session, _) = db.create_session(&admin._id.to_hex(), chrono::Duration::minutes(5)).await.unwrap();
+        let token = jwt.create_token(&admin._id.to_hex(), &session._id.to_hex(), (&admin).into()).unwrap();
+        let app = app(Arc::new(AppState { db: db.clone(), jwt })).await;
+
+        // five levels of 250 labelled spaces in zones of 50, as an import of a mall car park: four
+        // with the lot and one added to it later
+        let levels: Vec<Value> = (0..5)
+            .map(|no_level| json!({
+                "spots": (0..5).map(|zone| json!({
+                    "zone": format!("Z{}", zone),
+                    "row": "1",
+                    "vehicleType": "Car",
+                    "count": 50,
+                    "labels": (1..=50).map(|number| format!("P{}-Z{}-{:03}", no_level, zone, number)).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>(),
+            }))
+            .collect();
+        let money = serde_json::to_value(Money::new(500, Currency::default())).unwrap();
+        let lot = serde_json::to_string(&json!({
+            "costOfMaintenance": { "electricity": money, "cleaning": money, "security": money },
+            "location": { "city": "Test", "address": "Test", "latitude": 52.23, "longitude": 21.01 },
+            "levels": levels[..4],
+            "tariffs": [{ "minTime": 1, "maxTime": 24, "pricePerHour": money }],
+            "operatorId": operator._id.to_hex(),
+        })).unwrap();
+        let level = serde_json::to_string(&levels[4]).unwrap();
+        assert!(level.len() > 1024);
+
+        let post = |uri: &str, body: &String| {
+            Request::builder()
+                .method(http::Method::POST)
+                .uri(uri)
+                .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .header(http::header::CONTENT_LENGTH, body.len())
+                .body(Body::from(body.to_owned()))
+                .unwrap()
+        };
+
+        let created = app.clone().oneshot(post("/parking-lots", &lot)).await.unwrap().status();
+        let parking_lot = db.parking_lot_collection
+            .find_one(doc! { "operator_id": operator._id.to_hex() }, None)
+            .await
+            .unwrap();
+        let parking_lot_id = parking_lot.as_ref().map(|parking_lot| parking_lot._id).unwrap_or_default();
+        let added = app.clone()
+            .oneshot(post(&format!("/parking-lots/{}/levels", parking_lot_id.to_hex()), &level))
+            .await
+            .unwrap()
+            .status();
+        let spaces = db.parking_space_collection
+            .count_documents(doc! { "parking_lot_id": parking_lot_id }, None)
+            .await
+            .unwrap();
+        let too_large = app.oneshot(post("/tickets", &level)).await.unwrap().status();
+
+        let users = [admin._id, operator._id];
+        db.parking_space_collection.delete_many(doc! { "parking_lot_id": parking_lot_id }, None).await.unwrap();
+        db.tariff_collection.delete_many(doc! { "parking_lot_id": parking_lot_id.to_hex() }, None).await.unwrap();
+        db.tariff_set_collection.delete_many(doc! { "parking_lot_id": parking_lot_id.to_hex() }, None).await.unwrap();
+        db.parking_lot_collection.delete_one(doc! { "_id": parking_lot_id }, None).await.unwrap();
+        db.session_collection.delete_one(doc! { "_id": session._id }, None).await.unwrap();
+        db.user_collection.delete_many(doc! { "_id": { "$in": users.to_vec() } }, None).await.unwrap();
+
+        assert_eq!(created, StatusCode::CREATED);
+        assert_eq!(added, StatusCode::CREATED);
+        assert_eq!(spaces, 5 * 250);
+        // other routes keep the 1 KB limit
+        assert_eq!(too_large, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn parallel_tickets_get_distinct_spaces() {
+        use std::collections::HashSet;
+
+        use futures::StreamExt;
+
+        use bson::{doc, oid::ObjectId};
+        use structs::{
+            layout,
+            model::{CostOfMaintenance, Location, ParkingLocation, ParkingLot, SpotCategory, VehicleType},
+            money::{Currency, Money},
+            schema::CreateParkingSpaceSchema,
+        };
+
+        const SPACES: u32 = 50;
+        const TICKETS: usize = 300;
+
+        dotenv().ok();
+
+        let db = DB::new().await.unwrap();
+        let parking_lot_id = ObjectId::new();
+        let zero = Money::zero(Currency::default());
+        db.parking_lot_collection
+            .insert_one(ParkingLot {
+                _id: parking_lot_id,
+                cost_of_maintenance: CostOfMaintenance { electricity: zero, cleaning: zero, security: zero },
+                location: Location {
+                    city: "Test".to_string(),
+                    address: "Test".to_string(),
+                    latitude: 0.0,
+                    longitude: 0.0,
+                },
+                no_levels: 1,
+                operator_id: ObjectId::new().to_hex(),
+                pricing_policy: Default::default(),
+                calendar: Default::default(),
+                allocation: Default::default(),
+                reservation_policy: Default::default(),
+                archived_at: None,
+                geo: None,
+            }, None)
+            .await
+            .unwrap();
+        for no_space in 0..SPACES {
+            db.create_parking_space(&CreateParkingSpaceSchema {
+                parking_lot_id,
+                location: ParkingLocation { no_level: 0, no_space },
+                vehicle_type: VehicleType::Car,
+                category: SpotCategory::Standard,
+                label: layout::spot_label(0, "C", no_space + 1),
+                zone: None,
+                row: None,
+            }).await.unwrap();
+        }
+
+        let requests = (0..TICKETS).map(|i| {
+            let db = db.clone();
+            tokio::spawn(async move {
+                db.create_user_ticket(&ObjectId::new().to_hex(), &structs::schema::CreateTicketUserSchema {
+                    vehicle_license_number: format!("WAW{:05}", i),
+                    parking_lot_id: parking_lot_id.to_hex(),
+                    vehicle_type: Some(VehicleType::Car),
+                    preferred_level: None,
+                }).await
+            })
+        });
+        let issued = futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .filter(|result| result.as_ref().unwrap().is_ok())
+            .count();
+
+        let parking_lot_filter = doc! { "parking_lot_id": parking_lot_id.to_hex() };
+        let tickets: Vec<_> = db.ticket_collection
+            .find(parking_lot_filter.clone(), None)
+            .await
+            .unwrap()
+            .map(|ticket| ticket.unwrap().parking_spot_id)
+            .collect()
+            .await;
+        let free_spaces = db.parking_space_collection
+            .count_documents(doc! { "parking_lot_id": parking_lot_id, "occupied": false }, None)
+            .await
+            .unwrap();
+
+        db.ticket_collection.delete_many(parking_lot_filter, None).await.unwrap();
+        db.parking_space_collection.delete_many(doc! { "parking_lot_id": parking_lot_id }, None).await.unwrap();
+        db.parking_lot_collection.delete_one(doc! { "_id": parking_lot_id }, None).await.unwrap();
+
+        assert_eq!(issued, SPACES as usize);
+        assert_eq!(tickets.len(), SPACES as usize);
+        assert_eq!(tickets.iter().collect::<HashSet<_>>().len(), SPACES as usize);
+        assert_eq!(free_spaces, 0);
+    }
+
+    #[tokio::test]
+    async fn second_refund_of_a_ticket_is_rejected() {
+        use bson::{doc, oid::ObjectId};
+        use structs::{
+            error::MyError,
+            model::{CostOfMaintenance, Location, ParkingLot, Role, Ticket, TransactionKind, User},
+            money::{Currency, Money},
+            schema::CreateTransactionSchema,
+            ticket_status::TicketStatus,
+        };
+
+        dotenv().ok();
+
+        let db = DB::new().await.unwrap();
+        let zero = Money::zero(Currency::default());
+        let user = |role: Role, balance: i64| User {
+            _id: ObjectId::new(),
+            name: "Test".to_string(),
+            surname: "Test".to_string(),
+            email: format!("{}@test.com", ObjectId::new().to_hex()),
+            password: String::new(),
+            account_balance: Money::new(balance, Currency::default()),
+            role,
+            blocked: false,
+        };
+        let (driver, operator) = (user(Role::User, 10_000), user(Role::Operator, 0));
+        db.user_collection.insert_many([&driver, &operator], None).await.unwrap();
+
+        let parking_lot_id = ObjectId::new();
+        db.parking_lot_collection
+            .insert_one(ParkingLot {
+                _id: parking_lot_id,
+                cost_of_maintenance: CostOfMaintenance { electricity: zero, cleaning: zero, security: zero },
+                location: Location {
+                    city: "Test".to_string(),
+                    address: "Test".to_string(),
+                    latitude: 0.0,
+                    longitude: 0.0,
+                },
+                no_levels: 1,
+                operator_id: operator._id.to_hex(),
+                pricing_policy: Default::default(),
+                calendar: Default::default(),
+                allocation: Default::default(),
+                reservation_policy: Default::default(),
+                archived_at: None,
+                geo: None,
+            }, None)
+            .await
+            .unwrap();
+        let ticket = Ticket {
+            _id: ObjectId::new(),
+            user_id: driver._id.to_hex(),
+            vehicle_license_number: "WAW00001".to_string(),
+            parking_spot_id: ObjectId::new().to_hex(),
+            issue_timestamp: 0,
+            end_timestamp: 3600,
+            amount_paid: Money::new(1_500, Currency::default()),
+            level: 0,
+            spot_ordinal_number: 0,
+            parking_lot_id: parking_lot_id.to_hex(),
+            code: structs::access_code::generate_code(),
+            status: TicketStatus::Exited,
+        };
+        db.ticket_collection.insert_one(&ticket, None).await.unwrap();
+        db.transfer_balance(&ticket, ticket.amount_paid).await.unwrap();
+
+        let refund = |amount: i64| CreateTransactionSchema {
+            kind: TransactionKind::Refund,
+            amount: Money::new(amount, Currency::default()),
+            ticket_id: Some(ticket._id.to_hex()),
+            description: "Refund".to_string(),
+        };
+        let first = db.create_user_transaction(&driver._id.to_hex(), &refund(1_000)).await;
+        let second = db.create_user_transaction(&driver._id.to_hex(), &refund(1_000)).await;
+        let rest = db.create_user_transaction(&driver._id.to_hex(), &refund(500)).await;
+
+        let users = [driver._id, operator._id];
+        db.transaction_collection.delete_many(doc! { "ticket_id": ticket._id.to_hex() }, None).await.unwrap();
+        db.ticket_collection.delete_one(doc! { "_id": ticket._id }, None).await.unwrap();
+        db.parking_lot_collection.delete_one(doc! { "_id": parking_lot_id }, None).await.unwrap();
+        db.user_collection.delete_many(doc! { "_id": { "$in": users.to_vec() } }, None).await.unwrap();
+
+        assert!(first.is_ok());
+        assert!(matches!(second, Err(MyError::ValidationError(_))));
+        assert!(rest.is_ok());
+    }
+}
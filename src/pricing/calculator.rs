@@ -0,0 +1,486 @@
+use chrono::DateTime;
+use serde::Serialize;
+
+use crate::structs::{
+    model::{Calendar, ParkingSpace, PricingPolicy, Schedule, SpotCategory, Tariff, TariffScope, VehicleType},
+    money::{Currency, Money, MoneyError, Multiplier},
+};
+
+use super::rules::{DailyCap, HourBand, MinimumCharge, PassDiscount, PriceModifier, Round, TieredRate};
+
+pub const SECONDS_PER_HOUR: i64 = 3600;
+/// Longest stay quoted in advance.
+pub const MAX_QUOTE_DAYS: i64 = 366;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum PricingError {
+    #[error("no tariff covers hour {0} of the stay")]
+    NoRate(i64),
+    #[error("stay ends before it starts")]
+    InvalidStay,
+    #[error("timestamp {0} is out of range")]
+    OutOfRange(i64),
+    #[error("stays longer than {0} days cannot be quoted")]
+    QuoteTooLong(i64),
+    #[error(transparent)]
+    Money(#[from] MoneyError),
+}
+
+type Result<T> = std::result::Result<T, PricingError>;
+
+/// A stay to be priced, as unix timestamps.
+#[derive(Debug, Clone, Copy)]
+pub struct Stay {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Stay {
+    /// Both ends must be dates and the stay must not end before it starts.
+    pub fn validate(&self) -> Result<()> {
+        for timestamp in [self.start, self.end] {
+            if DateTime::from_timestamp(timestamp, 0).is_none() {
+                return Err(PricingError::OutOfRange(timestamp));
+            }
+        }
+        if self.end < self.start {
+            return Err(PricingError::InvalidStay);
+        }
+
+        Ok(())
+    }
+
+    /// A stay asked for a quote is also limited in length, as every billable
+    /// hour of it is priced.
+    pub fn validate_quote(&self) -> Result<()> {
+        self.validate()?;
+        if self.end - self.start > MAX_QUOTE_DAYS * 24 * SECONDS_PER_HOUR {
+            return Err(PricingError::QuoteTooLong(MAX_QUOTE_DAYS));
+        }
+
+        Ok(())
+    }
+
+    /// Every started hour is billed, and so is a stay shorter than a minute.
+    pub fn billable_hours(&self) -> Result<i64> {
+        let seconds = self
+            .end
+            .checked_sub(self.start)
+            .and_then(|seconds| seconds.checked_add(SECONDS_PER_HOUR - 1))
+            .ok_or(PricingError::InvalidStay)?;
+
+        Ok((seconds / SECONDS_PER_HOUR).max(1))
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LineItem {
+    pub description: String,
+    pub amount: Money,
+}
+
+/// Result of pricing a stay. The line items always add up to the total;
+/// `hourly` keeps the price of every billable hour as set by the rate rules,
+/// before caps, minimums and rounding are applied.
+#[derive(Debug, Clone)]
+pub struct Charge {
+    pub currency: Currency,
+    pub hourly: Vec<Money>,
+    pub items: Vec<LineItem>,
+}
+
+impl Charge {
+    pub fn new(currency: Currency) -> Self {
+        Charge {
+            currency,
+            hourly: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, description: String, amount: Money) {
+        self.items.push(LineItem { description, amount });
+    }
+
+    pub fn total(&self) -> Result<Money> {
+        let mut total = Money::zero(self.currency);
+        for item in &self.items {
+            total = total.checked_add(item.amount)?;
+        }
+
+        Ok(total)
+    }
+}
+
+/// What is being priced: picks the tariffs scoped to the spot, its modifier
+/// and the pass of the vehicle, if it has one.
+#[derive(Debug, Clone)]
+pub struct PricedSpot {
+    pub vehicle_type: VehicleType,
+    pub category: SpotCategory,
+    pub price_modifier: Multiplier,
+    pub pass: Option<PassCoverage>,
+}
+
+impl From<&ParkingSpace> for PricedSpot {
+    fn from(parking_space: &ParkingSpace) -> Self {
+        PricedSpot {
+            vehicle_type: parking_space.vehicle_type.to_owned(),
+            category: parking_space.category.to_owned(),
+            price_modifier: parking_space.price_modifier,
+            pass: None,
+        }
+    }
+}
+
+/// Hours a pass pays for: those starting from `valid_from` until `valid_until`
+/// while `schedule` matches. They cost `rate` of their price.
+#[derive(Debug, Clone)]
+pub struct PassCoverage {
+    pub name: String,
+    pub valid_from: i64,
+    pub valid_until: i64,
+    pub schedule: Schedule,
+    pub rate: Multiplier,
+}
+
+impl TariffScope {
+    pub fn matches(&self, spot: &PricedSpot) -> bool {
+        (self.vehicle_types.is_empty() || self.vehicle_types.contains(&spot.vehicle_type))
+            && (self.spot_categories.is_empty() || self.spot_categories.contains(&spot.category))
+    }
+
+    pub fn specificity(&self) -> u32 {
+        let mut specificity = 0;
+        if !self.spot_categories.is_empty() {
+            specificity += 2;
+        }
+        if !self.vehicle_types.is_empty() {
+            specificity += 1;
+        }
+        specificity
+    }
+}
+
+/// A step of a price calculation. Rules run in the order they were added to
+/// the [`PriceCalculator`] and each one sees the charge left by the previous.
+pub trait PricingRule: Send + Sync {
+    fn apply(&self, stay: &Stay, charge: &mut Charge) -> Result<()>;
+}
+
+pub struct PriceCalculator {
+    currency: Currency,
+    rules: Vec<Box<dyn PricingRule>>,
+}
+
+impl PriceCalculator {
+    pub fn new(currency: Currency) -> Self {
+        PriceCalculator {
+            currency,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with_rule(mut self, rule: impl PricingRule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Standard pipeline for a parking lot: hour bands from the tariffs scoped
+    /// to the spot, its price modifier, the pass of the vehicle, then the lot's
+    /// pricing policy. A stay under a pass owes no minimum charge.
+    pub fn for_parking_lot(tariffs: &[Tariff], calendar: &Calendar, policy: &PricingPolicy, spot: &PricedSpot) -> Self {
+        let currency = tariffs
+            .first()
+            .map(|tariff| tariff.price_per_hour.currency)
+            .unwrap_or_default();
+        // the scope outranks the schedule: an EV tariff beats a generic night one
+        let bands = tariffs
+            .iter()
+            .filter(|tariff| tariff.scope.matches(spot))
+            .map(|tariff| HourBand {
+                first_hour: tariff.min_time,
+                last_hour: tariff.max_time,
+                price_per_hour: tariff.price_per_hour,
+                schedule: tariff.schedule.to_owned(),
+                priority: tariff.scope.specificity() * 16 + tariff.schedule.specificity(),
+            })
+            .collect();
+
+        let mut calculator = PriceCalculator::new(currency)
+            .with_rule(TieredRate::new(bands, calendar.to_owned()))
+            .with_rule(PriceModifier(spot.price_modifier));
+        if let Some(pass) = &spot.pass {
+            calculator = calculator.with_rule(PassDiscount { pass: pass.to_owned(), calendar: calendar.to_owned() });
+        }
+        if let Some(cap) = policy.cap {
+            calculator = calculator.with_rule(DailyCap(cap));
+        }
+        if let Some(minimum) = policy.minimum.filter(|_| spot.pass.is_none()) {
+            calculator = calculator.with_rule(MinimumCharge(minimum));
+        }
+        calculator.with_rule(Round(policy.rounding.to_owned()))
+    }
+
+    pub fn calculate(&self, stay: &Stay) -> Result<Charge> {
+        stay.validate()?;
+
+        let mut charge = Charge::new(self.currency);
+        for rule in &self.rules {
+            rule.apply(stay, &mut charge)?;
+        }
+
+        Ok(charge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use chrono::{NaiveDate, Weekday};
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::structs::model::{HolidayRule, HourRange, Rounding, RoundingMode, Schedule};
+
+    fn pln(amount: i64) -> Money {
+        Money::new(amount, Currency::PLN)
+    }
+
+    fn hours(hours: i64) -> Stay {
+        Stay { start: 0, end: hours * SECONDS_PER_HOUR }
+    }
+
+    fn tariff(min_time: i64, max_time: i64, price_per_hour: i64) -> Tariff {
+        Tariff {
+            _id: ObjectId::new(),
+            parking_lot_id: "lot".to_string(),
+            tariff_set_id: "set".to_string(),
+            min_time,
+            max_time,
+            price_per_hour: pln(price_per_hour),
+            schedule: Schedule::default(),
+            scope: TariffScope::default(),
+        }
+    }
+
+    fn car(price_modifier: Multiplier) -> PricedSpot {
+        PricedSpot {
+            vehicle_type: VehicleType::Car,
+            category: SpotCategory::Standard,
+            price_modifier,
+            pass: None,
+        }
+    }
+
+    #[test]
+    fn started_hours_are_billed() {
+        assert_eq!(Stay { start: 0, end: 0 }.billable_hours(), Ok(1));
+        assert_eq!(Stay { start: 0, end: 1 }.billable_hours(), Ok(1));
+        assert_eq!(Stay { start: 0, end: 3600 }.billable_hours(), Ok(1));
+        assert_eq!(Stay { start: 0, end: 3601 }.billable_hours(), Ok(2));
+    }
+
+    #[test]
+    fn stays_out_of_range_are_rejected() {
+        assert_eq!(Stay { start: i64::MIN, end: i64::MAX }.billable_hours(), Err(PricingError::InvalidStay));
+        assert_eq!(Stay { start: i64::MAX, end: i64::MAX }.validate(), Err(PricingError::OutOfRange(i64::MAX)));
+        assert_eq!(Stay { start: 0, end: -1 }.validate(), Err(PricingError::InvalidStay));
+
+        let calculator = PriceCalculator::for_parking_lot(&[tariff(1, 1, 500)], &Calendar::default(), &PricingPolicy::default(), &car(Multiplier::ONE));
+        assert_eq!(calculator.calculate(&Stay { start: i64::MIN, end: 0 }).unwrap_err(), PricingError::OutOfRange(i64::MIN));
+    }
+
+    #[test]
+    fn quotes_are_limited_in_length() {
+        let year = MAX_QUOTE_DAYS * 24 * SECONDS_PER_HOUR;
+
+        assert_eq!(Stay { start: 0, end: year }.validate_quote(), Ok(()));
+        assert_eq!(Stay { start: 0, end: year + 1 }.validate_quote(), Err(PricingError::QuoteTooLong(MAX_QUOTE_DAYS)));
+        assert_eq!(Stay { start: 0, end: 1_000_000_000_000_000 }.validate_quote(), Err(PricingError::OutOfRange(1_000_000_000_000_000)));
+    }
+
+    #[test]
+    fn each_band_is_charged_at_its_own_rate() {
+        let tariffs = [tariff(1, 1, 500), tariff(2, 4, 400), tariff(5, 100, 300)];
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &PricingPolicy::default(), &car(Multiplier::ONE));
+
+        let charge = calculator.calculate(&hours(6)).unwrap();
+        assert_eq!(charge.total().unwrap(), pln(500 + 3 * 400 + 2 * 300));
+        assert_eq!(charge.items.len(), 3);
+
+        let charge = calculator.calculate(&hours(1)).unwrap();
+        assert_eq!(charge.total().unwrap(), pln(500));
+    }
+
+    #[test]
+    fn hours_past_the_last_band_use_its_rate() {
+        let tariffs = [tariff(1, 2, 500), tariff(3, 4, 400)];
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &PricingPolicy::default(), &car(Multiplier::ONE));
+
+        assert_eq!(calculator.calculate(&hours(6)).unwrap().total().unwrap(), pln(2 * 500 + 4 * 400));
+    }
+
+    #[test]
+    fn gaps_between_bands_are_rejected() {
+        let tariffs = [tariff(1, 2, 500), tariff(4, 10, 400)];
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &PricingPolicy::default(), &car(Multiplier::ONE));
+
+        assert_eq!(calculator.calculate(&hours(5)).unwrap_err(), PricingError::NoRate(3));
+        assert_eq!(
+            PriceCalculator::for_parking_lot(&[], &Calendar::default(), &PricingPolicy::default(), &car(Multiplier::ONE))
+                .calculate(&hours(1))
+                .unwrap_err(),
+            PricingError::NoRate(1),
+        );
+    }
+
+    #[test]
+    fn modifier_scales_every_hour() {
+        let tariffs = [tariff(1, 100, 400)];
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &PricingPolicy::default(), &car(Multiplier(12_500)));
+
+        assert_eq!(calculator.calculate(&hours(3)).unwrap().total().unwrap(), pln(1500));
+    }
+
+    #[test]
+    fn daily_cap_limits_every_day_separately() {
+        let tariffs = [tariff(1, 1000, 500)];
+        let policy = PricingPolicy { cap: Some(pln(6000)), ..Default::default() };
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &policy, &car(Multiplier::ONE));
+
+        // 24h capped at 60.00, then 2h at 5.00
+        assert_eq!(calculator.calculate(&hours(26)).unwrap().total().unwrap(), pln(6000 + 1000));
+        assert_eq!(calculator.calculate(&hours(10)).unwrap().total().unwrap(), pln(5000));
+    }
+
+    #[test]
+    fn minimum_charge_and_rounding_apply_to_the_total() {
+        let tariffs = [tariff(1, 100, 333)];
+        let policy = PricingPolicy {
+            minimum: Some(pln(500)),
+            rounding: Rounding { mode: RoundingMode::Up, step: 50 },
+            ..Default::default()
+        };
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &policy, &car(Multiplier::ONE));
+
+        assert_eq!(calculator.calculate(&hours(1)).unwrap().total().unwrap(), pln(500));
+        assert_eq!(calculator.calculate(&hours(2)).unwrap().total().unwrap(), pln(700));
+
+        let charge = calculator.calculate(&hours(2)).unwrap();
+        assert_eq!(charge.items.last().unwrap().amount, pln(34));
+    }
+
+    #[test]
+    fn stay_is_split_across_schedules() {
+        let night = Schedule {
+            hours: Some(HourRange { from: 22, to: 6 }),
+            ..Default::default()
+        };
+        let weekend = Schedule {
+            weekdays: vec![Weekday::Sat, Weekday::Sun],
+            ..Default::default()
+        };
+        let tariffs = [
+            Tariff { schedule: night, ..tariff(1, 1000, 200) },
+            Tariff { schedule: weekend, ..tariff(1, 1000, 300) },
+            tariff(1, 1000, 500),
+        ];
+        let calendar = Calendar { timezone: Tz::UTC, holidays: Vec::new() };
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &calendar, &PricingPolicy::default(), &car(Multiplier::ONE));
+
+        // Friday 2024-03-01 22:00 to Saturday 09:00: weekday night 2h, then
+        // the weekend schedule (more specific than the night one) for 9h
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(22, 0, 0).unwrap().and_utc().timestamp();
+        let charge = calculator.calculate(&Stay { start, end: start + 11 * SECONDS_PER_HOUR }).unwrap();
+        assert_eq!(charge.total().unwrap(), pln(2 * 200 + 9 * 300));
+        assert_eq!(charge.items.len(), 2);
+
+        // Thursday 20:00 to 23:00: day rate, then night rate
+        let start = start - 26 * SECONDS_PER_HOUR;
+        let charge = calculator.calculate(&Stay { start, end: start + 3 * SECONDS_PER_HOUR }).unwrap();
+        assert_eq!(charge.total().unwrap(), pln(2 * 500 + 200));
+    }
+
+    #[test]
+    fn holidays_use_their_own_tariff() {
+        let holiday = Schedule {
+            holidays: HolidayRule::Only,
+            ..Default::default()
+        };
+        let tariffs = [Tariff { schedule: holiday, ..tariff(1, 1000, 100) }, tariff(1, 1000, 500)];
+        let calendar = Calendar {
+            timezone: Tz::UTC,
+            holidays: vec![NaiveDate::from_ymd_opt(2024, 3, 2).unwrap()],
+        };
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &calendar, &PricingPolicy::default(), &car(Multiplier::ONE));
+
+        // Friday 23:00 to Saturday 02:00, Saturday being a holiday
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(23, 0, 0).unwrap().and_utc().timestamp();
+        let charge = calculator.calculate(&Stay { start, end: start + 3 * SECONDS_PER_HOUR }).unwrap();
+        assert_eq!(charge.total().unwrap(), pln(500 + 2 * 100));
+    }
+
+    #[test]
+    fn scoped_tariffs_take_precedence_for_their_spots() {
+        let ev = TariffScope {
+            spot_categories: vec![SpotCategory::Ev],
+            ..Default::default()
+        };
+        let trucks = TariffScope {
+            vehicle_types: vec![VehicleType::Truck],
+            ..Default::default()
+        };
+        let tariffs = [
+            Tariff { scope: ev, ..tariff(1, 1000, 800) },
+            Tariff { scope: trucks, ..tariff(1, 1000, 1000) },
+            tariff(1, 1000, 500),
+        ];
+        let price = |spot: PricedSpot| {
+            PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &PricingPolicy::default(), &spot)
+                .calculate(&hours(2))
+                .unwrap()
+                .total()
+                .unwrap()
+        };
+
+        assert_eq!(price(car(Multiplier::ONE)), pln(1000));
+        assert_eq!(price(PricedSpot { category: SpotCategory::Ev, ..car(Multiplier::ONE) }), pln(1600));
+        assert_eq!(price(PricedSpot { vehicle_type: VehicleType::Truck, ..car(Multiplier::ONE) }), pln(2000));
+        // category is more specific than vehicle type
+        assert_eq!(
+            price(PricedSpot { vehicle_type: VehicleType::Truck, category: SpotCategory::Ev, price_modifier: Multiplier::ONE, pass: None }),
+            pln(1600),
+        );
+    }
+
+    fn pass(valid_until: i64, schedule: Schedule, rate: Multiplier) -> PassCoverage {
+        PassCoverage { name: "Commuter".to_string(), valid_from: 0, valid_until, schedule, rate }
+    }
+
+    #[test]
+    fn pass_makes_covered_hours_free_without_a_minimum() {
+        let tariffs = [tariff(1, 100, 500)];
+        let policy = PricingPolicy { minimum: Some(pln(2000)), ..Default::default() };
+        let spot = PricedSpot { pass: Some(pass(2 * SECONDS_PER_HOUR, Schedule::default(), Multiplier(0))), ..car(Multiplier::ONE) };
+        let calculator = PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &policy, &spot);
+
+        // the pass runs out after two hours of the stay
+        assert_eq!(calculator.calculate(&hours(2)).unwrap().total().unwrap(), pln(0));
+        assert_eq!(calculator.calculate(&hours(3)).unwrap().total().unwrap(), pln(500));
+    }
+
+    #[test]
+    fn off_peak_pass_discounts_only_its_hours() {
+        let tariffs = [tariff(1, 100, 500)];
+        // the stay starts at 01:00 in Warsaw; the pass covers hours starting before 03:00
+        let off_peak = Schedule { hours: Some(HourRange { from: 22, to: 3 }), ..Default::default() };
+        let spot = PricedSpot { pass: Some(pass(i64::MAX, off_peak, Multiplier(5_000))), ..car(Multiplier::ONE) };
+        let charge = PriceCalculator::for_parking_lot(&tariffs, &Calendar::default(), &PricingPolicy::default(), &spot)
+            .calculate(&hours(4))
+            .unwrap();
+
+        assert_eq!(charge.total().unwrap(), pln(2 * 250 + 2 * 500));
+        assert_eq!(charge.items.last().unwrap().description, "Pass Commuter (2 hours)");
+    }
+}
@@ -0,0 +1,4 @@
+pub mod calculator;
+pub mod rules;
+pub mod schedule;
+pub mod tariffs;
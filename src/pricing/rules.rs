@@ -0,0 +1,219 @@
+use chrono::NaiveDateTime;
+
+use crate::structs::{
+    model::{Calendar, Rounding, RoundingMode, Schedule},
+    money::{Money, Multiplier},
+};
+
+use super::calculator::{Charge, PassCoverage, PricingError, PricingRule, Stay, SECONDS_PER_HOUR};
+
+type Result<T> = std::result::Result<T, PricingError>;
+
+const HOURS_PER_DAY: usize = 24;
+
+/// Hours `first_hour..=last_hour` of a stay, counted from 1, cost `price_per_hour`
+/// each while `schedule` matches the local time at which the hour starts.
+/// When bands of several schedules match, those with the highest `priority` are used.
+#[derive(Debug, Clone)]
+pub struct HourBand {
+    pub first_hour: i64,
+    pub last_hour: i64,
+    pub price_per_hour: Money,
+    pub schedule: Schedule,
+    pub priority: u32,
+}
+
+/// Charges every hour at the rate of the band it falls into: a 6 hour stay
+/// with bands 1, 2-4 and 5+ pays one hour at the first rate, three at the
+/// second and two at the third. Hours past the last band keep its rate.
+///
+/// Each hour is billed by the schedule in force when it starts, so a stay from
+/// Friday 22:00 to Saturday 09:00 pays night rates first and weekend rates
+/// after.
+pub struct TieredRate {
+    bands: Vec<HourBand>,
+    calendar: Calendar,
+}
+
+impl TieredRate {
+    pub fn new(mut bands: Vec<HourBand>, calendar: Calendar) -> Self {
+        bands.sort_by_key(|band| band.first_hour);
+        TieredRate { bands, calendar }
+    }
+
+    fn band_for(&self, hour: i64, local_time: &NaiveDateTime) -> Option<&HourBand> {
+        let holiday = self.calendar.is_holiday(local_time);
+        let mut matching: Vec<&HourBand> = self
+            .bands
+            .iter()
+            .filter(|band| band.schedule.matches(local_time, holiday))
+            .collect();
+        matching.sort_by_key(|band| std::cmp::Reverse(band.priority));
+
+        for level in matching.chunk_by(|a, b| a.priority == b.priority) {
+            let band = match level.iter().find(|band| band.first_hour <= hour && hour <= band.last_hour) {
+                Some(band) => Some(*band),
+                None => level.iter().max_by_key(|band| band.last_hour).filter(|band| hour > band.last_hour).copied(),
+            };
+            if band.is_some() {
+                return band;
+            }
+        }
+
+        None
+    }
+}
+
+impl PricingRule for TieredRate {
+    fn apply(&self, stay: &Stay, charge: &mut Charge) -> Result<()> {
+        // consecutive hours of the same band become one line item
+        let mut run: Option<(i64, i64, &HourBand)> = None;
+        let mut runs = Vec::new();
+        for hour in 1..=stay.billable_hours()? {
+            let start = stay.start + (hour - 1) * SECONDS_PER_HOUR;
+            let local_time = self.calendar.local_time(start).ok_or(PricingError::OutOfRange(start))?;
+            let band = self.band_for(hour, &local_time).ok_or(PricingError::NoRate(hour))?;
+            charge.hourly.push(band.price_per_hour);
+            run = match run {
+                Some((first, _, current)) if std::ptr::eq(current, band) => Some((first, hour, band)),
+                Some(finished) => {
+                    runs.push(finished);
+                    Some((hour, hour, band))
+                }
+                None => Some((hour, hour, band)),
+            };
+        }
+        runs.extend(run);
+
+        for (first, last, band) in runs {
+            let description = if first == last {
+                format!("Hour {} at {}", first, band.price_per_hour)
+            } else {
+                format!("Hours {}-{} at {}", first, last, band.price_per_hour)
+            };
+            charge.push(description, band.price_per_hour.checked_mul(last - first + 1)?);
+        }
+
+        Ok(())
+    }
+}
+
+/// Scales every hour by the price modifier of the parking space.
+pub struct PriceModifier(pub Multiplier);
+
+impl PricingRule for PriceModifier {
+    fn apply(&self, _stay: &Stay, charge: &mut Charge) -> Result<()> {
+        if self.0 == Multiplier::ONE {
+            return Ok(());
+        }
+
+        let mut difference = Money::zero(charge.currency);
+        for price in charge.hourly.iter_mut() {
+            let modified = price.apply_modifier(self.0)?;
+            difference = difference.checked_add(modified.checked_sub(*price)?)?;
+            *price = modified;
+        }
+        let Multiplier(basis_points) = self.0;
+        charge.push(format!("Parking space modifier {}%", basis_points as f64 / 100.0), difference);
+
+        Ok(())
+    }
+}
+
+/// Scales the hours a pass covers by its rate; a rate of zero makes them free.
+pub struct PassDiscount {
+    pub pass: PassCoverage,
+    pub calendar: Calendar,
+}
+
+impl PricingRule for PassDiscount {
+    fn apply(&self, stay: &Stay, charge: &mut Charge) -> Result<()> {
+        let mut difference = Money::zero(charge.currency);
+        let mut covered = 0;
+        for (hour, price) in charge.hourly.iter_mut().enumerate() {
+            let start = stay.start + hour as i64 * SECONDS_PER_HOUR;
+            let local_time = self.calendar.local_time(start).ok_or(PricingError::OutOfRange(start))?;
+            let valid = self.pass.valid_from <= start && start < self.pass.valid_until;
+            if !valid || !self.pass.schedule.matches(&local_time, self.calendar.is_holiday(&local_time)) {
+                continue;
+            }
+
+            let discounted = price.apply_modifier(self.pass.rate)?;
+            difference = difference.checked_add(discounted.checked_sub(*price)?)?;
+            *price = discounted;
+            covered += 1;
+        }
+        if covered > 0 {
+            charge.push(format!("Pass {} ({} hours)", self.pass.name, covered), difference);
+        }
+
+        Ok(())
+    }
+}
+
+/// No 24 hour period of a stay, counted from its start, costs more than the cap.
+pub struct DailyCap(pub Money);
+
+impl PricingRule for DailyCap {
+    fn apply(&self, _stay: &Stay, charge: &mut Charge) -> Result<()> {
+        let mut discounts = Vec::new();
+        for (day, hours) in charge.hourly.chunks(HOURS_PER_DAY).enumerate() {
+            let mut day_total = Money::zero(charge.currency);
+            for price in hours {
+                day_total = day_total.checked_add(*price)?;
+            }
+            let over_cap = day_total.checked_sub(self.0)?;
+            if over_cap.is_positive() {
+                discounts.push((day + 1, Money::zero(charge.currency).checked_sub(over_cap)?));
+            }
+        }
+
+        for (day, discount) in discounts {
+            charge.push(format!("Daily cap of {} (day {})", self.0, day), discount);
+        }
+
+        Ok(())
+    }
+}
+
+/// Raises the total to at least the given amount.
+pub struct MinimumCharge(pub Money);
+
+impl PricingRule for MinimumCharge {
+    fn apply(&self, _stay: &Stay, charge: &mut Charge) -> Result<()> {
+        let shortfall = self.0.checked_sub(charge.total()?)?;
+        if shortfall.is_positive() {
+            charge.push(format!("Minimum charge of {}", self.0), shortfall);
+        }
+
+        Ok(())
+    }
+}
+
+/// Rounds the total to a multiple of `step` minor units.
+pub struct Round(pub Rounding);
+
+impl PricingRule for Round {
+    fn apply(&self, _stay: &Stay, charge: &mut Charge) -> Result<()> {
+        let Rounding { mode, step } = &self.0;
+        let total = charge.total()?;
+        if *step <= 1 || *mode == RoundingMode::None {
+            return Ok(());
+        }
+
+        let remainder = total.amount.rem_euclid(*step);
+        let adjustment = match mode {
+            RoundingMode::None => 0,
+            RoundingMode::Down => -remainder,
+            RoundingMode::Up if remainder == 0 => 0,
+            RoundingMode::Up => step - remainder,
+            RoundingMode::Nearest if remainder * 2 >= *step => step - remainder,
+            RoundingMode::Nearest => -remainder,
+        };
+        if adjustment != 0 {
+            charge.push("Rounding".to_string(), Money::new(adjustment, charge.currency));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::{Datelike, NaiveDateTime, TimeZone, Timelike, Utc};
+
+use crate::structs::model::{Calendar, HolidayRule, Schedule};
+
+impl Calendar {
+    /// Wall clock time of the lot, or `None` for a timestamp chrono cannot
+    /// represent.
+    pub fn local_time(&self, timestamp: i64) -> Option<NaiveDateTime> {
+        Utc.timestamp_opt(timestamp, 0)
+            .single()
+            .map(|time| time.with_timezone(&self.timezone).naive_local())
+    }
+
+    pub fn is_holiday(&self, local_time: &NaiveDateTime) -> bool {
+        self.holidays.contains(&local_time.date())
+    }
+}
+
+impl Schedule {
+    pub fn matches(&self, local_time: &NaiveDateTime, holiday: bool) -> bool {
+        if !self.weekdays.is_empty() && !self.weekdays.contains(&local_time.weekday()) {
+            return false;
+        }
+        if let Some(hours) = &self.hours {
+            let hour = local_time.hour();
+            let in_range = if hours.from <= hours.to {
+                hours.from <= hour && hour < hours.to
+            } else {
+                hours.from <= hour || hour < hours.to
+            };
+            if !in_range {
+                return false;
+            }
+        }
+        if let Some(dates) = &self.dates {
+            let date = local_time.date();
+            if date < dates.from || date > dates.to {
+                return false;
+            }
+        }
+
+        match self.holidays {
+            HolidayRule::Any => true,
+            HolidayRule::Only => holiday,
+            HolidayRule::Never => !holiday,
+        }
+    }
+
+    /// When several schedules match the same hour the most specific one wins:
+    /// holiday rates over date ranges over weekdays over hours of the day.
+    pub fn specificity(&self) -> u32 {
+        let mut specificity = 0;
+        if self.holidays == HolidayRule::Only {
+            specificity += 8;
+        }
+        if self.dates.is_some() {
+            specificity += 4;
+        }
+        if !self.weekdays.is_empty() {
+            specificity += 2;
+        }
+        if self.hours.is_some() {
+            specificity += 1;
+        }
+        specificity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Weekday};
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::structs::model::{DateRange, HourRange};
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        // 2024-03-01 is a Friday
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn hour_ranges_wrap_past_midnight() {
+        let night = Schedule {
+            hours: Some(HourRange { from: 22, to: 6 }),
+            ..Default::default()
+        };
+
+        assert!(night.matches(&at(1, 22), false));
+        assert!(night.matches(&at(2, 5), false));
+        assert!(!night.matches(&at(2, 6), false));
+        assert!(!night.matches(&at(1, 21), false));
+    }
+
+    #[test]
+    fn weekdays_dates_and_holidays_restrict() {
+        let weekend = Schedule {
+            weekdays: vec![Weekday::Sat, Weekday::Sun],
+            ..Default::default()
+        };
+        assert!(!weekend.matches(&at(1, 12), false));
+        assert!(weekend.matches(&at(2, 12), false));
+
+        let march = Schedule {
+            dates: Some(DateRange {
+                from: NaiveDate::from_ymd_opt(2024, 3, 2).unwrap(),
+                to: NaiveDate::from_ymd_opt(2024, 3, 3).unwrap(),
+            }),
+            ..Default::default()
+        };
+        assert!(!march.matches(&at(1, 23), false));
+        assert!(march.matches(&at(3, 23), false));
+
+        let holiday = Schedule {
+            holidays: HolidayRule::Only,
+            ..Default::default()
+        };
+        assert!(holiday.matches(&at(1, 12), true));
+        assert!(!holiday.matches(&at(1, 12), false));
+        assert!(holiday.specificity() > weekend.specificity());
+    }
+
+    #[test]
+    fn local_time_follows_the_lot_time_zone() {
+        let calendar = Calendar {
+            timezone: Tz::Europe__Warsaw,
+            holidays: vec![NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()],
+        };
+
+        // 2023-12-31 23:30 UTC is already New Year's Day in Warsaw
+        let local_time = calendar.local_time(1704065400).unwrap();
+        assert_eq!(local_time.hour(), 0);
+        assert!(calendar.is_holiday(&local_time));
+    }
+
+    #[test]
+    fn timestamps_out_of_range_have_no_local_time() {
+        let calendar = Calendar { timezone: Tz::Europe__Warsaw, holidays: vec![] };
+
+        assert_eq!(calendar.local_time(i64::MAX), None);
+        assert_eq!(calendar.local_time(i64::MIN), None);
+    }
+}
@@ -0,0 +1,136 @@
+use chrono::NaiveDate;
+
+use crate::structs::model::{Schedule, Tariff};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum TariffError {
+    #[error("a parking lot needs at least one tariff")]
+    Empty,
+    #[error("hours {0}..{1} are not a valid tariff band")]
+    InvalidBand(i64, i64),
+    #[error("price per hour cannot be negative")]
+    NegativePrice,
+    #[error("tariffs of one parking lot must share a currency")]
+    MixedCurrency,
+    #[error("invalid hour range {0}..{1}")]
+    InvalidHours(u32, u32),
+    #[error("invalid date range {0}..{1}")]
+    InvalidDates(NaiveDate, NaiveDate),
+    #[error("tariff bands {0}..{1} and {2}..{3} overlap")]
+    Overlap(i64, i64, i64, i64),
+    #[error("no tariff band covers hours {0}..{1}")]
+    Gap(i64, i64),
+}
+
+/// Checks a tariff set before it is stored. Bands sharing a schedule and a
+/// scope must cover the hours of a stay from 1 on without gaps or overlaps;
+/// the last of them keeps its rate for longer stays.
+pub fn validate_tariffs(tariffs: &[Tariff]) -> Result<(), TariffError> {
+    let currency = tariffs.first().ok_or(TariffError::Empty)?.price_per_hour.currency;
+
+    let mut ladders: Vec<Vec<&Tariff>> = Vec::new();
+    for tariff in tariffs {
+        if tariff.min_time < 1 || tariff.max_time < tariff.min_time {
+            return Err(TariffError::InvalidBand(tariff.min_time, tariff.max_time));
+        }
+        if tariff.price_per_hour.is_negative() {
+            return Err(TariffError::NegativePrice);
+        }
+        if tariff.price_per_hour.currency != currency {
+            return Err(TariffError::MixedCurrency);
+        }
+        validate_schedule(&tariff.schedule)?;
+
+        match ladders
+            .iter_mut()
+            .find(|ladder| ladder[0].schedule == tariff.schedule && ladder[0].scope == tariff.scope)
+        {
+            Some(ladder) => ladder.push(tariff),
+            None => ladders.push(vec![tariff]),
+        }
+    }
+
+    for ladder in ladders.iter_mut() {
+        ladder.sort_by_key(|tariff| tariff.min_time);
+        if ladder[0].min_time > 1 {
+            return Err(TariffError::Gap(1, ladder[0].min_time - 1));
+        }
+        for pair in ladder.windows(2) {
+            let (previous, next) = (pair[0], pair[1]);
+            if next.min_time <= previous.max_time {
+                return Err(TariffError::Overlap(previous.min_time, previous.max_time, next.min_time, next.max_time));
+            }
+            if next.min_time > previous.max_time + 1 {
+                return Err(TariffError::Gap(previous.max_time + 1, next.min_time - 1));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn validate_schedule(schedule: &Schedule) -> Result<(), TariffError> {
+    if let Some(hours) = &schedule.hours {
+        if hours.from > 23 || hours.to > 24 || hours.from == hours.to {
+            return Err(TariffError::InvalidHours(hours.from, hours.to));
+        }
+    }
+    if let Some(dates) = &schedule.dates {
+        if dates.from > dates.to {
+            return Err(TariffError::InvalidDates(dates.from, dates.to));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+
+    use super::*;
+    use crate::structs::{
+        model::{HourRange, Schedule, TariffScope},
+        money::{Currency, Money},
+    };
+
+    fn tariff(min_time: i64, max_time: i64) -> Tariff {
+        Tariff {
+            _id: ObjectId::new(),
+            parking_lot_id: "lot".to_string(),
+            tariff_set_id: "set".to_string(),
+            min_time,
+            max_time,
+            price_per_hour: Money::new(500, Currency::PLN),
+            schedule: Schedule::default(),
+            scope: TariffScope::default(),
+        }
+    }
+
+    #[test]
+    fn bands_must_be_contiguous_from_the_first_hour() {
+        assert_eq!(validate_tariffs(&[]), Err(TariffError::Empty));
+        assert_eq!(validate_tariffs(&[tariff(9, 100), tariff(1, 8)]), Ok(()));
+        assert_eq!(validate_tariffs(&[tariff(2, 8)]), Err(TariffError::Gap(1, 1)));
+        assert_eq!(validate_tariffs(&[tariff(1, 4), tariff(7, 9)]), Err(TariffError::Gap(5, 6)));
+        assert_eq!(validate_tariffs(&[tariff(1, 4), tariff(4, 9)]), Err(TariffError::Overlap(1, 4, 4, 9)));
+        assert_eq!(validate_tariffs(&[tariff(5, 4)]), Err(TariffError::InvalidBand(5, 4)));
+    }
+
+    #[test]
+    fn each_schedule_has_its_own_bands() {
+        let mut night = tariff(1, 12);
+        night.schedule.hours = Some(HourRange { from: 22, to: 6 });
+        assert_eq!(validate_tariffs(&[tariff(1, 3), tariff(4, 24), night.clone()]), Ok(()));
+
+        night.min_time = 2;
+        assert_eq!(validate_tariffs(&[tariff(1, 24), night]), Err(TariffError::Gap(1, 1)));
+    }
+
+    #[test]
+    fn prices_share_one_currency() {
+        let mut euro = tariff(2, 10);
+        euro.price_per_hour = Money::new(100, Currency::EUR);
+        assert_eq!(validate_tariffs(&[tariff(1, 1), euro]), Err(TariffError::MixedCurrency));
+    }
+}
@@ -0,0 +1,58 @@
+use rand::Rng;
+
+/// Characters of access codes: digits and upper case letters without the
+/// ones read as each other (0/O, 1/I/L).
+const ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+/// 31^8, about 8.5e11 codes.
+pub const CODE_LENGTH: usize = 8;
+/// Attempts at a code not taken yet before giving up.
+pub const GENERATE_ATTEMPTS: usize = 5;
+
+/// Random access code in its stored form, e.g. `7KQ2MXRP`.
+pub fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Stored form of a code as people type it: upper case, without spaces or
+/// dashes. `None` when it cannot be a code.
+pub fn normalize_code(code: &str) -> Option<String> {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    let valid = normalized.len() == CODE_LENGTH && normalized.bytes().all(|c| ALPHABET.contains(&c));
+    valid.then_some(normalized)
+}
+
+/// Code as shown to people, in two groups: `7KQ2-MXRP`.
+pub fn format_code(code: &str) -> String {
+    let (head, tail) = code.split_at(code.len() / 2);
+    format!("{}-{}", head, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_codes_are_valid() {
+        for _ in 0..100 {
+            let code = generate_code();
+            assert_eq!(normalize_code(&code), Some(code));
+        }
+    }
+
+    #[test]
+    fn codes_are_read_without_case_or_separators() {
+        assert_eq!(normalize_code(" 7kq2-mxrp "), Some("7KQ2MXRP".to_string()));
+        assert_eq!(format_code("7KQ2MXRP"), "7KQ2-MXRP");
+        assert_eq!(normalize_code("7KQ2MXR"), None);
+        assert_eq!(normalize_code("7KQ2MXR0"), None);
+        assert_eq!(normalize_code("5f1e2a3b"), None);
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Reads at least this confident open or close tickets without review.
+pub const AUTO_CONFIDENCE: f64 = 0.9;
+/// Most characters a read may differ from a known plate by and still match it.
+pub const MAX_DISTANCE: usize = 1;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum AnprError {
+    #[error("confidence must be between 0 and 1")]
+    InvalidConfidence,
+    #[error("no characters in plate read {0:?}")]
+    EmptyPlate(String),
+}
+
+/// Where a plate read is in its life. Reads the backend could not act on
+/// wait in the review queue until an attendant resolves or rejects them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnprReadStatus {
+    /// Matched and acted on without review.
+    Processed,
+    PendingReview,
+    /// An attendant confirmed or corrected the plate.
+    Resolved,
+    Rejected,
+}
+
+impl AnprReadStatus {
+    pub fn can_become(&self, next: AnprReadStatus) -> bool {
+        use AnprReadStatus::*;
+
+        matches!((self, next), (PendingReview, Resolved | Rejected))
+    }
+}
+
+impl fmt::Display for AnprReadStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Plate as cameras and people write it differently: upper case, without
+/// spaces, dashes or other separators.
+pub fn normalize_plate(plate: &str) -> String {
+    plate
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Characters cameras confuse with each other, the first one standing for
+/// the whole group.
+const CONFUSABLE: [&str; 6] = ["0OQD", "1IL", "2Z", "5S", "6G", "8B"];
+
+fn fold(c: char) -> char {
+    CONFUSABLE
+        .iter()
+        .find(|group| group.contains(c))
+        .and_then(|group| group.chars().next())
+        .unwrap_or(c)
+}
+
+/// Normalized plate with confusable characters folded together, so `WO1234`
+/// and `W01234` compare equal.
+pub fn canonical_plate(plate: &str) -> String {
+    normalize_plate(plate).chars().map(fold).collect()
+}
+
+/// Edits needed to turn one plate into the other, not counting separators,
+/// case or confusable characters.
+pub fn plate_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = canonical_plate(a).chars().collect();
+    let b: Vec<char> = canonical_plate(b).chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Case-insensitive regular expression matching stored plates that are the
+/// read up to separators and confusable characters.
+pub fn plate_pattern(plate: &str) -> String {
+    let classes: Vec<String> = canonical_plate(plate)
+        .chars()
+        .map(|c| match CONFUSABLE.iter().find(|group| group.starts_with(c)) {
+            Some(group) => format!("[{}]", group),
+            None => c.to_string(),
+        })
+        .collect();
+
+    format!("^[^A-Za-z0-9]*{}[^A-Za-z0-9]*$", classes.join("[^A-Za-z0-9]*"))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlateMatch {
+    Found { plate: String, distance: usize },
+    /// Several known plates are equally close to the read.
+    Ambiguous(Vec<String>),
+    NotFound,
+}
+
+/// Closest known plate within `MAX_DISTANCE` of the read.
+pub fn match_plate<'a>(read: &str, candidates: impl IntoIterator<Item = &'a str>) -> PlateMatch {
+    let mut best: Vec<&str> = Vec::new();
+    let mut best_distance = MAX_DISTANCE + 1;
+    for candidate in candidates {
+        let distance = plate_distance(read, candidate);
+        if distance < best_distance {
+            best = vec![candidate];
+            best_distance = distance;
+        } else if distance == best_distance && !best.contains(&candidate) {
+            best.push(candidate);
+        }
+    }
+
+    match best.as_slice() {
+        [] => PlateMatch::NotFound,
+        [plate] => PlateMatch::Found { plate: plate.to_string(), distance: best_distance },
+        plates => PlateMatch::Ambiguous(plates.iter().map(|plate| plate.to_string()).collect()),
+    }
+}
+
+/// What to do with a read: act on the matched plate, or queue it for review
+/// with the reason.
+#[derive(Debug, PartialEq)]
+pub enum AnprOutcome {
+    Automatic(String),
+    Review(String),
+}
+
+pub fn decide(confidence: f64, found: PlateMatch) -> AnprOutcome {
+    match found {
+        PlateMatch::Found { plate, .. } if confidence >= AUTO_CONFIDENCE => AnprOutcome::Automatic(plate),
+        PlateMatch::Found { plate, .. } => AnprOutcome::Review(format!("low confidence read of {}", plate)),
+        PlateMatch::Ambiguous(plates) => AnprOutcome::Review(format!("read matches {}", plates.join(", "))),
+        PlateMatch::NotFound => AnprOutcome::Review("no matching vehicle".to_string()),
+    }
+}
+
+pub fn validate_read(plate: &str, confidence: f64) -> Result<(), AnprError> {
+    if !(0.0..=1.0).contains(&confidence) {
+        return Err(AnprError::InvalidConfidence);
+    }
+    if normalize_plate(plate).is_empty() {
+        return Err(AnprError::EmptyPlate(plate.to_owned()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plates_compare_without_separators_case_or_confusable_characters() {
+        assert_eq!(normalize_plate(" wa-123 45 "), "WA12345");
+        assert_eq!(plate_distance("WO 1234", "w01234"), 0);
+        assert_eq!(plate_distance("KR1234", "KR1235"), 1);
+        assert_eq!(plate_distance("KR1234", "KR123"), 1);
+        assert_eq!(plate_distance("KR1234", "GD9876"), 6);
+    }
+
+    #[test]
+    fn patterns_accept_separators_and_confusable_characters() {
+        assert_eq!(plate_pattern("wo-1"), "^[^A-Za-z0-9]*W[^A-Za-z0-9]*[0OQD][^A-Za-z0-9]*[1IL][^A-Za-z0-9]*$");
+    }
+
+    #[test]
+    fn only_a_single_close_plate_is_a_match() {
+        assert_eq!(
+            match_plate("KR1234", ["KR1235", "GD9876"]),
+            PlateMatch::Found { plate: "KR1235".to_string(), distance: 1 },
+        );
+        assert_eq!(match_plate("KR1234", ["KR1234", "KR1235"]), PlateMatch::Found { plate: "KR1234".to_string(), distance: 0 });
+        assert_eq!(
+            match_plate("KR1234", ["KR1235", "KR1236"]),
+            PlateMatch::Ambiguous(vec!["KR1235".to_string(), "KR1236".to_string()]),
+        );
+        assert_eq!(match_plate("KR1234", ["GD9876"]), PlateMatch::NotFound);
+    }
+
+    #[test]
+    fn confident_single_matches_are_acted_on() {
+        let found = || PlateMatch::Found { plate: "KR1234".to_string(), distance: 0 };
+
+        assert_eq!(decide(0.95, found()), AnprOutcome::Automatic("KR1234".to_string()));
+        assert!(matches!(decide(0.5, found()), AnprOutcome::Review(_)));
+        assert!(matches!(decide(0.99, PlateMatch::NotFound), AnprOutcome::Review(_)));
+        assert_eq!(validate_read("KR1234", 1.5), Err(AnprError::InvalidConfidence));
+        assert_eq!(validate_read(" - ", 0.5), Err(AnprError::EmptyPlate(" - ".to_string())));
+    }
+}
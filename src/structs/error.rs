@@ -1,6 +1,9 @@
 use axum::{http::StatusCode, Json};
 use serde::Serialize;
 
+use super::{anpr::{AnprError, AnprReadStatus}, geo::GeoError, layout::LayoutError, model::VehicleType, money::MoneyError, reservation::{ReservationError, ReservationStatus}, ticket_status::TicketStatus};
+use crate::pricing::{calculator::PricingError, tariffs::TariffError};
+
 #[derive(thiserror::Error, Debug)]
 pub enum MyError {
     #[error("MongoDB error")]
@@ -35,8 +38,52 @@ pub enum MyError {
     NotEnoughBalanceError(String),
     #[error("Vehicle not found: {0}")]
     VehicleNotFoundError(String),
+    #[error("vehicle {0} is registered as {1:?}, not {2:?}")]
+    VehicleTypeMismatchError(String, VehicleType, VehicleType),
     #[error("Invalid credentials: {0}")]
     InvalidCredentialsError(String),
+    #[error("Unauthorized: {0}")]
+    UnauthorizedError(String),
+    #[error("Forbidden: {0}")]
+    ForbiddenError(String),
+    #[error("User blocked: {0}")]
+    UserBlockedError(String),
+    #[error("token error: {0}")]
+    TokenError(#[from] jsonwebtoken::errors::Error),
+    #[error("validation error: {0}")]
+    ValidationError(String),
+    #[error("money error: {0}")]
+    MoneyError(#[from] MoneyError),
+    #[error("pricing error: {0}")]
+    PricingError(#[from] PricingError),
+    #[error("tariff error: {0}")]
+    TariffError(#[from] TariffError),
+    #[error("ticket {0} cannot go from {1} to {2}")]
+    IllegalTicketTransitionError(String, TicketStatus, TicketStatus),
+    #[error("payment of ticket {0} is in progress")]
+    TicketPaymentPendingError(String),
+    #[error("reservation error: {0}")]
+    ReservationError(#[from] ReservationError),
+    #[error("reservation {0} cannot go from {1} to {2}")]
+    IllegalReservationTransitionError(String, ReservationStatus, ReservationStatus),
+    #[error("reservation conflict: {0}")]
+    ReservationConflictError(String),
+    #[error("pass conflict: {0}")]
+    PassConflictError(String),
+    #[error("parking lot {0} is archived")]
+    ParkingLotArchivedError(String),
+    #[error("parking lot in use: {0}")]
+    ParkingLotInUseError(String),
+    #[error("parking space in use: {0}")]
+    ParkingSpaceInUseError(String),
+    #[error("layout error: {0}")]
+    LayoutError(#[from] LayoutError),
+    #[error("location error: {0}")]
+    GeoError(#[from] GeoError),
+    #[error("plate read error: {0}")]
+    AnprError(#[from] AnprError),
+    #[error("plate read {0} cannot go from {1} to {2}")]
+    IllegalAnprTransitionError(String, AnprReadStatus, AnprReadStatus),
 }
 
 #[derive(Serialize)]
@@ -119,7 +166,7 @@ impl Into<(axum::http::StatusCode, Json<serde_json::Value>)> for MyError {
                 },
             ),
             MyError::InvalidCodeError(code) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_REQUEST,
                 ErrorResponse {
                     status: "400",
                     message: format!("invalid code: {}", code),
@@ -160,6 +207,13 @@ impl Into<(axum::http::StatusCode, Json<serde_json::Value>)> for MyError {
                     message: format!("Vehicle not found: {}", message),
                 },
             ),
+            MyError::VehicleTypeMismatchError(license_plate_number, registered, requested) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    status: "400",
+                    message: format!("Vehicle {} is registered as {:?}, not {:?}", license_plate_number, registered, requested),
+                },
+            ),
             MyError::InvalidCredentialsError(message) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorResponse {
@@ -167,6 +221,153 @@ impl Into<(axum::http::StatusCode, Json<serde_json::Value>)> for MyError {
                     message: format!("Invalid credentials: {}", message),
                 },
             ),
+            MyError::UnauthorizedError(message) => (
+                StatusCode::UNAUTHORIZED,
+                ErrorResponse {
+                    status: "401",
+                    message: format!("Unauthorized: {}", message),
+                },
+            ),
+            MyError::ForbiddenError(user_id) => (
+                StatusCode::FORBIDDEN,
+                ErrorResponse {
+                    status: "403",
+                    message: format!("User {} is not allowed to access this resource", user_id),
+                },
+            ),
+            MyError::UserBlockedError(user_id) => (
+                StatusCode::FORBIDDEN,
+                ErrorResponse {
+                    status: "403",
+                    message: format!("User blocked: {}", user_id),
+                },
+            ),
+            MyError::TokenError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorResponse {
+                    status: "500",
+                    message: format!("token error: {}", e),
+                },
+            ),
+            MyError::ValidationError(message) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    status: "400",
+                    message: format!("validation error: {}", message),
+                },
+            ),
+            MyError::MoneyError(e) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    status: "400",
+                    message: format!("money error: {}", e),
+                },
+            ),
+            MyError::PricingError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorResponse {
+                    status: "500",
+                    message: format!("pricing error: {}", e),
+                },
+            ),
+            MyError::TariffError(e) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    status: "400",
+                    message: format!("tariff error: {}", e),
+                },
+            ),
+            MyError::IllegalTicketTransitionError(code, from, to) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    status: "409",
+                    message: format!("Ticket {} cannot go from {} to {}", code, from, to),
+                },
+            ),
+            MyError::TicketPaymentPendingError(code) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    status: "409",
+                    message: format!("Payment of ticket {} is in progress", code),
+                },
+            ),
+            MyError::ReservationError(e) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    status: "400",
+                    message: format!("reservation error: {}", e),
+                },
+            ),
+            MyError::IllegalReservationTransitionError(id, from, to) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    status: "409",
+                    message: format!("Reservation {} cannot go from {} to {}", id, from, to),
+                },
+            ),
+            MyError::ReservationConflictError(message) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    status: "409",
+                    message: format!("Reservation conflict: {}", message),
+                },
+            ),
+            MyError::PassConflictError(message) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    status: "409",
+                    message: format!("Pass conflict: {}", message),
+                },
+            ),
+            MyError::ParkingLotArchivedError(parking_lot_id) => (
+                StatusCode::GONE,
+                ErrorResponse {
+                    status: "410",
+                    message: format!("Parking lot {} is archived", parking_lot_id),
+                },
+            ),
+            MyError::ParkingLotInUseError(message) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    status: "409",
+                    message: format!("Parking lot in use: {}", message),
+                },
+            ),
+            MyError::ParkingSpaceInUseError(message) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    status: "409",
+                    message: format!("Parking space in use: {}", message),
+                },
+            ),
+            MyError::LayoutError(e) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    status: "400",
+                    message: format!("layout error: {}", e),
+                },
+            ),
+            MyError::GeoError(e) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    status: "400",
+                    message: format!("location error: {}", e),
+                },
+            ),
+            MyError::AnprError(e) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    status: "400",
+                    message: format!("plate read error: {}", e),
+                },
+            ),
+            MyError::IllegalAnprTransitionError(id, from, to) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    status: "409",
+                    message: format!("Plate read {} cannot go from {} to {}", id, from, to),
+                },
+            ),
         };
         (status, Json(serde_json::to_value(error_response).unwrap()))
     }
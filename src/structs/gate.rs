@@ -0,0 +1,24 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Which way the vehicles pass a gate.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateDirection {
+    Entry,
+    Exit,
+}
+
+impl fmt::Display for GateDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// What the barrier does with the vehicle in front of it. The message is
+/// shown on the gate display either way.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateAction {
+    Open,
+    Deny,
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use super::model::Location;
+
+/// Search radius in metres when none is given.
+pub const DEFAULT_RADIUS: f64 = 5_000.0;
+/// Widest search radius in metres.
+pub const MAX_RADIUS: f64 = 100_000.0;
+/// Most parking lots a nearby search returns.
+pub const MAX_NEARBY_LIMIT: i64 = 100;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum GeoError {
+    #[error("latitude {0} is not between -90 and 90")]
+    Latitude(f64),
+    #[error("longitude {0} is not between -180 and 180")]
+    Longitude(f64),
+    #[error("radius {0} is not between 0 and {MAX_RADIUS} metres")]
+    Radius(f64),
+}
+
+/// GeoJSON point, as a `2dsphere` index expects it: longitude first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GeoPoint {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub coordinates: [f64; 2],
+}
+
+impl GeoPoint {
+    pub fn new(latitude: f64, longitude: f64) -> Result<Self, GeoError> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(GeoError::Latitude(latitude));
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(GeoError::Longitude(longitude));
+        }
+
+        Ok(GeoPoint { kind: "Point".to_string(), coordinates: [longitude, latitude] })
+    }
+}
+
+impl TryFrom<&Location> for GeoPoint {
+    type Error = GeoError;
+
+    fn try_from(location: &Location) -> Result<Self, GeoError> {
+        GeoPoint::new(location.latitude, location.longitude)
+    }
+}
+
+pub fn validate_radius(radius: f64) -> Result<f64, GeoError> {
+    match radius > 0.0 && radius <= MAX_RADIUS {
+        true => Ok(radius),
+        false => Err(GeoError::Radius(radius)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_put_longitude_first() {
+        let point = GeoPoint::new(52.23, 21.01).unwrap();
+
+        assert_eq!(point.coordinates, [21.01, 52.23]);
+        assert_eq!(bson::to_document(&point).unwrap(), bson::doc! { "type": "Point", "coordinates": [21.01, 52.23] });
+    }
+
+    #[test]
+    fn coordinates_and_radius_are_checked() {
+        assert_eq!(GeoPoint::new(91.0, 0.0), Err(GeoError::Latitude(91.0)));
+        assert_eq!(GeoPoint::new(0.0, -180.5), Err(GeoError::Longitude(-180.5)));
+        assert_eq!(validate_radius(0.0), Err(GeoError::Radius(0.0)));
+        assert_eq!(validate_radius(DEFAULT_RADIUS), Ok(DEFAULT_RADIUS));
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{model::{Levels, SpotCategory}, vehicle_type::VehicleType};
+
+/// Largest request body carrying floor plans, in bytes: enough for a few
+/// thousand labelled spaces.
+pub const LAYOUT_BODY_LIMIT: usize = 256 * 1024;
+/// Most spaces a group of a floor plan may hold.
+pub const MAX_GROUP_SPACES: u32 = 1_000;
+/// Most spaces a level may hold.
+pub const MAX_LEVEL_SPACES: u32 = 5_000;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum LayoutError {
+    #[error("level {0} has no spaces")]
+    EmptyLevel(u32),
+    #[error("zone and row names may only hold letters and digits, not {0:?}")]
+    InvalidName(String),
+    #[error("{count} spaces were given {labels} labels")]
+    LabelCount { count: u32, labels: usize },
+    #[error("label {0:?} is used twice on level {1}")]
+    DuplicateLabel(String, u32),
+    #[error("a group may hold at most {MAX_GROUP_SPACES} spaces, not {0}")]
+    GroupTooLarge(u32),
+    #[error("level {0} may hold at most {MAX_LEVEL_SPACES} spaces")]
+    LevelTooLarge(u32),
+}
+
+/// Floor plan of one level: groups of spaces, optionally placed in zones and
+/// rows. This is the import format for parking lots built from a plan.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LevelLayout {
+    pub spots: Vec<SpotGroup>,
+}
+
+/// Spaces of one type next to each other. Without `labels` they are labelled
+/// `L<level>-<zone or type>-<number>`, numbered from 1 within the level.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpotGroup {
+    #[serde(default)]
+    pub zone: Option<String>,
+    #[serde(default)]
+    pub row: Option<String>,
+    #[serde(rename = "vehicleType")]
+    pub vehicle_type: VehicleType,
+    pub count: u32,
+    #[serde(default)]
+    pub category: SpotCategory,
+    #[serde(default)]
+    pub labels: Option<Vec<String>>,
+}
+
+/// Level of a new parking lot: either the number of spaces of each type, or
+/// a floor plan.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum LevelDescription {
+    Layout(LevelLayout),
+    Counts(Levels),
+}
+
+impl LevelDescription {
+    pub fn layout(&self) -> LevelLayout {
+        match self {
+            LevelDescription::Layout(layout) => layout.to_owned(),
+            LevelDescription::Counts(levels) => levels.into(),
+        }
+    }
+}
+
+impl From<&Levels> for LevelLayout {
+    fn from(levels: &Levels) -> Self {
+        let spots = levels
+            .counts()
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(vehicle_type, count)| SpotGroup {
+                zone: None,
+                row: None,
+                vehicle_type,
+                count,
+                category: SpotCategory::default(),
+                labels: None,
+            })
+            .collect();
+
+        LevelLayout { spots }
+    }
+}
+
+/// Space of a level as it will be built.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedSpot {
+    pub vehicle_type: VehicleType,
+    pub category: SpotCategory,
+    pub zone: Option<String>,
+    pub row: Option<String>,
+    pub label: String,
+}
+
+fn type_code(vehicle_type: VehicleType) -> &'static str {
+    match vehicle_type {
+        VehicleType::Motorcycle => "M",
+        VehicleType::Car => "C",
+        VehicleType::Electric => "E",
+        VehicleType::Van => "V",
+        VehicleType::Truck => "T",
+        VehicleType::Oversized => "O",
+    }
+}
+
+pub fn spot_label(no_level: u32, group: &str, number: u32) -> String {
+    format!("L{}-{}-{:03}", no_level, group, number)
+}
+
+fn check_name(name: &Option<String>) -> Result<Option<String>, LayoutError> {
+    match name {
+        Some(name) if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            Err(LayoutError::InvalidName(name.to_owned()))
+        }
+        name => Ok(name.as_ref().map(|name| name.to_ascii_uppercase())),
+    }
+}
+
+/// Spaces of level `no_level` in the order of the plan, each with a label
+/// unique on the level.
+pub fn plan_level(no_level: u32, layout: &LevelLayout) -> Result<Vec<PlannedSpot>, LayoutError> {
+    // sizes are checked before any space is planned
+    let mut total: u32 = 0;
+    for group in &layout.spots {
+        if group.count > MAX_GROUP_SPACES {
+            return Err(LayoutError::GroupTooLarge(group.count));
+        }
+        total += group.count;
+        if total > MAX_LEVEL_SPACES {
+            return Err(LayoutError::LevelTooLarge(no_level));
+        }
+    }
+
+    let mut planned: Vec<PlannedSpot> = Vec::new();
+    let mut numbers: HashMap<String, u32> = HashMap::new();
+    for group in &layout.spots {
+        let zone = check_name(&group.zone)?;
+        let row = check_name(&group.row)?;
+        if let Some(labels) = &group.labels {
+            if labels.len() != group.count as usize {
+                return Err(LayoutError::LabelCount { count: group.count, labels: labels.len() });
+            }
+        }
+
+        let prefix = zone.clone().unwrap_or_else(|| type_code(group.vehicle_type).to_string());
+        for index in 0..group.count as usize {
+            let label = match &group.labels {
+                Some(labels) => labels[index].trim().to_owned(),
+                None => {
+                    let number = numbers.entry(prefix.clone()).or_default();
+                    *number += 1;
+                    spot_label(no_level, &prefix, *number)
+                }
+            };
+            planned.push(PlannedSpot {
+                vehicle_type: group.vehicle_type,
+                category: group.category.to_owned(),
+                zone: zone.clone(),
+                row: row.clone(),
+                label,
+            });
+        }
+    }
+    if planned.is_empty() {
+        return Err(LayoutError::EmptyLevel(no_level));
+    }
+
+    let mut seen: HashSet<&str> = HashSet::new();
+    if let Some(spot) = planned.iter().find(|spot| spot.label.is_empty() || !seen.insert(&spot.label)) {
+        return Err(LayoutError::DuplicateLabel(spot.label.to_owned(), no_level));
+    }
+
+    Ok(planned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(zone: Option<&str>, vehicle_type: VehicleType, count: u32) -> SpotGroup {
+        SpotGroup {
+            zone: zone.map(str::to_string),
+            row: None,
+            vehicle_type,
+            count,
+            category: SpotCategory::Standard,
+            labels: None,
+        }
+    }
+
+    fn labels(no_level: u32, layout: &LevelLayout) -> Vec<String> {
+        plan_level(no_level, layout).unwrap().into_iter().map(|spot| spot.label).collect()
+    }
+
+    #[test]
+    fn counts_are_labelled_by_type_within_the_level() {
+        let levels = Levels { cars: 2, trucks: 1, motorcycles: 0, electric: 0, vans: 0, oversized: 0 };
+
+        assert_eq!(labels(2, &(&levels).into()), vec!["L2-C-001", "L2-C-002", "L2-T-001"]);
+    }
+
+    #[test]
+    fn zones_number_their_spaces_across_types() {
+        let layout = LevelLayout {
+            spots: vec![
+                group(Some("a"), VehicleType::Car, 2),
+                group(Some("a"), VehicleType::Electric, 1),
+                group(Some("B"), VehicleType::Car, 1),
+            ],
+        };
+
+        assert_eq!(labels(0, &layout), vec!["L0-A-001", "L0-A-002", "L0-A-003", "L0-B-001"]);
+    }
+
+    #[test]
+    fn given_labels_are_kept_and_must_be_unique() {
+        let mut named = group(None, VehicleType::Car, 2);
+        named.labels = Some(vec!["P1".to_string(), " P2 ".to_string()]);
+        assert_eq!(labels(1, &LevelLayout { spots: vec![named.clone()] }), vec!["P1", "P2"]);
+
+        named.labels = Some(vec!["P1".to_string(), "P1".to_string()]);
+        assert_eq!(
+            plan_level(1, &LevelLayout { spots: vec![named.clone()] }),
+            Err(LayoutError::DuplicateLabel("P1".to_string(), 1))
+        );
+
+        named.labels = Some(vec!["P1".to_string()]);
+        assert_eq!(
+            plan_level(1, &LevelLayout { spots: vec![named] }),
+            Err(LayoutError::LabelCount { count: 2, labels: 1 })
+        );
+    }
+
+    #[test]
+    fn groups_and_levels_are_limited_in_size() {
+        assert_eq!(
+            plan_level(0, &LevelLayout { spots: vec![group(None, VehicleType::Car, u32::MAX)] }),
+            Err(LayoutError::GroupTooLarge(u32::MAX))
+        );
+
+        let full = vec![group(None, VehicleType::Car, MAX_GROUP_SPACES); (MAX_LEVEL_SPACES / MAX_GROUP_SPACES) as usize];
+        assert_eq!(plan_level(1, &LevelLayout { spots: full.clone() }).unwrap().len(), MAX_LEVEL_SPACES as usize);
+
+        let mut over = full;
+        over.push(group(None, VehicleType::Van, 1));
+        assert_eq!(plan_level(1, &LevelLayout { spots: over }), Err(LayoutError::LevelTooLarge(1)));
+    }
+
+    #[test]
+    fn levels_need_spaces_and_plain_zone_names() {
+        assert_eq!(plan_level(3, &LevelLayout { spots: vec![] }), Err(LayoutError::EmptyLevel(3)));
+        assert_eq!(
+            plan_level(0, &LevelLayout { spots: vec![group(Some("A-1"), VehicleType::Car, 1)] }),
+            Err(LayoutError::InvalidName("A-1".to_string()))
+        );
+    }
+}
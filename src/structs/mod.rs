@@ -1,5 +1,15 @@
 pub mod sample;
 pub mod model;
+pub mod money;
+pub mod ticket_status;
+pub mod reservation;
+pub mod pass;
+pub mod gate;
+pub mod anpr;
+pub mod access_code;
+pub mod geo;
+pub mod layout;
+pub mod vehicle_type;
 pub mod error;
 pub mod response;
 pub mod schema;
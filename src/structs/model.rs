@@ -1,10 +1,18 @@
+use chrono::{NaiveDate, Weekday};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use mongodb::bson::oid::ObjectId;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use super::{anpr::AnprReadStatus, geo::GeoPoint, gate::{GateAction, GateDirection}, money::{Money, Multiplier}, pass::PassPeriod, reservation::{ReservationPolicy, ReservationStatus}, ticket_status::TicketStatus};
+
+pub use super::vehicle_type::{Dimensions, VehicleType};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub enum Role {
     Admin,
+    #[default]
     User,
+    Operator, // owns parking lots and receives their ticket revenue
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -14,11 +22,47 @@ pub struct User {
     pub surname: String,
     pub email: String,
     pub password: String,
-    pub account_balance: f64,
+    pub account_balance: Money,
     pub role: Role,
     pub blocked: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub _id: ObjectId,
+    pub user_id: String,
+    pub refresh_token_hash: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub revoked: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum TransactionKind {
+    Deposit,
+    TicketCharge,
+    Refund,
+    Adjustment,
+    ReservationFee,
+    PassPurchase,
+}
+
+/// One leg of a double-entry transfer. Entries are never updated or deleted;
+/// the two legs of a transfer share `transfer_id` and their amounts sum to zero.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Transaction {
+    pub _id: ObjectId,
+    pub transfer_id: ObjectId,
+    pub account_id: String,
+    pub kind: TransactionKind,
+    pub amount: Money, // credit > 0, debit < 0
+    pub balance_after: Option<Money>, // None for system accounts
+    pub ticket_id: Option<String>,
+    pub parking_lot_id: Option<String>,
+    pub description: String,
+    pub created_at: i64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Ticket {
     pub _id: ObjectId,
@@ -27,11 +71,124 @@ pub struct Ticket {
     pub parking_spot_id: String,
     pub issue_timestamp: i64,
     pub end_timestamp: i64,
-    pub amount_paid: f64,
+    pub amount_paid: Money,
     pub level: u32,
     pub spot_ordinal_number: u32,
     pub parking_lot_id: String,
     pub code: String,
+    pub status: TicketStatus,
+}
+
+/// Subscription product of a parking lot. Retired plans are no longer sold
+/// or renewed; passes already bought stay valid.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PassPlan {
+    pub _id: ObjectId,
+    pub parking_lot_id: String,
+    pub name: String,
+    pub period: PassPeriod,
+    pub price: Money,
+    pub schedule: Schedule, // when the pass covers a stay, e.g. off-peak hours only
+    pub rate: Multiplier, // share of the normal price paid for covered hours, 0 is free
+    pub reserved_spot: bool,
+    pub active: bool,
+    pub created_at: i64,
+}
+
+/// A pass bought for one vehicle, valid from `valid_from` until `valid_until`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pass {
+    pub _id: ObjectId,
+    pub plan_id: String,
+    pub user_id: String,
+    pub parking_lot_id: String,
+    pub vehicle_license_number: String,
+    pub valid_from: i64,
+    pub valid_until: i64,
+    pub parking_spot_id: Option<String>, // kept for the vehicle on reserved-spot plans
+    pub auto_renew: bool,
+    pub created_at: i64,
+}
+
+/// A barrier controller of a parking lot. It authenticates with an API key
+/// of which only the hash is stored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GateDevice {
+    pub _id: ObjectId,
+    pub parking_lot_id: String,
+    pub name: String,
+    pub direction: GateDirection,
+    pub lane: String,
+    pub api_key_hash: String,
+    pub active: bool,
+    pub created_at: i64,
+}
+
+/// A vehicle at a gate and what the barrier was told to do, kept for audit.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GateEvent {
+    pub _id: ObjectId,
+    pub gate_id: String,
+    pub parking_lot_id: String,
+    pub direction: GateDirection,
+    pub vehicle_license_number: Option<String>,
+    pub ticket_code: Option<String>, // given by the device or issued on entry
+    pub action: GateAction,
+    pub message: String,
+    pub timestamp: i64,
+}
+
+/// A licence plate read by a camera at a gate. `matched_plate` is the known
+/// plate the read was taken for, by the backend or an attendant.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnprRead {
+    pub _id: ObjectId,
+    pub gate_id: String,
+    pub parking_lot_id: String,
+    pub camera_id: String,
+    pub plate: String, // as read by the camera
+    pub normalized_plate: String,
+    pub confidence: f64,
+    pub image_ref: Option<String>,
+    pub captured_at: i64,
+    pub matched_plate: Option<String>,
+    pub status: AnprReadStatus,
+    pub reason: Option<String>, // why the read waits for review
+    pub event_id: Option<String>, // gate event of the read once acted on
+    pub reviewed_by: Option<String>,
+    pub created_at: i64,
+}
+
+/// Code people type to find a parking lot, for the whole lot or for one of
+/// its entrances. Revoked and expired codes no longer find the lot.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccessCode {
+    pub _id: ObjectId,
+    pub code: String, // stored normalized, unique
+    pub parking_lot_id: String,
+    pub gate_id: Option<String>, // entry gate the code is posted at
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+/// A parking space kept for a vehicle from `start` to `end`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Reservation {
+    pub _id: ObjectId,
+    pub user_id: String,
+    pub parking_lot_id: String,
+    pub vehicle_license_number: String,
+    pub vehicle_type: VehicleType,
+    pub parking_spot_id: String,
+    pub level: u32,
+    pub spot_ordinal_number: u32,
+    pub start: i64,
+    pub end: i64,
+    pub status: ReservationStatus,
+    pub ticket_code: Option<String>, // set on check-in
+    pub fee: Option<Money>, // cancellation or no-show fee charged
+    pub created_at: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -40,13 +197,82 @@ pub struct ParkingLot {
     pub cost_of_maintenance: CostOfMaintenance,
     pub location: Location,
     pub no_levels: u32,
+    pub operator_id: String,
+    #[serde(default)]
+    pub pricing_policy: PricingPolicy,
+    #[serde(default)]
+    pub calendar: Calendar,
+    #[serde(default)]
+    pub allocation: AllocationStrategy,
+    #[serde(default)]
+    pub reservation_policy: ReservationPolicy,
+    #[serde(default)]
+    pub archived_at: Option<i64>, // archived lots take no new vehicles, their history stays readable
+    #[serde(default)]
+    pub geo: Option<GeoPoint>, // `location` as GeoJSON for the 2dsphere index
+}
+
+/// How a parking lot picks the space for an arriving vehicle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum AllocationStrategy {
+    /// The lowest level first, and the lowest space number on it.
+    #[default]
+    LowestLevel,
+    /// The fullest level with a free space, so that levels fill one by one.
+    FillLevel,
+    /// The emptiest level, spreading vehicles evenly.
+    SpreadLevels,
+    /// The space closest to an entrance or elevator at `space` on `level`;
+    /// spaces on its level come first.
+    Nearest { level: u32, space: u32 },
+}
+
+/// Local time and public holidays of a parking lot, used to pick tariffs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Calendar {
+    pub timezone: Tz,
+    #[serde(default)]
+    pub holidays: Vec<NaiveDate>,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Calendar {
+            timezone: Tz::Europe__Warsaw,
+            holidays: Vec::new(),
+        }
+    }
+}
+
+/// Lot-wide adjustments applied after the tariffs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PricingPolicy {
+    pub cap: Option<Money>, // per 24 hours of a stay
+    pub minimum: Option<Money>,
+    #[serde(default)]
+    pub rounding: Rounding,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Rounding {
+    pub mode: RoundingMode,
+    pub step: i64, // minor units
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum RoundingMode {
+    #[default]
+    None,
+    Up,
+    Down,
+    Nearest,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CostOfMaintenance {
-    pub electricity: f64,
-    pub cleaning: f64,
-    pub security: f64,
+    pub electricity: Money,
+    pub cleaning: Money,
+    pub security: Money,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -61,6 +287,28 @@ pub struct Location {
 pub struct Levels {
     pub cars: u32,
     pub trucks: u32,
+    #[serde(default)]
+    pub motorcycles: u32,
+    #[serde(default)]
+    pub electric: u32,
+    #[serde(default)]
+    pub vans: u32,
+    #[serde(default)]
+    pub oversized: u32,
+}
+
+impl Levels {
+    /// Number of spaces of each type on the level.
+    pub fn counts(&self) -> [(VehicleType, u32); 6] {
+        [
+            (VehicleType::Motorcycle, self.motorcycles),
+            (VehicleType::Car, self.cars),
+            (VehicleType::Electric, self.electric),
+            (VehicleType::Van, self.vans),
+            (VehicleType::Truck, self.trucks),
+            (VehicleType::Oversized, self.oversized),
+        ]
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -72,6 +320,8 @@ pub struct Vehicle {
     pub brand: String,
     pub model: String,
     pub license_plate_number: String,
+    #[serde(default)]
+    pub dimensions: Option<Dimensions>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -81,13 +331,28 @@ pub struct ParkingSpace {
     pub location: ParkingLocation,
     pub vehicle_type: VehicleType,
     pub occupied: bool,
-    pub price_modifier: f64, // default 1.0
+    pub price_modifier: Multiplier, // default 1.0
+    #[serde(default)]
+    pub category: SpotCategory,
+    #[serde(default)]
+    pub retired: bool, // removed with its level; kept for the tickets that refer to it
+    #[serde(default)]
+    pub out_of_service: bool, // closed for maintenance, not handed out until reopened
+    #[serde(default)]
+    pub label: String, // e.g. L2-C-017, unique on the level and kept when the space is renumbered
+    #[serde(default)]
+    pub zone: Option<String>,
+    #[serde(default)]
+    pub row: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum VehicleType {
-    Car,
-    Truck,
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum SpotCategory {
+    #[default]
+    Standard,
+    Ev,
+    Accessible,
+    Premium,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -100,7 +365,69 @@ pub struct ParkingLocation {
 pub struct Tariff {
     pub _id: ObjectId,
     pub parking_lot_id: String,
+    #[serde(default)]
+    pub tariff_set_id: String,
     pub min_time: i64,
     pub max_time: i64,
-    pub price_per_hour: f64,
+    pub price_per_hour: Money,
+    #[serde(default)]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub scope: TariffScope,
+}
+
+/// A version of the tariffs of a parking lot. A stay is priced by the set
+/// with the latest `effective_from` not after its start, or by the first set
+/// when it started before any.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TariffSet {
+    pub _id: ObjectId,
+    pub parking_lot_id: String,
+    pub version: u32,
+    pub effective_from: i64,
+    pub created_at: i64,
+}
+
+/// Spots a tariff applies to. Empty lists do not restrict; a scoped tariff
+/// takes precedence over unscoped ones for the spots it covers.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TariffScope {
+    #[serde(default)]
+    pub vehicle_types: Vec<VehicleType>,
+    #[serde(default)]
+    pub spot_categories: Vec<SpotCategory>,
+}
+
+/// When a tariff applies, in the local time of its parking lot. Fields left
+/// empty do not restrict it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    pub hours: Option<HourRange>,
+    pub dates: Option<DateRange>,
+    #[serde(default)]
+    pub holidays: HolidayRule,
+}
+
+/// Hours of the day `from..to`; wraps past midnight when `from > to` (22 to 6).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HourRange {
+    pub from: u32,
+    pub to: u32,
+}
+
+/// Dates `from..=to`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum HolidayRule {
+    #[default]
+    Any,
+    Only,
+    Never,
 }
\ No newline at end of file
@@ -0,0 +1,242 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MoneyError {
+    #[error("currency mismatch: {0} and {1}")]
+    CurrencyMismatch(Currency, Currency),
+    #[error("amount out of range")]
+    Overflow,
+    #[error("invalid amount: {0}")]
+    Parse(String),
+}
+
+type Result<T> = std::result::Result<T, MoneyError>;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Currency {
+    #[default]
+    PLN,
+    EUR,
+    USD,
+}
+
+impl Currency {
+    /// Number of decimal places of the minor unit.
+    pub fn decimals(&self) -> u32 {
+        match self {
+            Currency::PLN | Currency::EUR | Currency::USD => 2,
+        }
+    }
+
+    fn minor_per_major(&self) -> i64 {
+        10_i64.pow(self.decimals())
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "PLN" => Ok(Currency::PLN),
+            "EUR" => Ok(Currency::EUR),
+            "USD" => Ok(Currency::USD),
+            _ => Err(MoneyError::Parse(s.to_owned())),
+        }
+    }
+}
+
+/// Exact amount of money, stored as an integer number of minor units
+/// (e.g. grosze) together with its currency.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub amount: i64,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: i64, currency: Currency) -> Self {
+        Money { amount, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money::new(0, currency)
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.amount > 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount < 0
+    }
+
+    pub fn abs(&self) -> Money {
+        Money::new(self.amount.abs(), self.currency)
+    }
+
+    pub fn checked_add(&self, other: Money) -> Result<Money> {
+        self.same_currency(&other)?;
+        let amount = self.amount.checked_add(other.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount, self.currency))
+    }
+
+    pub fn checked_sub(&self, other: Money) -> Result<Money> {
+        self.same_currency(&other)?;
+        let amount = self.amount.checked_sub(other.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount, self.currency))
+    }
+
+    pub fn checked_mul(&self, factor: i64) -> Result<Money> {
+        let amount = self.amount.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount, self.currency))
+    }
+
+    /// Multiplies by `numerator / denominator`, rounding half away from zero
+    /// to the nearest minor unit.
+    pub fn mul_ratio(&self, numerator: i64, denominator: i64) -> Result<Money> {
+        if denominator == 0 {
+            return Err(MoneyError::Overflow);
+        }
+        let product = self.amount as i128 * numerator as i128;
+        let negative = (product < 0) != (denominator < 0);
+        let (product, denominator) = (product.abs(), (denominator as i128).abs());
+        let quotient = (product + denominator / 2) / denominator;
+        let rounded = if negative { -quotient } else { quotient };
+        let amount = i64::try_from(rounded).map_err(|_| MoneyError::Overflow)?;
+        Ok(Money::new(amount, self.currency))
+    }
+
+    pub fn apply_modifier(&self, modifier: Multiplier) -> Result<Money> {
+        self.mul_ratio(modifier.0 as i64, Multiplier::SCALE)
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<()> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, other.currency));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_major = self.currency.minor_per_major();
+        let sign = if self.amount < 0 { "-" } else { "" };
+        let amount = self.amount.unsigned_abs();
+        write!(
+            f,
+            "{}{}.{:0width$} {}",
+            sign,
+            amount / per_major as u64,
+            amount % per_major as u64,
+            self.currency,
+            width = self.currency.decimals() as usize,
+        )
+    }
+}
+
+/// Parses `"12.34"` (default currency) or `"12.34 EUR"`.
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let value = parts.next().ok_or(MoneyError::Parse(s.to_owned()))?;
+        let currency = match parts.next() {
+            Some(currency) => currency.parse()?,
+            None => Currency::default(),
+        };
+        if parts.next().is_some() {
+            return Err(MoneyError::Parse(s.to_owned()));
+        }
+
+        let (negative, value) = match value.strip_prefix('-') {
+            Some(value) => (true, value),
+            None => (false, value),
+        };
+        let (major, minor) = value.split_once('.').unwrap_or((value, ""));
+        let decimals = currency.decimals() as usize;
+        let digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+        if !digits(major) || minor.len() > decimals || (!minor.is_empty() && !digits(minor)) {
+            return Err(MoneyError::Parse(s.to_owned()));
+        }
+
+        let major: i64 = major.parse().map_err(|_| MoneyError::Parse(s.to_owned()))?;
+        let minor: i64 = format!("{:0<width$}", minor, width = decimals)
+            .parse()
+            .map_err(|_| MoneyError::Parse(s.to_owned()))?;
+        let amount = major
+            .checked_mul(currency.minor_per_major())
+            .and_then(|amount| amount.checked_add(minor))
+            .ok_or(MoneyError::Overflow)?;
+
+        Ok(Money::new(if negative { -amount } else { amount }, currency))
+    }
+}
+
+/// Price multiplier in basis points: `Multiplier(10_000)` is 1.0, `Multiplier(12_500)` is 1.25.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Multiplier(pub u32);
+
+impl Multiplier {
+    pub const SCALE: i64 = 10_000;
+    pub const ONE: Multiplier = Multiplier(10_000);
+}
+
+impl Default for Multiplier {
+    fn default() -> Self {
+        Multiplier::ONE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pln(amount: i64) -> Money {
+        Money::new(amount, Currency::PLN)
+    }
+
+    #[test]
+    fn parses_and_formats_decimal_amounts() {
+        assert_eq!("12.34".parse::<Money>().unwrap(), pln(1234));
+        assert_eq!("12.3".parse::<Money>().unwrap(), pln(1230));
+        assert_eq!("7".parse::<Money>().unwrap(), pln(700));
+        assert_eq!("-0.05 EUR".parse::<Money>().unwrap(), Money::new(-5, Currency::EUR));
+        assert!("1.234".parse::<Money>().is_err());
+        assert!("1,5".parse::<Money>().is_err());
+        assert!("12 GBP".parse::<Money>().is_err());
+
+        assert_eq!(pln(1234).to_string(), "12.34 PLN");
+        assert_eq!(pln(-5).to_string(), "-0.05 PLN");
+    }
+
+    #[test]
+    fn arithmetic_requires_matching_currency() {
+        assert_eq!(pln(100).checked_add(pln(250)).unwrap(), pln(350));
+        assert_eq!(
+            pln(100).checked_add(Money::new(1, Currency::EUR)),
+            Err(MoneyError::CurrencyMismatch(Currency::PLN, Currency::EUR)),
+        );
+        assert_eq!(Money::new(i64::MAX, Currency::PLN).checked_add(pln(1)), Err(MoneyError::Overflow));
+    }
+
+    #[test]
+    fn ratios_round_half_away_from_zero() {
+        assert_eq!(pln(5).mul_ratio(1, 2).unwrap(), pln(3));
+        assert_eq!(pln(-5).mul_ratio(1, 2).unwrap(), pln(-3));
+        assert_eq!(pln(1000).apply_modifier(Multiplier(12_500)).unwrap(), pln(1250));
+        assert_eq!(pln(333).apply_modifier(Multiplier(3_333)).unwrap(), pln(111));
+    }
+}
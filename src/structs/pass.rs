@@ -0,0 +1,71 @@
+use chrono::{Duration, Months, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use super::model::Calendar;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// How long a pass lasts before it has to be renewed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassPeriod {
+    Weekly,
+    Monthly,
+}
+
+impl PassPeriod {
+    /// End of a period starting at `start`: seven days later, or the same local
+    /// time a calendar month later. Days missing from the next month are
+    /// clamped, so a pass bought on 31 January lasts until the end of February.
+    pub fn end(&self, start: i64, calendar: &Calendar) -> i64 {
+        match self {
+            PassPeriod::Weekly => start + 7 * SECONDS_PER_DAY,
+            PassPeriod::Monthly => {
+                let next = match calendar.local_time(start) {
+                    Some(local_time) => local_time + Months::new(1),
+                    None => return start + 31 * SECONDS_PER_DAY,
+                };
+                // a local time skipped by a clock change falls to the hour after it
+                calendar
+                    .timezone
+                    .from_local_datetime(&next)
+                    .earliest()
+                    .or_else(|| calendar.timezone.from_local_datetime(&(next + Duration::hours(1))).earliest())
+                    .map(|end| end.timestamp())
+                    .unwrap_or(start + 31 * SECONDS_PER_DAY)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn local(calendar: &Calendar, date: NaiveDate, hour: u32) -> i64 {
+        calendar
+            .timezone
+            .from_local_datetime(&date.and_hms_opt(hour, 0, 0).unwrap())
+            .unwrap()
+            .timestamp()
+    }
+
+    #[test]
+    fn weekly_passes_last_seven_days() {
+        assert_eq!(PassPeriod::Weekly.end(1_700_000_000, &Calendar::default()), 1_700_000_000 + 7 * SECONDS_PER_DAY);
+    }
+
+    #[test]
+    fn monthly_passes_end_on_the_same_local_time_next_month() {
+        let calendar = Calendar::default();
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+
+        // across the change to summer time the month is an hour shorter
+        let start = local(&calendar, date(3, 15), 8);
+        assert_eq!(PassPeriod::Monthly.end(start, &calendar), local(&calendar, date(4, 15), 8));
+
+        let start = local(&calendar, date(1, 31), 8);
+        assert_eq!(PassPeriod::Monthly.end(start, &calendar), local(&calendar, date(2, 29), 8));
+    }
+}
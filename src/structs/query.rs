@@ -1,5 +1,14 @@
 use serde::Deserialize;
 
+use super::{anpr::AnprReadStatus, geo, model::{SpotCategory, VehicleType}};
+
+#[derive(Deserialize)]
+pub struct QueryParkingLots {
+    /// Lists archived parking lots instead of the open ones.
+    #[serde(default)]
+    pub archived: bool,
+}
+
 #[derive(Deserialize)]
 pub struct QueryParkingLotCode {
     pub code: String,
@@ -40,7 +49,71 @@ fn default_level() -> i32 {
     -1
 }
 
+#[derive(Deserialize)]
+pub struct QueryQuote {
+    pub from: i64,
+    pub to: Option<i64>, // now when left out
+    #[serde(rename = "vehicleType")]
+    pub vehicle_type: VehicleType,
+    #[serde(default)]
+    pub category: SpotCategory,
+}
+
 #[derive(Deserialize)]
 pub struct UserBalance {
-    pub balance: f64,
-}
\ No newline at end of file
+    // decimal amount, optionally followed by the currency: `12.50` or `12.50 EUR`
+    pub balance: String,
+}
+
+#[derive(Deserialize)]
+pub struct QueryTransactions {
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub skip: u64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
+
+#[derive(Deserialize)]
+pub struct QueryNearbyParkingLots {
+    pub lat: f64,
+    pub lng: f64,
+    /// Metres from the point.
+    #[serde(default = "default_radius")]
+    pub radius: f64,
+    /// Counts free spaces this vehicle may use instead of all free spaces.
+    #[serde(rename = "vehicleType", default)]
+    pub vehicle_type: Option<VehicleType>,
+    #[serde(default = "default_nearby_limit")]
+    pub limit: i64,
+}
+
+fn default_radius() -> f64 {
+    geo::DEFAULT_RADIUS
+}
+
+fn default_nearby_limit() -> i64 {
+    20
+}
+
+#[derive(Deserialize)]
+pub struct QueryGateEvents {
+    #[serde(rename = "gateId")]
+    pub gate_id: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub skip: u64,
+}
+
+#[derive(Deserialize)]
+pub struct QueryAnprReads {
+    pub status: Option<AnprReadStatus>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub skip: u64,
+}
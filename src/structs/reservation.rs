@@ -0,0 +1,201 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::money::Money;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ReservationError {
+    #[error("a reservation must end after it starts")]
+    InvalidWindow,
+    #[error("a reservation cannot start in the past")]
+    StartsInPast,
+    #[error("reservations open {0} seconds ahead at most")]
+    TooFarAhead(i64),
+    #[error("check-in opens at {0}")]
+    TooEarly(i64),
+    #[error("check-in closed at {0}")]
+    Expired(i64),
+    #[error("{0} must not be negative")]
+    InvalidPolicy(&'static str),
+}
+
+/// Where a reservation is in its life. `Booked` and `CheckedIn` reservations
+/// keep their parking space for the reserved window.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationStatus {
+    Booked,
+    /// The vehicle arrived and got a ticket.
+    CheckedIn,
+    Cancelled,
+    /// The vehicle did not arrive in time.
+    NoShow,
+}
+
+impl ReservationStatus {
+    /// Reservations that keep their parking space.
+    pub const HOLDING: [ReservationStatus; 2] = [ReservationStatus::Booked, ReservationStatus::CheckedIn];
+
+    pub fn can_become(&self, next: ReservationStatus) -> bool {
+        use ReservationStatus::*;
+
+        matches!(
+            (self, next),
+            (Booked, CheckedIn | Cancelled | NoShow)
+                // a failed check-in or cancellation fee returns the reservation to where it was
+                | (CheckedIn | Cancelled, Booked)
+        )
+    }
+}
+
+impl fmt::Display for ReservationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// How a parking lot takes reservations. Times are in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReservationPolicy {
+    /// How long before the start the space is kept from other vehicles and
+    /// check-in opens.
+    pub hold_before: i64,
+    /// How long after the start a vehicle may still check in.
+    pub no_show_after: i64,
+    /// Cancelling later than this before the start costs `cancellation_fee`.
+    pub free_cancellation_before: i64,
+    pub cancellation_fee: Option<Money>,
+    pub no_show_fee: Option<Money>,
+    pub max_advance: i64,
+}
+
+impl Default for ReservationPolicy {
+    fn default() -> Self {
+        ReservationPolicy {
+            hold_before: 15 * 60,
+            no_show_after: 15 * 60,
+            free_cancellation_before: 60 * 60,
+            cancellation_fee: None,
+            no_show_fee: None,
+            max_advance: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl ReservationPolicy {
+    pub fn validate(&self) -> Result<(), ReservationError> {
+        let durations = [
+            ("hold_before", self.hold_before),
+            ("no_show_after", self.no_show_after),
+            ("free_cancellation_before", self.free_cancellation_before),
+            ("max_advance", self.max_advance),
+        ];
+        if let Some((name, _)) = durations.into_iter().find(|(_, duration)| *duration < 0) {
+            return Err(ReservationError::InvalidPolicy(name));
+        }
+        if self.cancellation_fee.is_some_and(|fee| fee.is_negative()) {
+            return Err(ReservationError::InvalidPolicy("cancellation_fee"));
+        }
+        if self.no_show_fee.is_some_and(|fee| fee.is_negative()) {
+            return Err(ReservationError::InvalidPolicy("no_show_fee"));
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_window(&self, start: i64, end: i64, now: i64) -> Result<(), ReservationError> {
+        if end <= start {
+            return Err(ReservationError::InvalidWindow);
+        }
+        if start < now {
+            return Err(ReservationError::StartsInPast);
+        }
+        if start > now + self.max_advance {
+            return Err(ReservationError::TooFarAhead(self.max_advance));
+        }
+
+        Ok(())
+    }
+
+    /// Whether a reservation starting at `start` keeps its space at `now`.
+    pub fn holds(&self, start: i64, now: i64) -> bool {
+        now >= start - self.hold_before
+    }
+
+    pub fn can_check_in(&self, start: i64, now: i64) -> Result<(), ReservationError> {
+        if !self.holds(start, now) {
+            return Err(ReservationError::TooEarly(start - self.hold_before));
+        }
+        if self.is_no_show(start, now) {
+            return Err(ReservationError::Expired(start + self.no_show_after));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_no_show(&self, start: i64, now: i64) -> bool {
+        now > start + self.no_show_after
+    }
+
+    /// Fee for cancelling at `now` a reservation starting at `start`.
+    pub fn cancellation_fee(&self, start: i64, now: i64) -> Option<Money> {
+        if now < start - self.free_cancellation_before {
+            return None;
+        }
+
+        self.cancellation_fee.filter(|fee| fee.is_positive())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReservationStatus::*;
+    use super::*;
+    use crate::structs::money::Currency;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[test]
+    fn only_booked_reservations_move_on() {
+        assert!(Booked.can_become(CheckedIn));
+        assert!(Booked.can_become(NoShow));
+        assert!(!NoShow.can_become(Booked));
+        assert!(!CheckedIn.can_become(Cancelled));
+    }
+
+    #[test]
+    fn windows_start_ahead_and_end_after_start() {
+        let policy = ReservationPolicy::default();
+        let now = 1_700_000_000;
+
+        assert_eq!(policy.validate_window(now + HOUR, now + 2 * HOUR, now), Ok(()));
+        assert_eq!(policy.validate_window(now + HOUR, now + HOUR, now), Err(ReservationError::InvalidWindow));
+        assert_eq!(policy.validate_window(now - 1, now + HOUR, now), Err(ReservationError::StartsInPast));
+        assert_eq!(
+            policy.validate_window(now + 31 * 24 * HOUR, now + 32 * 24 * HOUR, now),
+            Err(ReservationError::TooFarAhead(policy.max_advance)),
+        );
+    }
+
+    #[test]
+    fn check_in_opens_before_the_start_and_closes_after_it() {
+        let policy = ReservationPolicy::default();
+        let start = 1_700_000_000;
+
+        assert_eq!(policy.can_check_in(start, start - HOUR), Err(ReservationError::TooEarly(start - policy.hold_before)));
+        assert_eq!(policy.can_check_in(start, start - policy.hold_before), Ok(()));
+        assert_eq!(policy.can_check_in(start, start + policy.no_show_after), Ok(()));
+        assert!(policy.is_no_show(start, start + policy.no_show_after + 1));
+    }
+
+    #[test]
+    fn late_cancellations_cost_the_fee() {
+        let fee = Money::new(1_000, Currency::PLN);
+        let policy = ReservationPolicy { cancellation_fee: Some(fee), ..Default::default() };
+        let start = 1_700_000_000;
+
+        assert_eq!(policy.cancellation_fee(start, start - 2 * HOUR), None);
+        assert_eq!(policy.cancellation_fee(start, start - HOUR / 2), Some(fee));
+        assert_eq!(ReservationPolicy::default().cancellation_fee(start, start), None);
+    }
+}
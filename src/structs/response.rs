@@ -1,6 +1,10 @@
 use serde::Serialize;
 
-use super::model::{CostOfMaintenance, Location};
+use crate::pricing::calculator::LineItem;
+
+use super::{anpr::AnprReadStatus, gate::{GateAction, GateDirection}, pass::PassPeriod, reservation::{ReservationPolicy, ReservationStatus}, ticket_status::TicketStatus};
+
+use super::{model::{CostOfMaintenance, Location, TransactionKind, Role, PricingPolicy, Calendar, Schedule, TariffScope, SpotCategory, AllocationStrategy, Dimensions}, money::{Money, Multiplier}};
 
 #[derive(Serialize, Debug)]
 pub struct UserResponse {
@@ -9,13 +13,14 @@ pub struct UserResponse {
     pub surname: String,
     pub email: String,
     #[serde(rename = "accountBalance")]
-    pub account_balance: f64,
+    pub account_balance: Money,
     pub blocked: bool,
+    pub role: Role,
 }
 
 #[derive(Serialize, Debug)]
 pub struct UserBalance {
-    pub balance: f64,
+    pub balance: Money,
 }
 
 #[derive(Serialize, Debug)]
@@ -25,6 +30,26 @@ pub struct ParkingLotResponse {
     pub cost_of_maintance: CostOfMaintenance,
     pub location: Location,
     pub no_levels: u32,
+    #[serde(rename = "operatorId")]
+    pub operator_id: String,
+    #[serde(rename = "pricingPolicy")]
+    pub pricing_policy: PricingPolicy,
+    pub calendar: Calendar,
+    pub allocation: AllocationStrategy,
+    #[serde(rename = "reservationPolicy")]
+    pub reservation_policy: ReservationPolicy,
+    #[serde(rename = "archivedAt")]
+    pub archived_at: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NearbyParkingLotResponse {
+    #[serde(flatten)]
+    pub parking_lot: ParkingLotResponse,
+    /// Metres from the searched point.
+    pub distance: f64,
+    #[serde(rename = "freeSpaces")]
+    pub free_spaces: u64,
 }
 
 #[derive(Serialize, Debug)]
@@ -37,6 +62,7 @@ pub struct VehicleResponse {
     pub model: String,
     #[serde(rename = "licensePlateNumber")]
     pub license_plate_number: String,
+    pub dimensions: Option<Dimensions>,
 }
 
 #[derive(Serialize, Debug)]
@@ -53,13 +79,76 @@ pub struct TicketResponse {
     #[serde(rename = "endTimestamp")]
     pub end_timestamp: i64,
     #[serde(rename = "amountPaid")]
-    pub amount_paid: f64,
+    pub amount_paid: Money,
     pub level: u32,
     #[serde(rename = "spotOrdinalNumber")]
     pub spot_ordinal_number: u32,
     #[serde(rename = "parkingLotId")]
     pub parking_lot_id: String,
     pub code: String,
+    pub status: TicketStatus,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PassPlanResponse {
+    pub id: String,
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: String,
+    pub name: String,
+    pub period: PassPeriod,
+    pub price: Money,
+    pub schedule: Schedule,
+    pub rate: Multiplier,
+    #[serde(rename = "reservedSpot")]
+    pub reserved_spot: bool,
+    pub active: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PassResponse {
+    pub id: String,
+    #[serde(rename = "planId")]
+    pub plan_id: String,
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: String,
+    #[serde(rename = "vehicleLicenseNumber")]
+    pub vehicle_license_number: String,
+    #[serde(rename = "validFrom")]
+    pub valid_from: i64,
+    #[serde(rename = "validUntil")]
+    pub valid_until: i64,
+    #[serde(rename = "parkingSpotId")]
+    pub parking_spot_id: Option<String>,
+    #[serde(rename = "autoRenew")]
+    pub auto_renew: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReservationResponse {
+    pub id: String,
+    #[serde(rename = "userId")]
+    pub user_id: String,
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: String,
+    #[serde(rename = "vehicleLicenseNumber")]
+    pub vehicle_license_number: String,
+    #[serde(rename = "vehicleType")]
+    pub vehicle_type: String,
+    #[serde(rename = "parkingSpotId")]
+    pub parking_spot_id: String,
+    pub level: u32,
+    #[serde(rename = "spotOrdinalNumber")]
+    pub spot_ordinal_number: u32,
+    pub start: i64,
+    pub end: i64,
+    pub status: ReservationStatus,
+    #[serde(rename = "ticketCode")]
+    pub ticket_code: Option<String>,
+    pub fee: Option<Money>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
 }
 
 #[derive(Serialize, Debug)]
@@ -74,10 +163,12 @@ pub struct TicketUserResponse {
     #[serde(rename = "parkingLotId")]
     pub parking_lot_id: String,
     pub code: String,
+    pub status: TicketStatus,
 }
 
 #[derive(Serialize, Debug)]
 pub struct TariffResponse {
+    pub id: String,
     #[serde(rename = "parkingLotId")]
     pub parking_lot_id: String,
     #[serde(rename = "minTime")]
@@ -85,7 +176,9 @@ pub struct TariffResponse {
     #[serde(rename = "maxTime")]
     pub max_time: i64,
     #[serde(rename = "pricePerHour")]
-    pub price_per_hour: f64,
+    pub price_per_hour: Money,
+    pub schedule: Schedule,
+    pub scope: TariffScope,
 }
 
 #[derive(Serialize, Debug)]
@@ -100,31 +193,233 @@ pub struct ParkingSpaceResponse {
     pub vehicle_type: String,
     #[serde(rename = "isOccupied")]
     pub is_occupied: bool,
+    pub category: SpotCategory,
+    #[serde(rename = "priceModifier")]
+    pub price_modifier: Multiplier,
+    pub retired: bool,
+    #[serde(rename = "outOfService")]
+    pub out_of_service: bool,
+    pub label: String,
+    pub zone: Option<String>,
+    pub row: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct IncomeStatsResponse {
     pub stats: Vec<IncomeStats>,
-    pub today: f64,
-    pub now: f64,
+    pub today: Money,
+    pub now: Money,
 }
 
 #[derive(Serialize, Debug)]
 pub struct IncomeStats {
     pub month: String,
-    pub income: f64,
+    pub income: Money,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Default)]
 pub struct ParkingLotStatsResponse {
-    pub truck: ParkingLotStats,
+    pub no_level: u32,
+    pub motorcycle: ParkingLotStats,
     pub car: ParkingLotStats,
+    pub electric: ParkingLotStats,
+    pub van: ParkingLotStats,
+    pub truck: ParkingLotStats,
+    pub oversized: ParkingLotStats,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Default)]
 pub struct ParkingLotStats {
     #[serde(rename = "spotsOccupied")]
     pub spots_occupied: u32,
     #[serde(rename = "spotsFree")]
     pub spots_free: u32,
-}
\ No newline at end of file
+    #[serde(rename = "spotsOutOfService")]
+    pub spots_out_of_service: u32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct JwksResponse {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Jwk {
+    pub kty: String,
+    pub kid: String,
+    pub alg: String,
+    #[serde(rename = "use")]
+    pub key_use: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+}
+
+
+#[derive(Serialize, Debug)]
+pub struct AuthTokensResponse {
+    #[serde(rename = "accessToken")]
+    pub access_token: String,
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+    #[serde(rename = "expiresIn")]
+    pub expires_in: i64,
+}
+
+
+#[derive(Serialize, Debug)]
+pub struct TransactionResponse {
+    pub id: String,
+    #[serde(rename = "transferId")]
+    pub transfer_id: String,
+    pub kind: TransactionKind,
+    pub amount: Money,
+    #[serde(rename = "balanceAfter")]
+    pub balance_after: Option<Money>,
+    #[serde(rename = "ticketId")]
+    pub ticket_id: Option<String>,
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: Option<String>,
+    pub description: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BalanceReconciliationResponse {
+    #[serde(rename = "userId")]
+    pub user_id: String,
+    pub balance: Money,
+    #[serde(rename = "ledgerBalance")]
+    pub ledger_balance: Money,
+    pub difference: Money,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TariffSetResponse {
+    pub id: String,
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: String,
+    pub version: u32,
+    #[serde(rename = "effectiveFrom")]
+    pub effective_from: i64,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    pub tariffs: Vec<TariffResponse>,
+}
+
+/// Price of a stay broken down by tariff band and adjustment. Nothing is
+/// charged or stored when a quote is made.
+#[derive(Serialize, Debug)]
+pub struct QuoteResponse {
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: String,
+    pub from: i64,
+    pub to: i64,
+    #[serde(rename = "billableHours")]
+    pub billable_hours: i64,
+    pub items: Vec<LineItem>,
+    pub total: Money,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GateDeviceResponse {
+    pub id: String,
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: String,
+    pub name: String,
+    pub direction: GateDirection,
+    pub lane: String,
+    pub active: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AccessCodeResponse {
+    pub id: String,
+    pub code: String,
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: String,
+    #[serde(rename = "gateId")]
+    pub gate_id: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<i64>,
+    #[serde(rename = "revokedAt")]
+    pub revoked_at: Option<i64>,
+}
+
+/// Parking lot found by an access code, with the entrance the code is for.
+#[derive(Serialize, Debug)]
+pub struct ParkingLotByCodeResponse {
+    #[serde(flatten)]
+    pub parking_lot: ParkingLotResponse,
+    #[serde(rename = "gateId")]
+    pub gate_id: Option<String>,
+}
+
+/// A gate with its plain API key, returned once when the key is issued.
+#[derive(Serialize, Debug)]
+pub struct GateKeyResponse {
+    pub gate: GateDeviceResponse,
+    #[serde(rename = "apiKey")]
+    pub api_key: String,
+}
+
+/// What the barrier should do with the vehicle in front of it.
+#[derive(Serialize, Debug)]
+pub struct GateDecisionResponse {
+    #[serde(rename = "eventId")]
+    pub event_id: String,
+    pub action: GateAction,
+    pub message: String,
+    #[serde(rename = "ticketCode")]
+    pub ticket_code: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GateEventResponse {
+    pub id: String,
+    #[serde(rename = "gateId")]
+    pub gate_id: String,
+    pub direction: GateDirection,
+    #[serde(rename = "vehicleLicenseNumber")]
+    pub vehicle_license_number: Option<String>,
+    #[serde(rename = "ticketCode")]
+    pub ticket_code: Option<String>,
+    pub action: GateAction,
+    pub message: String,
+    pub timestamp: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AnprReadResponse {
+    pub id: String,
+    #[serde(rename = "gateId")]
+    pub gate_id: String,
+    #[serde(rename = "cameraId")]
+    pub camera_id: String,
+    pub plate: String,
+    #[serde(rename = "normalizedPlate")]
+    pub normalized_plate: String,
+    pub confidence: f64,
+    #[serde(rename = "imageRef")]
+    pub image_ref: Option<String>,
+    #[serde(rename = "capturedAt")]
+    pub captured_at: i64,
+    #[serde(rename = "matchedPlate")]
+    pub matched_plate: Option<String>,
+    pub status: AnprReadStatus,
+    pub reason: Option<String>,
+    /// What the barrier was told, once the read is acted on.
+    pub decision: Option<GateDecisionResponse>,
+}
@@ -1,15 +1,17 @@
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
-use super::model::{CostOfMaintenance, Location, Levels, ParkingLocation, VehicleType};
+use super::{model::{CostOfMaintenance, Location, ParkingLocation, VehicleType, Dimensions, TransactionKind, Role, PricingPolicy, Calendar, Schedule, TariffScope, SpotCategory, AllocationStrategy}, gate::GateDirection, layout::LevelDescription, money::{Money, Multiplier}, pass::PassPeriod, reservation::ReservationPolicy, ticket_status::TicketStatus};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreateUserSchema {
     pub name: String,
     pub surname: String,
     #[serde(rename = "accountBalance")]
-    pub account_balance: f64,
+    pub account_balance: Money,
     pub blocked: bool,
+    #[serde(default)]
+    pub role: Role,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -17,8 +19,32 @@ pub struct CreateParkingSchema {
     #[serde(rename = "costOfMaintenance")]
     pub cost_of_maintenance: CostOfMaintenance,
     pub location: Location,
-    pub levels: Vec<Levels>,
+    pub levels: Vec<LevelDescription>,
     pub tariffs: Vec<CreateTariffSchema>,
+    #[serde(rename = "operatorId")]
+    pub operator_id: String,
+    #[serde(default, rename = "pricingPolicy")]
+    pub pricing_policy: PricingPolicy,
+    #[serde(default)]
+    pub calendar: Calendar,
+    #[serde(default)]
+    pub allocation: AllocationStrategy,
+    #[serde(default, rename = "reservationPolicy")]
+    pub reservation_policy: ReservationPolicy,
+}
+
+/// Metadata of a parking lot; fields left out keep their value.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateParkingLotSchema {
+    #[serde(rename = "costOfMaintenance")]
+    pub cost_of_maintenance: Option<CostOfMaintenance>,
+    pub location: Option<Location>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetParkingLotOperatorSchema {
+    #[serde(rename = "operatorId")]
+    pub operator_id: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -26,6 +52,10 @@ pub struct CreateParkingSpaceSchema {
     pub parking_lot_id: ObjectId,
     pub location: ParkingLocation,
     pub vehicle_type: VehicleType,
+    pub category: SpotCategory,
+    pub label: String,
+    pub zone: Option<String>,
+    pub row: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -38,6 +68,8 @@ pub struct CreateVehicleSchema {
     pub model: String,
     #[serde(rename = "licensePlateNumber")]
     pub license_plate_number: String,
+    #[serde(default)]
+    pub dimensions: Option<Dimensions>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -48,6 +80,8 @@ pub struct CreateVehicleUserSchema {
     pub model: String,
     #[serde(rename = "licensePlateNumber")]
     pub license_plate_number: String,
+    #[serde(default)]
+    pub dimensions: Option<Dimensions>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -58,6 +92,9 @@ pub struct CreateTicketSchema {
     pub vehicle_license_number: String,
     #[serde(rename = "parkingLotId")]
     pub parking_lot_id: String,
+    /// Only needed for vehicles that are not registered.
+    #[serde(default, rename = "vehicleType")]
+    pub vehicle_type: Option<VehicleType>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -66,16 +103,74 @@ pub struct CreateTicketUserSchema {
     pub vehicle_license_number: String,
     #[serde(rename = "parkingLotId")]
     pub parking_lot_id: String,
+    /// Only needed for vehicles that are not registered.
+    #[serde(default, rename = "vehicleType")]
+    pub vehicle_type: Option<VehicleType>,
+    /// Level the driver would like to park on, if it has a free space.
+    #[serde(rename = "preferredLevel")]
+    pub preferred_level: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CreateTariffSchema {
     #[serde(rename = "minTime")]
     pub min_time: i64,
     #[serde(rename = "maxTime")]
     pub max_time: i64,
     #[serde(rename = "pricePerHour")]
-    pub price_per_hour: f64,
+    pub price_per_hour: Money,
+    #[serde(default)]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub scope: TariffScope,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateTariffSetSchema {
+    #[serde(rename = "effectiveFrom")]
+    pub effective_from: Option<i64>, // now when left out
+    pub tariffs: Vec<CreateTariffSchema>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetTicketStatusSchema {
+    pub status: TicketStatus,
+}
+
+/// Fields left out are not changed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateSpotPricingSchema {
+    pub category: Option<SpotCategory>,
+    #[serde(rename = "priceModifier")]
+    pub price_modifier: Option<Multiplier>,
+}
+
+/// Fields left out are not changed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateParkingSpaceSchema {
+    #[serde(rename = "outOfService")]
+    pub out_of_service: Option<bool>,
+    #[serde(rename = "vehicleType")]
+    pub vehicle_type: Option<VehicleType>,
+    #[serde(rename = "ordinalNumber")]
+    pub ordinal_number: Option<u32>,
+    pub label: Option<String>,
+    #[serde(flatten)]
+    pub pricing: UpdateSpotPricingSchema,
+}
+
+/// Changes applied to every space of a level. Fields left out are not changed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateLevelSpacesSchema {
+    #[serde(rename = "outOfService")]
+    pub out_of_service: Option<bool>,
+    #[serde(rename = "vehicleType")]
+    pub vehicle_type: Option<VehicleType>,
+    /// Renumbers the spaces of the level in their current order from this number on.
+    #[serde(rename = "firstOrdinalNumber")]
+    pub first_ordinal_number: Option<u32>,
+    #[serde(flatten)]
+    pub pricing: UpdateSpotPricingSchema,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -90,4 +185,109 @@ pub struct RegisterUserSchema {
 pub struct LoginUserSchema {
     pub email: String,
     pub password: String,
-}
\ No newline at end of file
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RefreshTokenSchema {
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateTransactionSchema {
+    pub kind: TransactionKind,
+    pub amount: Money,
+    #[serde(rename = "ticketId")]
+    pub ticket_id: Option<String>,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateReservationSchema {
+    #[serde(rename = "parkingLotId")]
+    pub parking_lot_id: String,
+    #[serde(rename = "vehicleLicenseNumber")]
+    pub vehicle_license_number: String,
+    #[serde(default, rename = "vehicleType")]
+    pub vehicle_type: Option<VehicleType>,
+    pub start: i64,
+    pub end: i64,
+    #[serde(default, rename = "preferredLevel")]
+    pub preferred_level: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatePassPlanSchema {
+    pub name: String,
+    pub period: PassPeriod,
+    pub price: Money,
+    #[serde(default)]
+    pub schedule: Schedule,
+    pub rate: Option<Multiplier>,
+    #[serde(default, rename = "reservedSpot")]
+    pub reserved_spot: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BuyPassSchema {
+    #[serde(rename = "planId")]
+    pub plan_id: String,
+    #[serde(rename = "vehicleLicenseNumber")]
+    pub vehicle_license_number: String,
+    #[serde(rename = "validFrom")]
+    pub valid_from: Option<i64>,
+    #[serde(default, rename = "autoRenew")]
+    pub auto_renew: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetPassAutoRenewSchema {
+    #[serde(rename = "autoRenew")]
+    pub auto_renew: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateGateSchema {
+    pub name: String,
+    pub direction: GateDirection,
+    #[serde(default)]
+    pub lane: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CreateAccessCodeSchema {
+    /// Entry gate the code is posted at; without it the code is for the whole lot.
+    #[serde(rename = "gateId", default)]
+    pub gate_id: Option<String>,
+    #[serde(rename = "expiresAt", default)]
+    pub expires_at: Option<i64>,
+}
+
+/// A vehicle at a gate, identified by its plate, its ticket or both.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GateEventSchema {
+    #[serde(rename = "vehicleLicenseNumber")]
+    pub vehicle_license_number: Option<String>,
+    #[serde(rename = "ticketCode")]
+    pub ticket_code: Option<String>,
+}
+
+/// A plate read sent by a camera.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AnprReadSchema {
+    pub plate: String,
+    pub confidence: f64,
+    #[serde(rename = "cameraId")]
+    pub camera_id: String,
+    #[serde(rename = "imageRef")]
+    pub image_ref: Option<String>,
+    pub timestamp: Option<i64>,
+}
+
+/// An attendant's verdict on a read in the review queue: the plate it shows,
+/// or none to reject it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewAnprReadSchema {
+    #[serde(rename = "vehicleLicenseNumber")]
+    pub vehicle_license_number: Option<String>,
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Where a ticket is in its life. A ticket holds its parking space until it
+/// is `Exited` or `Cancelled`, which are final.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TicketStatus {
+    /// Issued ahead of the vehicle entering.
+    Issued,
+    Parked,
+    /// The stay is priced and the charge is being posted.
+    PendingPayment,
+    Paid,
+    Exited,
+    Cancelled,
+    /// The vehicle is still parked but its ticket is lost; it is paid as usual.
+    Lost,
+}
+
+impl TicketStatus {
+    /// Tickets not yet paid for.
+    pub const ACTIVE: [TicketStatus; 4] = [
+        TicketStatus::Issued,
+        TicketStatus::Parked,
+        TicketStatus::PendingPayment,
+        TicketStatus::Lost,
+    ];
+
+    pub fn can_become(&self, next: TicketStatus) -> bool {
+        use TicketStatus::*;
+
+        matches!(
+            (self, next),
+            (Issued, Parked | Cancelled)
+                | (Parked, PendingPayment | Cancelled | Lost)
+                // a failed payment returns the ticket to where it was
+                | (PendingPayment, Paid | Parked | Lost)
+                | (Lost, PendingPayment)
+                | (Paid, Exited)
+        )
+    }
+}
+
+impl fmt::Display for TicketStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TicketStatus::{self, *};
+
+    const ALL: [TicketStatus; 7] = [Issued, Parked, PendingPayment, Paid, Exited, Cancelled, Lost];
+
+    #[test]
+    fn tickets_are_paid_before_they_exit() {
+        assert!(Parked.can_become(PendingPayment));
+        assert!(PendingPayment.can_become(Paid));
+        assert!(Paid.can_become(Exited));
+
+        assert!(!Parked.can_become(Paid));
+        assert!(!Parked.can_become(Exited));
+        assert!(!Lost.can_become(Paid));
+        assert!(!Paid.can_become(Cancelled));
+    }
+
+    #[test]
+    fn final_states_cannot_change() {
+        for next in ALL {
+            assert!(!Exited.can_become(next));
+            assert!(!Cancelled.can_become(next));
+        }
+        for status in ALL {
+            assert!(!status.can_become(status));
+        }
+    }
+
+    #[test]
+    fn active_tickets_can_still_be_paid() {
+        for status in TicketStatus::ACTIVE {
+            assert!(status.can_become(PendingPayment) || status.can_become(Parked) || status == PendingPayment);
+        }
+        assert!(!TicketStatus::ACTIVE.contains(&Paid));
+    }
+}
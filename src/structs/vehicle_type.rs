@@ -0,0 +1,158 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("invalid vehicle type: {0}")]
+pub struct InvalidVehicleType(pub String);
+
+/// Type of a vehicle, and of the parking spaces built for it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VehicleType {
+    Motorcycle,
+    Car,
+    /// Electric car; its spaces have a charger and are kept for electric cars.
+    Electric,
+    Van,
+    Truck,
+    Oversized,
+}
+
+/// Outer size in centimetres and weight in kilograms.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Dimensions {
+    pub length: u32,
+    pub width: u32,
+    pub height: u32,
+    pub weight: u32,
+}
+
+impl Dimensions {
+    pub fn fit_within(&self, limits: &Dimensions) -> bool {
+        self.length <= limits.length
+            && self.width <= limits.width
+            && self.height <= limits.height
+            && self.weight <= limits.weight
+    }
+}
+
+impl VehicleType {
+    pub const ALL: [VehicleType; 6] = [
+        VehicleType::Motorcycle,
+        VehicleType::Car,
+        VehicleType::Electric,
+        VehicleType::Van,
+        VehicleType::Truck,
+        VehicleType::Oversized,
+    ];
+
+    /// Spaces open to any vehicle small enough, smallest first.
+    const BY_SIZE: [VehicleType; 5] = [
+        VehicleType::Motorcycle,
+        VehicleType::Car,
+        VehicleType::Van,
+        VehicleType::Truck,
+        VehicleType::Oversized,
+    ];
+
+    /// Largest vehicle a space of this type takes; oversized spaces take any.
+    pub fn space_limits(&self) -> Option<Dimensions> {
+        let (length, width, height, weight) = match self {
+            VehicleType::Motorcycle => (250, 100, 200, 500),
+            VehicleType::Car => (500, 250, 200, 2_500),
+            VehicleType::Electric => (500, 250, 200, 3_000),
+            VehicleType::Van => (650, 250, 300, 3_500),
+            VehicleType::Truck => (1_200, 300, 400, 18_000),
+            VehicleType::Oversized => return None,
+        };
+
+        Some(Dimensions { length, width, height, weight })
+    }
+
+    /// Types of space a vehicle of this type may use, best fit first: its own
+    /// and the next larger one, so a motorcycle may take a car space but a car
+    /// never takes a motorcycle space.
+    pub fn space_types(&self) -> &'static [VehicleType] {
+        match self {
+            VehicleType::Motorcycle => &[VehicleType::Motorcycle, VehicleType::Car],
+            VehicleType::Car => &[VehicleType::Car, VehicleType::Van],
+            VehicleType::Electric => &[VehicleType::Electric, VehicleType::Car, VehicleType::Van],
+            VehicleType::Van => &[VehicleType::Van, VehicleType::Truck],
+            VehicleType::Truck => &[VehicleType::Truck, VehicleType::Oversized],
+            VehicleType::Oversized => &[VehicleType::Oversized],
+        }
+    }
+
+    /// Like `space_types`, keeping only spaces the vehicle fits. A vehicle too
+    /// large for all of them gets the smallest larger space it fits.
+    pub fn compatible_spaces(&self, dimensions: Option<&Dimensions>) -> Vec<VehicleType> {
+        let fits = |space_type: &VehicleType| match (dimensions, space_type.space_limits()) {
+            (Some(dimensions), Some(limits)) => dimensions.fit_within(&limits),
+            _ => true,
+        };
+
+        let compatible: Vec<VehicleType> = self.space_types().iter().copied().filter(fits).collect();
+        if !compatible.is_empty() {
+            return compatible;
+        }
+
+        Self::BY_SIZE
+            .iter()
+            .copied()
+            .skip_while(|space_type| !self.space_types().contains(space_type))
+            .find(fits)
+            .into_iter()
+            .collect()
+    }
+}
+
+impl fmt::Display for VehicleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl FromStr for VehicleType {
+    type Err = InvalidVehicleType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VehicleType::ALL
+            .into_iter()
+            .find(|vehicle_type| vehicle_type.to_string() == s)
+            .ok_or_else(|| InvalidVehicleType(s.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VehicleType::{self, *};
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for vehicle_type in VehicleType::ALL {
+            assert_eq!(vehicle_type.to_string().parse(), Ok(vehicle_type));
+        }
+        assert_eq!("Bus".parse::<VehicleType>(), Err(InvalidVehicleType("Bus".to_string())));
+    }
+
+    #[test]
+    fn smaller_vehicles_may_use_larger_spaces_only() {
+        assert_eq!(Motorcycle.compatible_spaces(None), vec![Motorcycle, Car]);
+        assert!(!Car.compatible_spaces(None).contains(&Motorcycle));
+        assert!(!Car.compatible_spaces(None).contains(&Electric));
+        assert_eq!(Electric.compatible_spaces(None), vec![Electric, Car, Van]);
+    }
+
+    #[test]
+    fn dimensions_rule_out_spaces() {
+        let tall_van = Dimensions { length: 600, width: 220, height: 280, weight: 3_000 };
+        assert_eq!(Car.compatible_spaces(Some(&tall_van)), vec![Van]);
+
+        let heavy_van = Dimensions { weight: 5_000, ..tall_van };
+        assert_eq!(Van.compatible_spaces(Some(&heavy_van)), vec![Truck]);
+
+        let long_truck = Dimensions { length: 1_800, width: 255, height: 400, weight: 30_000 };
+        assert_eq!(Car.compatible_spaces(Some(&long_truck)), vec![Oversized]);
+    }
+}
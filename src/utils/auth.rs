@@ -0,0 +1,142 @@
+use std::{marker::PhantomData, ops::Deref, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    Json,
+};
+
+use crate::{
+    structs::{error::MyError, model::{GateDevice, Role, User}},
+    AppState,
+};
+
+/// Authenticated caller, resolved from the `Authorization: Bearer <token>` header.
+///
+/// The token is decoded once and both its session and the user are re-read from
+/// the database, so a revoked session or a user blocked after the token was
+/// issued is rejected immediately.
+pub struct AuthUser {
+    pub user_id: String,
+    pub session_id: String,
+    pub user: User,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuthUser {
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        authenticate(parts, state).await.map_err(Into::into)
+    }
+}
+
+async fn authenticate(parts: &Parts, state: &AppState) -> Result<AuthUser, MyError> {
+    let authorization_header = parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .ok_or(MyError::UnauthorizedError("missing Authorization header".to_string()))?;
+
+    let claims = state
+        .jwt
+        .decode_token(authorization_header)
+        .map_err(|e| MyError::UnauthorizedError(e.to_string()))?;
+
+    state.db.get_active_session(&claims.sid).await?;
+
+    let user = state
+        .db
+        .get_user_by_id(&claims.sub)
+        .await
+        .map_err(|_| MyError::UnauthorizedError("unknown user".to_string()))?;
+
+    if user.blocked {
+        return Err(MyError::UserBlockedError(claims.sub));
+    }
+
+    Ok(AuthUser {
+        user_id: claims.sub,
+        session_id: claims.sid,
+        user,
+    })
+}
+
+/// Marker trait for the roles accepted by [`RequireRole`].
+pub trait RoleGuard {
+    fn allows(role: &Role) -> bool;
+}
+
+pub struct Admin;
+
+impl RoleGuard for Admin {
+    fn allows(role: &Role) -> bool {
+        matches!(role, Role::Admin)
+    }
+}
+
+/// Authenticated caller whose role is accepted by `R`, e.g. `RequireRole<Admin>`.
+pub struct RequireRole<R: RoleGuard> {
+    pub auth_user: AuthUser,
+    _role: PhantomData<R>,
+}
+
+impl<R: RoleGuard> Deref for RequireRole<R> {
+    type Target = AuthUser;
+
+    fn deref(&self) -> &AuthUser {
+        &self.auth_user
+    }
+}
+
+#[async_trait]
+impl<R> FromRequestParts<Arc<AppState>> for RequireRole<R>
+where
+    R: RoleGuard + Send + Sync,
+{
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let auth_user = AuthUser::from_request_parts(parts, state).await?;
+
+        if !R::allows(&auth_user.user.role) {
+            return Err(MyError::ForbiddenError(auth_user.user_id).into());
+        }
+
+        Ok(RequireRole {
+            auth_user,
+            _role: PhantomData,
+        })
+    }
+}
+
+/// Header gate devices send their API key in.
+pub const GATE_API_KEY_HEADER: &str = "x-api-key";
+
+/// Gate device resolved from its API key. Keys of deactivated gates are
+/// rejected.
+pub struct AuthGate {
+    pub gate: GateDevice,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuthGate {
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        authenticate_gate(parts, state).await.map_err(Into::into)
+    }
+}
+
+async fn authenticate_gate(parts: &Parts, state: &AppState) -> Result<AuthGate, MyError> {
+    let api_key = parts
+        .headers
+        .get(GATE_API_KEY_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .ok_or(MyError::UnauthorizedError(format!("missing {} header", GATE_API_KEY_HEADER)))?;
+
+    let gate = state.db.authenticate_gate(api_key).await?;
+
+    Ok(AuthGate { gate })
+}
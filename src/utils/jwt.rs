@@ -1,11 +1,18 @@
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use jsonwebtoken::{encode, Algorithm, EncodingKey, Header, DecodingKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rsa::{pkcs8::DecodePublicKey, traits::PublicKeyParts, RsaPublicKey};
 use serde::{Deserialize, Serialize};
 
-use crate::structs::model::Role;
+use crate::structs::{model::{self, Role}, response::{Jwk, JwksResponse}};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Claims {
     pub sub: String,
+    // id of the session the access token was issued for
+    pub sid: String,
     pub user: User,
     pub exp: usize,
 }
@@ -18,38 +25,304 @@ pub struct User {
     pub role: Role,
 }
 
-pub fn create_token(user_id: &str, user: User) -> String {
-    let expiration = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::minutes(60000))
-        .unwrap()
-        .timestamp();
+impl From<&model::User> for User {
+    fn from(user: &model::User) -> Self {
+        User {
+            name: user.name.to_owned(),
+            surname: user.surname.to_owned(),
+            email: user.email.to_owned(),
+            role: user.role.to_owned(),
+        }
+    }
+}
+
+/// A key accepted when verifying tokens, identified by the `kid` header.
+pub struct VerificationKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    decoding_key: DecodingKey,
+    // only asymmetric keys are published in the JWKS
+    jwk: Option<Jwk>,
+}
+
+impl VerificationKey {
+    pub fn from_secret(kid: &str, algorithm: Algorithm, secret: &[u8]) -> Self {
+        VerificationKey {
+            kid: kid.to_owned(),
+            algorithm,
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    pub fn from_public_pem(kid: &str, algorithm: Algorithm, pem: &[u8]) -> Result<Self, String> {
+        let pem_str = std::str::from_utf8(pem).map_err(|e| e.to_string())?;
+        let (decoding_key, jwk) = match algorithm {
+            Algorithm::RS256 => {
+                let public_key = RsaPublicKey::from_public_key_pem(pem_str).map_err(|e| e.to_string())?;
+                let jwk = Jwk {
+                    kty: "RSA".to_string(),
+                    kid: kid.to_owned(),
+                    alg: "RS256".to_string(),
+                    key_use: "sig".to_string(),
+                    n: Some(URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be())),
+                    e: Some(URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be())),
+                    crv: None,
+                    x: None,
+                    y: None,
+                };
+                (DecodingKey::from_rsa_pem(pem).map_err(|e| e.to_string())?, jwk)
+            }
+            Algorithm::ES256 => {
+                let public_key = p256::PublicKey::from_public_key_pem(pem_str).map_err(|e| e.to_string())?;
+                let point = public_key.to_encoded_point(false);
+                let jwk = Jwk {
+                    kty: "EC".to_string(),
+                    kid: kid.to_owned(),
+                    alg: "ES256".to_string(),
+                    key_use: "sig".to_string(),
+                    n: None,
+                    e: None,
+                    crv: Some("P-256".to_string()),
+                    x: point.x().map(|x| URL_SAFE_NO_PAD.encode(x)),
+                    y: point.y().map(|y| URL_SAFE_NO_PAD.encode(y)),
+                };
+                (DecodingKey::from_ec_pem(pem).map_err(|e| e.to_string())?, jwk)
+            }
+            _ => return Err(format!("unsupported asymmetric algorithm: {:?}", algorithm)),
+        };
+
+        Ok(VerificationKey {
+            kid: kid.to_owned(),
+            algorithm,
+            decoding_key,
+            jwk: Some(jwk),
+        })
+    }
+}
+
+/// Signing key plus every key currently accepted for verification.
+///
+/// Keys retired during a rotation stay in `verification_keys` until all tokens
+/// signed with them have expired.
+pub struct JwtKeys {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    verification_keys: Vec<VerificationKey>,
+    pub token_ttl: chrono::Duration,
+    pub refresh_token_ttl: chrono::Duration,
+}
+
+impl JwtKeys {
+    pub fn new(
+        kid: &str,
+        algorithm: Algorithm,
+        encoding_key: EncodingKey,
+        verification_keys: Vec<VerificationKey>,
+        token_ttl: chrono::Duration,
+        refresh_token_ttl: chrono::Duration,
+    ) -> Self {
+        JwtKeys {
+            kid: kid.to_owned(),
+            algorithm,
+            encoding_key,
+            verification_keys,
+            token_ttl,
+            refresh_token_ttl,
+        }
+    }
+
+    /// Loads the keys from the environment:
+    ///
+    /// - `JWT_ALGORITHM`: `HS256`, `HS384`, `HS512` (default), `RS256` or `ES256`
+    /// - `JWT_KEY_ID`: `kid` of the signing key (default `default`)
+    /// - `JWT_SECRET`: shared secret for the HS algorithms
+    /// - `JWT_PRIVATE_KEY_PATH` / `JWT_PUBLIC_KEY_PATH`: PEM key pair for RS256 and ES256
+    /// - `JWT_PREVIOUS_KEYS`: comma separated `kid:ALGORITHM:path` entries still accepted for
+    ///   verification, where `path` points to a public key PEM (or a secret file for HS keys)
+    /// - `JWT_TOKEN_TTL_MINUTES`: access token lifetime (default 15)
+    /// - `JWT_REFRESH_TOKEN_TTL_DAYS`: refresh token lifetime (default 30)
+    pub fn from_env() -> Self {
+        let algorithm = Algorithm::from_str(
+            &std::env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS512".to_string()),
+        )
+        .expect("Failed to parse `JWT_ALGORITHM` environment variable.");
+
+        let kid = std::env::var("JWT_KEY_ID").unwrap_or_else(|_| "default".to_string());
+
+        let token_ttl_minutes: i64 = std::env::var("JWT_TOKEN_TTL_MINUTES")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .expect("Failed to parse `JWT_TOKEN_TTL_MINUTES` environment variable.");
+
+        let refresh_token_ttl_days: i64 = std::env::var("JWT_REFRESH_TOKEN_TTL_DAYS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .expect("Failed to parse `JWT_REFRESH_TOKEN_TTL_DAYS` environment variable.");
+
+        let (encoding_key, current_key) = match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                let secret = std::env::var("JWT_SECRET")
+                    .expect("Failed to load `JWT_SECRET` environment variable.");
+                (
+                    EncodingKey::from_secret(secret.as_bytes()),
+                    VerificationKey::from_secret(&kid, algorithm, secret.as_bytes()),
+                )
+            }
+            Algorithm::RS256 | Algorithm::ES256 => {
+                let private_key = read_key_file("JWT_PRIVATE_KEY_PATH");
+                let public_key = read_key_file("JWT_PUBLIC_KEY_PATH");
+                let encoding_key = match algorithm {
+                    Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_key),
+                    _ => EncodingKey::from_ec_pem(&private_key),
+                }
+                .expect("Failed to parse `JWT_PRIVATE_KEY_PATH` key.");
+                (
+                    encoding_key,
+                    VerificationKey::from_public_pem(&kid, algorithm, &public_key)
+                        .expect("Failed to parse `JWT_PUBLIC_KEY_PATH` key."),
+                )
+            }
+            _ => panic!("Unsupported `JWT_ALGORITHM`: {:?}", algorithm),
+        };
+
+        let mut verification_keys = vec![current_key];
+        let previous_keys = std::env::var("JWT_PREVIOUS_KEYS").unwrap_or_default();
+        for entry in previous_keys.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let mut parts = entry.splitn(3, ':');
+            let (kid, algorithm, path) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(kid), Some(algorithm), Some(path)) => (kid, algorithm, path),
+                _ => panic!("Failed to parse `JWT_PREVIOUS_KEYS` entry: {}", entry),
+            };
+            let algorithm = Algorithm::from_str(algorithm)
+                .expect("Failed to parse `JWT_PREVIOUS_KEYS` algorithm.");
+            let key = std::fs::read(path)
+                .expect("Failed to read `JWT_PREVIOUS_KEYS` key file.");
+            let key = match algorithm {
+                Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 =>
+                    VerificationKey::from_secret(kid, algorithm, key.trim_ascii()),
+                _ => VerificationKey::from_public_pem(kid, algorithm, &key)
+                    .expect("Failed to parse `JWT_PREVIOUS_KEYS` key."),
+            };
+            verification_keys.push(key);
+        }
+
+        JwtKeys::new(
+            &kid,
+            algorithm,
+            encoding_key,
+            verification_keys,
+            chrono::Duration::minutes(token_ttl_minutes),
+            chrono::Duration::days(refresh_token_ttl_days),
+        )
+    }
+
+    pub fn create_token(&self, user_id: &str, session_id: &str, user: User) -> Result<String, jsonwebtoken::errors::Error> {
+        let expiration = chrono::Utc::now()
+            .checked_add_signed(self.token_ttl)
+            .unwrap()
+            .timestamp();
+
+        let claims = Claims {
+            sub: user_id.to_owned(),
+            sid: session_id.to_owned(),
+            user,
+            exp: expiration as usize,
+        };
+
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.to_owned());
+
+        encode(&header, &claims, &self.encoding_key)
+    }
 
-    let claims = Claims {
-        sub: user_id.to_owned(),
-        user: user,
-        exp: expiration as usize,
-    };
+    pub fn decode_token(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let token = token.trim_start_matches("Bearer ");
+        let header = jsonwebtoken::decode_header(token)?;
 
-    let token = encode(
-        &Header::new(Algorithm::HS512),
-        &claims,
-        &EncodingKey::from_secret("secret".as_ref()),
-    )
-    .unwrap();
+        // tokens issued before key ids were introduced carry no `kid`
+        let kid = header.kid.unwrap_or_else(|| self.kid.to_owned());
+        let key = self
+            .verification_keys
+            .iter()
+            .find(|key| key.kid == kid && key.algorithm == header.alg)
+            .ok_or(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat)?;
+
+        let token_data = jsonwebtoken::decode::<Claims>(
+            token,
+            &key.decoding_key,
+            &jsonwebtoken::Validation::new(key.algorithm),
+        )?;
+
+        Ok(token_data.claims)
+    }
+
+    pub fn jwks(&self) -> JwksResponse {
+        JwksResponse {
+            keys: self
+                .verification_keys
+                .iter()
+                .filter_map(|key| key.jwk.to_owned())
+                .collect(),
+        }
+    }
+}
 
-    token
+fn read_key_file(variable: &str) -> Vec<u8> {
+    let path = std::env::var(variable)
+        .unwrap_or_else(|_| panic!("Failed to load `{}` environment variable.", variable));
+    std::fs::read(&path)
+        .unwrap_or_else(|_| panic!("Failed to read key file from `{}`.", variable))
 }
 
-pub fn decode_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let token = token.replace("Bearer ", "");
-    let token_data = jsonwebtoken::decode::<Claims>(
-        &token,
-        &DecodingKey::from_secret("secret".as_ref()),
-        &jsonwebtoken::Validation::new(Algorithm::HS512),
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(kid: &str, secret: &str, previous: Vec<VerificationKey>) -> JwtKeys {
+        let mut verification_keys = vec![VerificationKey::from_secret(kid, Algorithm::HS512, secret.as_bytes())];
+        verification_keys.extend(previous);
+        JwtKeys::new(
+            kid,
+            Algorithm::HS512,
+            EncodingKey::from_secret(secret.as_bytes()),
+            verification_keys,
+            chrono::Duration::minutes(5),
+            chrono::Duration::days(1),
+        )
+    }
+
+    fn user() -> User {
+        User {
+            name: "Jan".to_string(),
+            surname: "Kowalski".to_string(),
+            email: "jan.kowalski@example.com".to_string(),
+            role: Role::User,
+        }
+    }
+
+    #[test]
+    fn token_round_trip() {
+        let keys = keys("current", "secret", vec![]);
+        let token = keys.create_token("user-id", "session-id", user()).unwrap();
 
-    match token_data {
-        Ok(data) => Ok(data.claims),
-        Err(e) => Err(e),
+        let claims = keys.decode_token(&format!("Bearer {}", token)).unwrap();
+        assert_eq!(claims.sub, "user-id");
+        assert_eq!(claims.sid, "session-id");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn previous_key_is_accepted_during_rotation() {
+        let old_keys = keys("old", "old-secret", vec![]);
+        let token = old_keys.create_token("user-id", "session-id", user()).unwrap();
+
+        let rotated = keys("new", "new-secret", vec![
+            VerificationKey::from_secret("old", Algorithm::HS512, b"old-secret"),
+        ]);
+        assert_eq!(rotated.decode_token(&token).unwrap().sub, "user-id");
+
+        let retired = keys("new", "new-secret", vec![]);
+        assert!(retired.decode_token(&token).is_err());
+    }
+}
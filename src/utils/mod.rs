@@ -1 +1,3 @@
-pub mod jwt;
\ No newline at end of file
+pub mod jwt;
+pub mod auth;
+pub mod secret;
\ No newline at end of file